@@ -1,32 +1,87 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use mongodb::{
-  bson::Document,
-  event::{command::CommandEventHandler, sdam::SdamEventHandler},
-  options::{ClientOptions, FindOptions, ServerAddress},
-  sync::{Client, Cursor},
+  bson::{Bson, Document},
+  event::{cmap::CmapEventHandler, command::CommandEventHandler, sdam::SdamEventHandler},
+  options::{ClientOptions, ServerAddress},
+  sync::Client,
 };
-use tauri::command;
+use tauri::{command, AppHandle};
 
 use crate::mongodb_events::{
-  CommandInfoHandler, ServerDescription, ServerInfoHandler, DATABASE_HEARTBEAT, DATABASE_TOPOLOGY,
+  CommandInfoHandler, DatabaseConnectionPool, DatabaseHeartbeat, DatabaseTopology,
+  DatabaseTopologyDescription, ServerInfoHandler, DATABASE_HEARTBEAT, DATABASE_TOPOLOGY,
   SERVER_METRIC,
 };
-use crate::{error::PError, model::DatabaseInformation};
 use crate::{
-  model::{AppArg, BsonType},
-  mongodb_events::FinishedCommandInfo,
+  codegen::{generate_types, GeneratedTypes, TypeLanguage},
+  current_op::{get_current_operations, kill_operation, CurrentOpFilter, CurrentOperation},
+  distribution::{get_value_distribution, DistributionOptions, ValueDistribution},
+  drift::{
+    compare_schemas, SchemaDrift, SchemaSnapshot, SchemaSnapshotSummary, DEFAULT_PRESENCE_THRESHOLD,
+  },
+  error::PError,
+  history::{
+    record_query, run_history_query, HistoryEntry, HistoryQuery, QueryOutput, DEFAULT_HISTORY_LIMIT,
+  },
+  model::{CollectionPage, DatabaseInformation},
+  pipeline::{preview_pipeline, PreviewOptions, StagePreview},
+  pipeline_lint::{lint_lookup_indexes, lint_pipeline, PipelineIssue},
+  profiler::{
+    analyze_profile_entries, find_profile_entries, get_profiling_status, set_profiling_status,
+    ProfileFilter, ProfilingStatus,
+  },
+  query::{aggregate_documents, count_documents, find_documents, AggregateQuery, FindQuery},
+  query_shape::QueryShapeStatistics,
+  replica_set::{
+    freeze_member, get_replica_set_config, get_replica_set_status, step_down_primary,
+    ReplicaSetConfig, ReplicaSetStatus,
+  },
+  saved_queries::{run_saved_query, SavedQuery},
+  schema::{analyze_documents, CollectionSchema, DEFAULT_SAMPLE_SIZE},
+  sharding::{
+    get_balancer_status, list_sharded_collections, list_shards, list_zones, BalancerStatus, Shard,
+    ShardedCollection, ZoneRange,
+  },
+  shell::{parse_shell_document, to_shell_syntax},
+  stats::{
+    get_collection_statistics, get_database_statistics, CollectionStatistics, DatabaseStatistics,
+  },
+  validation::{
+    apply_validator, find_validation_violations, get_validation_rules, preview_validator,
+    ValidationRules, ValidationViolations, ValidatorPreview, DEFAULT_REQUIRED_THRESHOLD,
+  },
+};
+use crate::{
+  model::AppArg,
+  mongodb_events::{
+    ConnectionPoolHandler, ConnectionPoolStatistics, FinishedCommandInfo, ServerHeartbeats,
+    TopologyEvent, CONNECTION_POOL,
+  },
+  prometheus::PrometheusExporter,
+  server_status::{ServerStatusSample, SERVER_STATUS},
+  store::{connection_profile, MetricsRollup, MAX_RETENTION_SECS},
 };
 
 #[command]
 pub async fn mongodb_connect(
+  app_handle: AppHandle,
   state: AppArg<'_>,
   url: String,
   port: u16,
-) -> Result<Document, PError> {
-  let sdam_handler: Arc<dyn SdamEventHandler> = Arc::new(ServerInfoHandler);
+  max_pool_size: Option<u32>,
+  min_pool_size: Option<u32>,
+) -> Result<BTreeMap<String, DatabaseInformation>, PError> {
+  // The new client reports its events as soon as it is created, so that the previous deployment
+  // is forgotten first rather than diffed against.
+  *DATABASE_TOPOLOGY.lock().unwrap() = DatabaseTopology::default();
+  *DATABASE_HEARTBEAT.lock().unwrap() = DatabaseHeartbeat::default();
+  *CONNECTION_POOL.lock().unwrap() = DatabaseConnectionPool::default();
+  let sdam_handler: Arc<dyn SdamEventHandler> = Arc::new(ServerInfoHandler { app_handle });
   let command_handler: Arc<dyn CommandEventHandler> = Arc::new(CommandInfoHandler);
+  let cmap_handler: Arc<dyn CmapEventHandler> = Arc::new(ConnectionPoolHandler);
   let client = Client::with_options(
     ClientOptions::builder()
       .hosts(vec![ServerAddress::Tcp {
@@ -35,16 +90,81 @@ pub async fn mongodb_connect(
       }])
       .sdam_event_handler(sdam_handler)
       .command_event_handler(command_handler)
+      .cmap_event_handler(cmap_handler)
+      .max_pool_size(max_pool_size)
+      .min_pool_size(min_pool_size)
       .build(),
   )?;
   let result = DatabaseInformation::from_client(&client)?;
   {
     let mut handle = state.client.lock().unwrap();
+    // Cleared with the client locked, so that a poll in flight of the previous client is dropped.
+    SERVER_STATUS.lock().unwrap().clear();
     *handle = Some(client)
   };
+  if let Some(metrics_store) = state.metrics_store.lock().unwrap().as_mut() {
+    metrics_store.set_profile(connection_profile(&url, port));
+  }
   Ok(result)
 }
 
+#[command]
+pub async fn mongodb_list_collections(
+  state: AppArg<'_>,
+  database_name: String,
+  name_only: bool,
+  with_sizes: bool,
+  page: i64,
+  per_page: i64,
+) -> Result<CollectionPage, PError> {
+  let (page, per_page) = CollectionPage::page_arguments(page, per_page)?;
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  CollectionPage::from_database(
+    client,
+    &database_name,
+    name_only,
+    with_sizes,
+    page,
+    per_page,
+  )
+}
+
+#[command]
+pub async fn mongodb_refresh_database(
+  state: AppArg<'_>,
+  database_name: String,
+  name_only: bool,
+  with_sizes: bool,
+  per_page: i64,
+) -> Result<Option<DatabaseInformation>, PError> {
+  let (_, per_page) = CollectionPage::page_arguments(0, per_page)?;
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  DatabaseInformation::refresh(client, &database_name, name_only, with_sizes, per_page)
+}
+
+#[command]
+pub async fn mongodb_get_collection_statistics(
+  state: AppArg<'_>,
+  database_name: String,
+  collection_name: String,
+) -> Result<CollectionStatistics, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  get_collection_statistics(client, &database_name, &collection_name)
+}
+
+#[command]
+pub async fn mongodb_get_database_statistics(
+  state: AppArg<'_>,
+  database_name: String,
+) -> Result<DatabaseStatistics, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  get_database_statistics(client, &database_name)
+}
+
 #[command]
 pub async fn mongodb_find_documents(
   state: AppArg<'_>,
@@ -56,20 +176,43 @@ pub async fn mongodb_find_documents(
   documents_projection: Document,
   documents_sort: Document,
 ) -> Result<Vec<Document>, PError> {
-  let handle = &*state.client.lock().unwrap();
-  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
-  let database = client.database(&database_name);
-  let collections = database.collection(&collection_name);
-  let find_options = FindOptions::builder()
-    .limit(per_page)
-    .skip((per_page * page) as u64)
-    .projection(documents_projection)
-    .sort(documents_sort)
-    .build();
-  let result = collections
-    .find(documents_filter, find_options)
-    .and_then(|cursor| cursor.collect::<Result<Vec<_>, _>>())?;
-  Ok(result)
+  let query = FindQuery {
+    filter: documents_filter,
+    projection: Some(documents_projection),
+    sort: Some(documents_sort),
+    collation: None,
+  };
+  let history_query = HistoryQuery::Find {
+    query: Box::new(query.clone()),
+    page,
+    per_page,
+  };
+  let start = Instant::now();
+  let result = {
+    let handle = &*state.client.lock().unwrap();
+    handle
+      .as_ref()
+      .ok_or(PError::ClientNotAvailable)
+      .and_then(|client| {
+        find_documents(
+          client,
+          &database_name,
+          &collection_name,
+          query,
+          page,
+          per_page,
+        )
+      })
+  };
+  record_query(
+    &state.query_history_store,
+    &database_name,
+    &collection_name,
+    &history_query,
+    start,
+    result.as_ref().map(|documents| documents.len() as u64),
+  );
+  result
 }
 
 #[command]
@@ -79,12 +222,28 @@ pub async fn mongodb_count_documents(
   collection_name: String,
   documents_filter: Document,
 ) -> Result<u64, PError> {
-  let handle = &*state.client.lock().unwrap();
-  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
-  let database = client.database(&database_name);
-  let collections = database.collection::<Document>(&collection_name);
-  let result = collections.count_documents(documents_filter, None)?;
-  Ok(result)
+  let history_query = HistoryQuery::Count {
+    filter: documents_filter.clone(),
+  };
+  let start = Instant::now();
+  let result = {
+    let handle = &*state.client.lock().unwrap();
+    handle
+      .as_ref()
+      .ok_or(PError::ClientNotAvailable)
+      .and_then(|client| {
+        count_documents(client, &database_name, &collection_name, documents_filter)
+      })
+  };
+  record_query(
+    &state.query_history_store,
+    &database_name,
+    &collection_name,
+    &history_query,
+    start,
+    result.as_ref().copied(),
+  );
+  result
 }
 
 #[command]
@@ -94,28 +253,300 @@ pub async fn mongodb_aggregate_documents(
   collection_name: String,
   stages: Vec<Document>,
 ) -> Result<Vec<Document>, PError> {
+  let query = AggregateQuery {
+    stages,
+    collation: None,
+  };
+  let history_query = HistoryQuery::Aggregate {
+    query: query.clone(),
+  };
+  let start = Instant::now();
+  let result = {
+    let handle = &*state.client.lock().unwrap();
+    handle
+      .as_ref()
+      .ok_or(PError::ClientNotAvailable)
+      .and_then(|client| aggregate_documents(client, &database_name, &collection_name, query))
+  };
+  record_query(
+    &state.query_history_store,
+    &database_name,
+    &collection_name,
+    &history_query,
+    start,
+    result.as_ref().map(|documents| documents.len() as u64),
+  );
+  result
+}
+
+#[command]
+pub async fn mongodb_search_query_history(
+  state: AppArg<'_>,
+  text: Option<String>,
+  pinned_only: bool,
+  limit: Option<usize>,
+) -> Result<Vec<HistoryEntry>, PError> {
+  let handle = &*state.query_history_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.search(
+    text.as_deref().filter(|text| !text.is_empty()),
+    pinned_only,
+    limit.unwrap_or(DEFAULT_HISTORY_LIMIT),
+  )
+}
+
+#[command]
+pub async fn mongodb_pin_query_history_entry(
+  state: AppArg<'_>,
+  id: i64,
+  pinned: bool,
+) -> Result<(), PError> {
+  let handle = &*state.query_history_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.set_pinned(id, pinned)
+}
+
+#[command]
+pub async fn mongodb_delete_query_history_entry(state: AppArg<'_>, id: i64) -> Result<(), PError> {
+  let handle = &*state.query_history_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.delete(id)
+}
+
+#[command]
+pub async fn mongodb_rerun_query_history_entry(
+  state: AppArg<'_>,
+  id: i64,
+) -> Result<QueryOutput, PError> {
+  let entry = {
+    let handle = &*state.query_history_store.lock().unwrap();
+    let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+    store.get(id)?
+  };
+  let start = Instant::now();
+  let result = {
+    let handle = &*state.client.lock().unwrap();
+    handle
+      .as_ref()
+      .ok_or(PError::ClientNotAvailable)
+      .and_then(|client| {
+        run_history_query(
+          client,
+          &entry.database_name,
+          &entry.collection_name,
+          entry.query.clone(),
+        )
+      })
+  };
+  record_query(
+    &state.query_history_store,
+    &entry.database_name,
+    &entry.collection_name,
+    &entry.query,
+    start,
+    result.as_ref().map(QueryOutput::result_count),
+  );
+  result
+}
+
+#[command]
+pub async fn mongodb_preview_pipeline(
+  state: AppArg<'_>,
+  database_name: String,
+  collection_name: String,
+  stages: Vec<Document>,
+  options: PreviewOptions,
+) -> Result<Vec<StagePreview>, PError> {
+  options.validate()?;
   let handle = &*state.client.lock().unwrap();
   let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
-  let database = client.database(&database_name);
-  let collections = database.collection::<Document>(&collection_name);
-  let result = collections
-    .aggregate(stages, None)
-    .and_then(|cursor| cursor.collect::<Result<Vec<Document>, _>>())?;
+  Ok(preview_pipeline(
+    client,
+    &database_name,
+    &collection_name,
+    stages,
+    &options,
+  ))
+}
+
+#[command]
+pub async fn mongodb_lint_pipeline(
+  state: AppArg<'_>,
+  database_name: String,
+  stages: Vec<Document>,
+) -> Result<Vec<PipelineIssue>, PError> {
+  let mut result = lint_pipeline(&stages);
+  // The indexes of the joined collections are only checked once connected.
+  let handle = &*state.client.lock().unwrap();
+  if let Some(client) = handle.as_ref() {
+    result.extend(lint_lookup_indexes(client, &database_name, &stages));
+    result.sort_by_key(|issue| issue.stage);
+  }
   Ok(result)
 }
 
 #[command]
-pub async fn mongodb_get_database_topology() -> Vec<ServerDescription> {
+pub async fn mongodb_list_saved_queries(
+  state: AppArg<'_>,
+  tag: Option<String>,
+) -> Result<Vec<SavedQuery>, PError> {
+  let handle = &*state.saved_query_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.list(tag.as_deref())
+}
+
+#[command]
+pub async fn mongodb_get_saved_query(
+  state: AppArg<'_>,
+  name: String,
+) -> Result<SavedQuery, PError> {
+  let handle = &*state.saved_query_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.get(&name)
+}
+
+#[command]
+pub async fn mongodb_save_query(
+  state: AppArg<'_>,
+  saved_query: SavedQuery,
+) -> Result<SavedQuery, PError> {
+  let handle = &*state.saved_query_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.save(saved_query)
+}
+
+#[command]
+pub async fn mongodb_delete_saved_query(state: AppArg<'_>, name: String) -> Result<(), PError> {
+  let handle = &*state.saved_query_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.delete(&name)
+}
+
+#[command]
+pub async fn mongodb_export_saved_queries(
+  state: AppArg<'_>,
+  names: Vec<String>,
+) -> Result<String, PError> {
+  let handle = &*state.saved_query_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.export(&names)
+}
+
+#[command]
+pub async fn mongodb_import_saved_queries(
+  state: AppArg<'_>,
+  content: String,
+  overwrite: bool,
+) -> Result<Vec<String>, PError> {
+  let handle = &*state.saved_query_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.import(&content, overwrite)
+}
+
+#[command]
+pub async fn mongodb_run_saved_query(
+  state: AppArg<'_>,
+  name: String,
+  arguments: HashMap<String, Bson>,
+  page: i64,
+  per_page: i64,
+) -> Result<Vec<Document>, PError> {
+  let saved_query = {
+    let handle = &*state.saved_query_store.lock().unwrap();
+    let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+    store.get(&name)?
+  };
+  let query = saved_query.resolve(arguments)?;
+  let history_query = query.to_history_query(page, per_page);
+  let start = Instant::now();
+  let result = {
+    let handle = &*state.client.lock().unwrap();
+    handle
+      .as_ref()
+      .ok_or(PError::ClientNotAvailable)
+      .and_then(|client| run_saved_query(client, &saved_query, query, page, per_page))
+  };
+  record_query(
+    &state.query_history_store,
+    &saved_query.database_name,
+    &saved_query.collection_name,
+    &history_query,
+    start,
+    result.as_ref().map(|documents| documents.len() as u64),
+  );
+  result
+}
+
+#[command]
+pub async fn mongodb_parse_shell_syntax(text: String) -> Result<Document, PError> {
+  parse_shell_document(&text)
+}
+
+#[command]
+pub async fn mongodb_format_shell_syntax(document: Document, pretty: bool) -> String {
+  to_shell_syntax(&Bson::Document(document), pretty)
+}
+
+#[command]
+pub async fn mongodb_get_database_topology() -> DatabaseTopologyDescription {
   let handle = &*DATABASE_TOPOLOGY.lock().unwrap();
   handle.get_database_topology()
 }
 
+#[command]
+pub async fn mongodb_get_topology_timeline(since: u64) -> Vec<TopologyEvent> {
+  let handle = &*DATABASE_TOPOLOGY.lock().unwrap();
+  handle.get_topology_timeline(since)
+}
+
 #[command]
 pub async fn mongodb_get_connection_heartbeat() -> Vec<(usize, usize)> {
   let handle = &*DATABASE_HEARTBEAT.lock().unwrap();
   handle.get_connection_heartbeat()
 }
 
+#[command]
+pub async fn mongodb_get_server_heartbeats() -> BTreeMap<String, ServerHeartbeats> {
+  let handle = &*DATABASE_HEARTBEAT.lock().unwrap();
+  handle.get_server_heartbeats()
+}
+
+#[command]
+pub async fn mongodb_get_connection_pools() -> BTreeMap<String, ConnectionPoolStatistics> {
+  let handle = &*CONNECTION_POOL.lock().unwrap();
+  handle.get_connection_pools()
+}
+
+#[command]
+pub async fn mongodb_get_server_status_series(
+  count: usize,
+) -> (Vec<ServerStatusSample>, Option<String>) {
+  let handle = &*SERVER_STATUS.lock().unwrap();
+  handle.get_server_status_series(count)
+}
+
+/// Returns the milliseconds between two `serverStatus` polls, `None` when polling is off.
+#[command]
+pub async fn mongodb_get_server_status_interval(state: AppArg<'_>) -> Option<u64> {
+  let handle = state.server_status_interval.lock().unwrap();
+  handle.map(|interval| interval.as_millis() as u64)
+}
+
+#[command]
+pub async fn mongodb_set_server_status_interval(
+  state: AppArg<'_>,
+  interval_millis: Option<u64>,
+) -> Result<(), PError> {
+  if interval_millis == Some(0) {
+    return Err(PError::InvalidArgument(
+      "the serverStatus interval must be positive".to_string(),
+    ));
+  }
+  let mut handle = state.server_status_interval.lock().unwrap();
+  *handle = interval_millis.map(Duration::from_millis);
+  Ok(())
+}
+
 #[command]
 pub async fn mongodb_get_commands_statistics_per_sec(count: usize) -> Vec<(usize, usize, usize)> {
   let handle = &*SERVER_METRIC.lock().unwrap();
@@ -128,33 +559,408 @@ pub async fn mongodb_n_slowest_commands(count: usize) -> Vec<FinishedCommandInfo
   handle.get_n_slowest_commands(count)
 }
 
+#[command]
+pub async fn mongodb_get_commands_by_shape() -> Vec<QueryShapeStatistics> {
+  let handle = &*SERVER_METRIC.lock().unwrap();
+  handle.get_commands_by_shape()
+}
+
+#[command]
+pub async fn mongodb_get_metrics_profiles(state: AppArg<'_>) -> Result<Vec<String>, PError> {
+  let handle = &*state.metrics_store.lock().unwrap();
+  let metrics_store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  metrics_store.get_profiles()
+}
+
+#[command]
+pub async fn mongodb_get_metrics_history(
+  state: AppArg<'_>,
+  profile: String,
+  from: u64,
+  until: u64,
+) -> Result<Vec<MetricsRollup>, PError> {
+  let handle = &*state.metrics_store.lock().unwrap();
+  let metrics_store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  metrics_store.get_rollups(&profile, from, until)
+}
+
+#[command]
+pub async fn mongodb_get_metrics_retention(state: AppArg<'_>) -> Result<u64, PError> {
+  let handle = &*state.metrics_store.lock().unwrap();
+  let metrics_store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  Ok(metrics_store.get_retention_secs())
+}
+
+#[command]
+pub async fn mongodb_set_metrics_retention(
+  state: AppArg<'_>,
+  retention_secs: u64,
+) -> Result<(), PError> {
+  if !(1..=MAX_RETENTION_SECS).contains(&retention_secs) {
+    return Err(PError::InvalidArgument(format!(
+      "the retention must be between 1 and {} seconds",
+      MAX_RETENTION_SECS
+    )));
+  }
+  let handle = &mut *state.metrics_store.lock().unwrap();
+  let metrics_store = handle.as_mut().ok_or(PError::StoreNotAvailable)?;
+  metrics_store.set_retention_secs(retention_secs)
+}
+
+#[command]
+pub async fn mongodb_start_prometheus_exporter(state: AppArg<'_>, port: u16) -> Result<(), PError> {
+  let handle = &mut *state.prometheus_exporter.lock().unwrap();
+  // Stops the previous listener, and waits for it, in case the same port is requested again.
+  *handle = None;
+  *handle = Some(PrometheusExporter::start(port)?);
+  Ok(())
+}
+
+#[command]
+pub async fn mongodb_stop_prometheus_exporter(state: AppArg<'_>) {
+  let handle = &mut *state.prometheus_exporter.lock().unwrap();
+  *handle = None;
+}
+
+#[command]
+pub async fn mongodb_get_prometheus_exporter_port(state: AppArg<'_>) -> Option<u16> {
+  let handle = &*state.prometheus_exporter.lock().unwrap();
+  handle.as_ref().map(|exporter| exporter.get_port())
+}
+
+#[command]
+pub async fn mongodb_get_current_operations(
+  state: AppArg<'_>,
+  filter: CurrentOpFilter,
+) -> Result<Vec<CurrentOperation>, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  get_current_operations(client, &filter)
+}
+
+#[command]
+pub async fn mongodb_kill_operation(
+  state: AppArg<'_>,
+  opid: Bson,
+  confirmed: bool,
+) -> Result<Document, PError> {
+  if !confirmed {
+    return Err(PError::ConfirmationRequired);
+  }
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  kill_operation(client, opid)
+}
+
+#[command]
+pub async fn mongodb_get_profiling_status(
+  state: AppArg<'_>,
+  database_name: String,
+) -> Result<ProfilingStatus, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  get_profiling_status(client, &database_name)
+}
+
+#[command]
+pub async fn mongodb_set_profiling_status(
+  state: AppArg<'_>,
+  database_name: String,
+  level: i32,
+  slowms: Option<i64>,
+) -> Result<ProfilingStatus, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  set_profiling_status(client, &database_name, level, slowms)
+}
+
+#[command]
+pub async fn mongodb_find_profile_entries(
+  state: AppArg<'_>,
+  database_name: String,
+  filter: ProfileFilter,
+  limit: i64,
+) -> Result<Vec<Document>, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  find_profile_entries(client, &database_name, &filter, limit)
+}
+
+#[command]
+pub async fn mongodb_analyze_profile_entries(
+  state: AppArg<'_>,
+  database_name: String,
+  filter: ProfileFilter,
+) -> Result<Vec<QueryShapeStatistics>, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  analyze_profile_entries(client, &database_name, &filter)
+}
+
+#[command]
+pub async fn mongodb_get_replica_set_status(state: AppArg<'_>) -> Result<ReplicaSetStatus, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  get_replica_set_status(client)
+}
+
+#[command]
+pub async fn mongodb_get_replica_set_config(state: AppArg<'_>) -> Result<ReplicaSetConfig, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  get_replica_set_config(client)
+}
+
+#[command]
+pub async fn mongodb_set_replica_set_write_mode(state: AppArg<'_>, enabled: bool) {
+  let mut handle = state.replica_set_write_mode.lock().unwrap();
+  *handle = enabled;
+}
+
+#[command]
+pub async fn mongodb_step_down_primary(
+  state: AppArg<'_>,
+  step_down_secs: i64,
+  secondary_catch_up_period_secs: Option<i64>,
+) -> Result<Document, PError> {
+  if !*state.replica_set_write_mode.lock().unwrap() {
+    return Err(PError::WriteModeDisabled);
+  }
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  step_down_primary(client, step_down_secs, secondary_catch_up_period_secs)
+}
+
+#[command]
+pub async fn mongodb_freeze_member(
+  state: AppArg<'_>,
+  host: String,
+  freeze_secs: i64,
+) -> Result<Document, PError> {
+  if !*state.replica_set_write_mode.lock().unwrap() {
+    return Err(PError::WriteModeDisabled);
+  }
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  freeze_member(client, host, freeze_secs)
+}
+
+#[command]
+pub async fn mongodb_list_shards(state: AppArg<'_>) -> Result<Vec<Shard>, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  list_shards(client)
+}
+
+#[command]
+pub async fn mongodb_list_sharded_collections(
+  state: AppArg<'_>,
+) -> Result<Vec<ShardedCollection>, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  list_sharded_collections(client)
+}
+
+#[command]
+pub async fn mongodb_get_balancer_status(state: AppArg<'_>) -> Result<BalancerStatus, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  get_balancer_status(client)
+}
+
+#[command]
+pub async fn mongodb_list_zones(state: AppArg<'_>) -> Result<Vec<ZoneRange>, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  list_zones(client)
+}
+
 #[command]
 pub async fn mongodb_analyze_documents(
   state: AppArg<'_>,
   database_name: String,
   collection_name: String,
   documents_filter: Document,
-) -> Result<Vec<(String, Vec<(BsonType, u64)>)>, PError> {
-  let handle = &*state.client.lock().unwrap();
-  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
-  let database = client.database(&database_name);
-  let collections = database.collection(&collection_name);
-  let find_options = FindOptions::builder().limit(1000).build();
-
-  let cursor: Cursor<Document> = collections.find(documents_filter, find_options)?;
-  let mut result: HashMap<String, HashMap<BsonType, u64>> = HashMap::default();
-  for document_cursor in cursor {
-    let document = document_cursor?;
-    for (document_key, document_value) in &document {
-      let document_value_bson_type = BsonType::from(document_value);
-      let entry: &mut HashMap<BsonType, u64> = result.entry(document_key.to_string()).or_default();
-      let eentry = entry.entry(document_value_bson_type).or_default();
-      *eentry = *eentry + 1;
-    }
+  sample_size: Option<i64>,
+) -> Result<CollectionSchema, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  analyze_documents(
+    client,
+    &database_name,
+    &collection_name,
+    documents_filter,
+    sample_size.unwrap_or(DEFAULT_SAMPLE_SIZE),
+  )
+}
+
+#[command]
+pub async fn mongodb_save_schema_snapshot(
+  state: AppArg<'_>,
+  name: String,
+  database_name: String,
+  collection_name: String,
+  documents_filter: Document,
+  sample_size: Option<i64>,
+) -> Result<SchemaSnapshotSummary, PError> {
+  let schema = {
+    let handle = &*state.client.lock().unwrap();
+    let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+    analyze_documents(
+      client,
+      &database_name,
+      &collection_name,
+      documents_filter,
+      sample_size.unwrap_or(DEFAULT_SAMPLE_SIZE),
+    )?
+  };
+  let snapshot = SchemaSnapshot::new(name, database_name, collection_name, schema);
+  let handle = &*state.schema_snapshot_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.save(&snapshot)?;
+  Ok(snapshot.summary())
+}
+
+#[command]
+pub async fn mongodb_list_schema_snapshots(
+  state: AppArg<'_>,
+) -> Result<Vec<SchemaSnapshotSummary>, PError> {
+  let handle = &*state.schema_snapshot_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.list()
+}
+
+#[command]
+pub async fn mongodb_delete_schema_snapshot(state: AppArg<'_>, id: String) -> Result<(), PError> {
+  let handle = &*state.schema_snapshot_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  store.delete(&id)
+}
+
+#[command]
+pub async fn mongodb_compare_schema_snapshots(
+  state: AppArg<'_>,
+  before_id: String,
+  after_id: String,
+  presence_threshold: Option<f64>,
+) -> Result<SchemaDrift, PError> {
+  let handle = &*state.schema_snapshot_store.lock().unwrap();
+  let store = handle.as_ref().ok_or(PError::StoreNotAvailable)?;
+  let before = store.load(&before_id)?;
+  let after = store.load(&after_id)?;
+  Ok(compare_schemas(
+    &before.schema,
+    &after.schema,
+    presence_threshold.unwrap_or(DEFAULT_PRESENCE_THRESHOLD),
+  ))
+}
+
+#[command]
+pub async fn mongodb_generate_types(
+  state: AppArg<'_>,
+  database_name: String,
+  collection_name: String,
+  documents_filter: Document,
+  sample_size: Option<i64>,
+  language: TypeLanguage,
+) -> Result<GeneratedTypes, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  let schema = analyze_documents(
+    client,
+    &database_name,
+    &collection_name,
+    documents_filter,
+    sample_size.unwrap_or(DEFAULT_SAMPLE_SIZE),
+  )?;
+  Ok(generate_types(&schema, &collection_name, language))
+}
+
+#[command]
+pub async fn mongodb_get_value_distribution(
+  state: AppArg<'_>,
+  database_name: String,
+  collection_name: String,
+  documents_filter: Document,
+  options: DistributionOptions,
+) -> Result<ValueDistribution, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  get_value_distribution(
+    client,
+    &database_name,
+    &collection_name,
+    documents_filter,
+    &options,
+  )
+}
+
+#[command]
+pub async fn mongodb_preview_validator(
+  state: AppArg<'_>,
+  database_name: String,
+  collection_name: String,
+  documents_filter: Document,
+  sample_size: Option<i64>,
+  required_threshold: Option<f64>,
+) -> Result<ValidatorPreview, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  preview_validator(
+    client,
+    &database_name,
+    &collection_name,
+    documents_filter,
+    sample_size.unwrap_or(DEFAULT_SAMPLE_SIZE),
+    required_threshold.unwrap_or(DEFAULT_REQUIRED_THRESHOLD),
+  )
+}
+
+#[command]
+pub async fn mongodb_get_validation_rules(
+  state: AppArg<'_>,
+  database_name: String,
+  collection_name: String,
+) -> Result<ValidationRules, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  get_validation_rules(client, &database_name, &collection_name)
+}
+
+#[command]
+pub async fn mongodb_find_validation_violations(
+  state: AppArg<'_>,
+  database_name: String,
+  collection_name: String,
+  validator: Document,
+  limit: i64,
+) -> Result<ValidationViolations, PError> {
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  find_validation_violations(client, &database_name, &collection_name, &validator, limit)
+}
+
+#[command]
+pub async fn mongodb_apply_validator(
+  state: AppArg<'_>,
+  database_name: String,
+  collection_name: String,
+  validator: Document,
+  validation_level: String,
+  validation_action: String,
+  confirmed: bool,
+) -> Result<Document, PError> {
+  if !confirmed {
+    return Err(PError::ConfirmationRequired);
   }
-  let r = result
-    .into_iter()
-    .map(|(k, v)| (k, v.into_iter().collect()))
-    .collect();
-  Ok(r)
+  let handle = &*state.client.lock().unwrap();
+  let client = handle.as_ref().ok_or(PError::ClientNotAvailable)?;
+  apply_validator(
+    client,
+    &database_name,
+    &collection_name,
+    validator,
+    &validation_level,
+    &validation_action,
+  )
 }
@@ -0,0 +1,625 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  model::BsonType,
+  schema::{CollectionSchema, FieldSchema, ARRAY_ELEMENTS},
+};
+
+const RUST_KEYWORDS: [&str; 47] = [
+  "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+  "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop",
+  "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return", "static",
+  "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use", "virtual",
+  "where", "while", "yield",
+];
+
+/// The keywords that cannot be written as raw identifiers.
+const RUST_PATH_KEYWORDS: [&str; 3] = ["crate", "self", "super"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TypeLanguage {
+  Rust,
+  TypeScript,
+  Go,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GeneratedTypes {
+  pub file_name: String,
+  pub content: String,
+}
+
+/// Splits `createdAt`, `created_at` or `Created-At` into `["created", "at"]`.
+fn words(name: &str) -> Vec<String> {
+  let mut result: Vec<String> = Vec::new();
+  let mut current = String::new();
+  let mut previous_lowercase = false;
+  for c in name.chars() {
+    if !c.is_ascii_alphanumeric() {
+      if !current.is_empty() {
+        result.push(std::mem::take(&mut current));
+      }
+      previous_lowercase = false;
+      continue;
+    }
+    if c.is_ascii_uppercase() && previous_lowercase {
+      result.push(std::mem::take(&mut current));
+    }
+    previous_lowercase = c.is_ascii_lowercase() || c.is_ascii_digit();
+    current.push(c.to_ascii_lowercase());
+  }
+  if !current.is_empty() {
+    result.push(current);
+  }
+  result
+}
+
+fn pascal_case(name: &str) -> String {
+  let result = words(name)
+    .iter()
+    .map(|word| {
+      let mut chars = word.chars();
+      match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+      }
+    })
+    .collect::<String>();
+  match result.chars().next() {
+    None => "Field".to_string(),
+    Some(first) if first.is_ascii_digit() => format!("F{}", result),
+    Some(_) => result,
+  }
+}
+
+fn snake_case(name: &str) -> String {
+  let result = words(name).join("_");
+  match result.chars().next() {
+    None => "field".to_string(),
+    Some(first) if first.is_ascii_digit() => format!("_{}", result),
+    Some(_) if RUST_PATH_KEYWORDS.contains(&result.as_str()) => format!("{}_", result),
+    Some(_) => result,
+  }
+}
+
+fn rust_identifier(name: String) -> String {
+  if RUST_KEYWORDS.contains(&name.as_str()) {
+    format!("r#{}", name)
+  } else {
+    name
+  }
+}
+
+/// Suffixes the names already taken by a previous one with an index, so that `createdAt` and
+/// `created_at` do not become the same field.
+fn deduplicate(names: Vec<String>) -> Vec<String> {
+  let mut taken = HashSet::new();
+  names
+    .into_iter()
+    .map(|name| {
+      let mut result = name.clone();
+      let mut suffix = 2;
+      while !taken.insert(result.clone()) {
+        result = format!("{}{}", name, suffix);
+        suffix += 1;
+      }
+      result
+    })
+    .collect()
+}
+
+/// Go spells the `id` initialism in capitals.
+fn go_case(name: &str) -> String {
+  let result = pascal_case(name);
+  match result.strip_suffix("Id") {
+    Some(prefix) => format!("{}ID", prefix),
+    None => result,
+  }
+}
+
+fn is_identifier(name: &str) -> bool {
+  let mut chars = name.chars();
+  matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$')
+    && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+enum TypeRef {
+  Bson(BsonType),
+  Struct(String),
+  /// `None` for the arrays that were always empty.
+  Array(Option<Box<TypeRef>>),
+  Nullable(Box<TypeRef>),
+  Union(String, Vec<TypeRef>),
+  Any,
+}
+
+struct FieldDefinition {
+  name: String,
+  type_ref: TypeRef,
+  optional: bool,
+}
+
+struct StructDefinition {
+  name: String,
+  fields: Vec<FieldDefinition>,
+}
+
+/// Turns the analyzed schema into language independent structs, in a stable order.
+struct TypeCollector<'a> {
+  fields: HashMap<&'a [String], &'a FieldSchema>,
+  children: HashMap<Option<&'a [String]>, Vec<&'a FieldSchema>>,
+  names: HashSet<String>,
+  structs: Vec<StructDefinition>,
+}
+
+impl<'a> TypeCollector<'a> {
+  fn new(schema: &'a CollectionSchema) -> Self {
+    let mut children: HashMap<Option<&[String]>, Vec<&FieldSchema>> = HashMap::new();
+    for field in &schema.fields {
+      children
+        .entry(field.parent_segments())
+        .or_default()
+        .push(field);
+    }
+    TypeCollector {
+      fields: schema
+        .fields
+        .iter()
+        .map(|f| (f.segments.as_slice(), f))
+        .collect(),
+      children,
+      names: HashSet::new(),
+      structs: Vec::new(),
+    }
+  }
+
+  fn unique_name(&mut self, name: String) -> String {
+    let mut result = name.clone();
+    let mut suffix = 2;
+    while !self.names.insert(result.clone()) {
+      result = format!("{}{}", name, suffix);
+      suffix += 1;
+    }
+    result
+  }
+
+  /// Adds the struct of the objects at `segments` and returns its name.
+  fn add_struct(&mut self, segments: Option<&[String]>, name: String, object_count: u64) -> String {
+    let name = self.unique_name(name);
+    // Reserves the position so that a struct comes before the structs of its fields.
+    let index = self.structs.len();
+    self.structs.push(StructDefinition {
+      name: name.clone(),
+      fields: Vec::new(),
+    });
+    let mut fields = Vec::new();
+    for field in self.children.get(&segments).cloned().unwrap_or_default() {
+      let field_name = field.key().to_string();
+      if field_name == ARRAY_ELEMENTS {
+        continue;
+      }
+      let (type_ref, nullable) =
+        self.resolve(field, format!("{}{}", name, pascal_case(&field_name)));
+      fields.push(FieldDefinition {
+        name: field_name,
+        type_ref,
+        optional: nullable || field.values < object_count,
+      });
+    }
+    self.structs[index].fields = fields;
+    name
+  }
+
+  /// Returns the type of the values of `field` and whether some of them are `null`.
+  fn resolve(&mut self, field: &FieldSchema, name: String) -> (TypeRef, bool) {
+    let mut nullable = false;
+    let mut variants = Vec::new();
+    for t in &field.types {
+      let variant = match t.bson_type {
+        BsonType::Null | BsonType::Undefined => {
+          nullable = true;
+          continue;
+        }
+        BsonType::Document => {
+          TypeRef::Struct(self.add_struct(Some(&field.segments), name.clone(), t.count))
+        }
+        BsonType::Array => {
+          let items_segments = field.items_segments();
+          let items = self
+            .fields
+            .get(items_segments.as_slice())
+            .copied()
+            .map(|items| {
+              let (items, nullable) = self.resolve(items, format!("{}Item", name));
+              let items = if nullable {
+                TypeRef::Nullable(Box::new(items))
+              } else {
+                items
+              };
+              Box::new(items)
+            });
+          TypeRef::Array(items)
+        }
+        bson_type => TypeRef::Bson(bson_type),
+      };
+      variants.push(variant);
+    }
+    let type_ref = match variants.len() {
+      0 => TypeRef::Any,
+      1 => variants.remove(0),
+      _ => TypeRef::Union(self.unique_name(format!("{}Value", name)), variants),
+    };
+    (type_ref, nullable)
+  }
+}
+
+fn rust_type(type_ref: &TypeRef) -> String {
+  match type_ref {
+    TypeRef::Bson(bson_type) => match bson_type {
+      BsonType::Double => "f64",
+      BsonType::String | BsonType::JavaScriptCode | BsonType::Symbol => "String",
+      BsonType::Boolean => "bool",
+      BsonType::Int32 => "i32",
+      BsonType::Int64 => "i64",
+      BsonType::ObjectId => "ObjectId",
+      BsonType::DateTime => "DateTime",
+      BsonType::Timestamp => "bson::Timestamp",
+      BsonType::Binary => "bson::Binary",
+      BsonType::Decimal128 => "bson::Decimal128",
+      BsonType::RegularExpression => "bson::Regex",
+      _ => "bson::Bson",
+    }
+    .to_string(),
+    TypeRef::Struct(name) | TypeRef::Union(name, _) => name.clone(),
+    TypeRef::Array(Some(items)) => format!("Vec<{}>", rust_type(items)),
+    TypeRef::Array(None) => "Vec<bson::Bson>".to_string(),
+    TypeRef::Nullable(type_ref) => format!("Option<{}>", rust_type(type_ref)),
+    TypeRef::Any => "bson::Bson".to_string(),
+  }
+}
+
+fn rust_variant_name(type_ref: &TypeRef) -> String {
+  match type_ref {
+    TypeRef::Bson(bson_type) => format!("{:?}", bson_type),
+    TypeRef::Struct(name) | TypeRef::Union(name, _) => name.clone(),
+    TypeRef::Array(_) => "Array".to_string(),
+    TypeRef::Nullable(_) | TypeRef::Any => "Bson".to_string(),
+  }
+}
+
+fn rust_unions<'a>(type_ref: &'a TypeRef, unions: &mut Vec<&'a TypeRef>) {
+  match type_ref {
+    TypeRef::Union(_, variants) => {
+      unions.push(type_ref);
+      for variant in variants {
+        rust_unions(variant, unions);
+      }
+    }
+    TypeRef::Array(Some(type_ref)) | TypeRef::Nullable(type_ref) => rust_unions(type_ref, unions),
+    _ => {}
+  }
+}
+
+/// The `bson` types used by their short name, the other ones are written with their path.
+fn rust_imports(type_ref: &TypeRef, imports: &mut Vec<&'static str>) {
+  match type_ref {
+    TypeRef::Bson(bson_type) => {
+      let import = match bson_type {
+        BsonType::ObjectId => "oid::ObjectId",
+        BsonType::DateTime => "DateTime",
+        _ => return,
+      };
+      if !imports.contains(&import) {
+        imports.push(import);
+      }
+    }
+    TypeRef::Union(_, variants) => {
+      for variant in variants {
+        rust_imports(variant, imports);
+      }
+    }
+    TypeRef::Array(Some(type_ref)) | TypeRef::Nullable(type_ref) => rust_imports(type_ref, imports),
+    _ => {}
+  }
+}
+
+fn render_rust(structs: &[StructDefinition]) -> String {
+  let mut imports = Vec::new();
+  for field in structs.iter().flat_map(|definition| &definition.fields) {
+    rust_imports(&field.type_ref, &mut imports);
+  }
+  // Ordered as by `rustfmt`, the modules first.
+  imports.sort_by_key(|import| (!import.contains("::"), *import));
+  let mut result = match imports.as_slice() {
+    [] => String::new(),
+    [import] => format!("use bson::{};\n", import),
+    imports => format!("use bson::{{{}}};\n", imports.join(", ")),
+  };
+  result.push_str("use serde::{Deserialize, Serialize};\n");
+  let mut unions = Vec::new();
+  for definition in structs {
+    result.push_str("\n#[derive(Debug, Clone, Serialize, Deserialize)]\n");
+    result.push_str(&format!("pub struct {} {{\n", definition.name));
+    let field_names = deduplicate(
+      definition
+        .fields
+        .iter()
+        .map(|field| snake_case(&field.name))
+        .collect(),
+    );
+    for (field, field_name) in definition.fields.iter().zip(field_names) {
+      let mut attributes = Vec::new();
+      if field_name != field.name {
+        attributes.push(format!("rename = {:?}", field.name));
+      }
+      let field_name = rust_identifier(field_name);
+      let mut type_name = rust_type(&field.type_ref);
+      if field.optional {
+        attributes.push("default, skip_serializing_if = \"Option::is_none\"".to_string());
+        type_name = format!("Option<{}>", type_name);
+      }
+      if !attributes.is_empty() {
+        result.push_str(&format!("  #[serde({})]\n", attributes.join(", ")));
+      }
+      result.push_str(&format!("  pub {}: {},\n", field_name, type_name));
+      rust_unions(&field.type_ref, &mut unions);
+    }
+    result.push_str("}\n");
+  }
+  for union in unions {
+    if let TypeRef::Union(name, variants) = union {
+      result.push_str("\n#[derive(Debug, Clone, Serialize, Deserialize)]\n#[serde(untagged)]\n");
+      result.push_str(&format!("pub enum {} {{\n", name));
+      for variant in variants {
+        result.push_str(&format!(
+          "  {}({}),\n",
+          rust_variant_name(variant),
+          rust_type(variant)
+        ));
+      }
+      result.push_str("}\n");
+    }
+  }
+  result
+}
+
+fn typescript_type(type_ref: &TypeRef, imports: &mut Vec<&'static str>) -> String {
+  match type_ref {
+    TypeRef::Bson(bson_type) => {
+      let (type_name, import) = match bson_type {
+        BsonType::Double | BsonType::Int32 | BsonType::Int64 => ("number", false),
+        BsonType::String | BsonType::JavaScriptCode | BsonType::Symbol => ("string", false),
+        BsonType::Boolean => ("boolean", false),
+        BsonType::DateTime => ("Date", false),
+        BsonType::RegularExpression => ("RegExp", false),
+        BsonType::ObjectId => ("ObjectId", true),
+        BsonType::Timestamp => ("Timestamp", true),
+        BsonType::Binary => ("Binary", true),
+        BsonType::Decimal128 => ("Decimal128", true),
+        _ => ("unknown", false),
+      };
+      if import && !imports.contains(&type_name) {
+        imports.push(type_name);
+      }
+      type_name.to_string()
+    }
+    TypeRef::Struct(name) => name.clone(),
+    TypeRef::Array(Some(items)) => match **items {
+      TypeRef::Union(..) | TypeRef::Nullable(_) => {
+        format!("({})[]", typescript_type(items, imports))
+      }
+      _ => format!("{}[]", typescript_type(items, imports)),
+    },
+    TypeRef::Array(None) => "unknown[]".to_string(),
+    TypeRef::Nullable(type_ref) => format!("{} | null", typescript_type(type_ref, imports)),
+    TypeRef::Union(_, variants) => {
+      let mut result: Vec<String> = Vec::new();
+      for variant in variants {
+        let variant = typescript_type(variant, imports);
+        if !result.contains(&variant) {
+          result.push(variant);
+        }
+      }
+      result.join(" | ")
+    }
+    TypeRef::Any => "unknown".to_string(),
+  }
+}
+
+fn render_typescript(structs: &[StructDefinition]) -> String {
+  let mut imports = Vec::new();
+  let mut body = String::new();
+  for definition in structs {
+    body.push_str(&format!("\nexport interface {} {{\n", definition.name));
+    for field in &definition.fields {
+      let field_name = if is_identifier(&field.name) {
+        field.name.clone()
+      } else {
+        format!("{:?}", field.name)
+      };
+      body.push_str(&format!(
+        "  {}{}: {};\n",
+        field_name,
+        if field.optional { "?" } else { "" },
+        typescript_type(&field.type_ref, &mut imports)
+      ));
+    }
+    body.push_str("}\n");
+  }
+  imports.sort_unstable();
+  if imports.is_empty() {
+    body.trim_start().to_string()
+  } else {
+    format!(
+      "import {{ {} }} from \"bson\";\n{}",
+      imports.join(", "),
+      body
+    )
+  }
+}
+
+fn go_type(type_ref: &TypeRef) -> String {
+  match type_ref {
+    TypeRef::Bson(bson_type) => match bson_type {
+      BsonType::Double => "float64",
+      BsonType::String | BsonType::JavaScriptCode | BsonType::Symbol => "string",
+      BsonType::Boolean => "bool",
+      BsonType::Int32 => "int32",
+      BsonType::Int64 => "int64",
+      BsonType::ObjectId => "primitive.ObjectID",
+      BsonType::DateTime => "primitive.DateTime",
+      BsonType::Timestamp => "primitive.Timestamp",
+      BsonType::Binary => "primitive.Binary",
+      BsonType::Decimal128 => "primitive.Decimal128",
+      BsonType::RegularExpression => "primitive.Regex",
+      _ => "interface{}",
+    }
+    .to_string(),
+    TypeRef::Struct(name) => name.clone(),
+    TypeRef::Array(Some(items)) => format!("[]{}", go_type(items)),
+    TypeRef::Array(None) => "[]interface{}".to_string(),
+    TypeRef::Nullable(type_ref) => match **type_ref {
+      TypeRef::Union(..) | TypeRef::Any => go_type(type_ref),
+      _ => format!("*{}", go_type(type_ref)),
+    },
+    // Go has no union types.
+    TypeRef::Union(..) | TypeRef::Any => "interface{}".to_string(),
+  }
+}
+
+fn render_go(structs: &[StructDefinition]) -> String {
+  let mut body = String::new();
+  for definition in structs {
+    let field_names = deduplicate(
+      definition
+        .fields
+        .iter()
+        .map(|field| go_case(&field.name))
+        .collect(),
+    );
+    let fields = definition
+      .fields
+      .iter()
+      .zip(field_names)
+      .map(|(field, field_name)| {
+        let mut type_name = go_type(&field.type_ref);
+        let mut tag = field.name.clone();
+        if field.optional {
+          // Slices and interfaces are already nil when missing.
+          if !["*", "[]", "interface"]
+            .iter()
+            .any(|p| type_name.starts_with(p))
+          {
+            type_name = format!("*{}", type_name);
+          }
+          tag.push_str(",omitempty");
+        }
+        let tags = format!("`bson:\"{}\" json:\"{}\"`", tag, tag);
+        (field_name, type_name, tags)
+      })
+      .collect::<Vec<_>>();
+    // Aligned like `gofmt` does.
+    let name_width = fields.iter().map(|f| f.0.len()).max().unwrap_or(0);
+    let type_width = fields.iter().map(|f| f.1.len()).max().unwrap_or(0);
+    body.push_str(&format!("\ntype {} struct {{\n", definition.name));
+    for (name, type_name, tags) in fields {
+      body.push_str(&format!(
+        "\t{:name_width$} {:type_width$} {}\n",
+        name,
+        type_name,
+        tags,
+        name_width = name_width,
+        type_width = type_width
+      ));
+    }
+    body.push_str("}\n");
+  }
+  let mut result = String::from("package models\n");
+  if body.contains("primitive.") {
+    result.push_str("\nimport \"go.mongodb.org/mongo-driver/bson/primitive\"\n");
+  }
+  result.push_str(&body);
+  result
+}
+
+/// Generates the types of the documents of a collection, a struct for every nested object.
+pub fn generate_types(
+  schema: &CollectionSchema,
+  collection_name: &str,
+  language: TypeLanguage,
+) -> GeneratedTypes {
+  let mut collector = TypeCollector::new(schema);
+  collector.add_struct(None, pascal_case(collection_name), schema.document_count);
+  let structs = collector.structs;
+  match language {
+    TypeLanguage::Rust => GeneratedTypes {
+      file_name: format!("{}.rs", snake_case(collection_name)),
+      content: render_rust(&structs),
+    },
+    TypeLanguage::TypeScript => GeneratedTypes {
+      file_name: format!("{}.ts", collection_name),
+      content: render_typescript(&structs),
+    },
+    TypeLanguage::Go => GeneratedTypes {
+      file_name: format!("{}.go", snake_case(collection_name)),
+      content: render_go(&structs),
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use mongodb::bson::{doc, oid::ObjectId, DateTime};
+
+  use super::*;
+  use crate::schema::SchemaAnalyzer;
+
+  fn generate(language: TypeLanguage) -> String {
+    let mut analyzer = SchemaAnalyzer::default();
+    analyzer.add(&doc! {
+      "_id": 1,
+      "id": 2,
+      "createdAt": 3,
+      "created_at": 4,
+      "self": 5,
+      "type": 6,
+      "try": 7,
+    });
+    generate_types(&analyzer.into_schema(1), "items", language).content
+  }
+
+  #[test]
+  fn rust_field_names_are_valid_and_unique() {
+    let content = generate(TypeLanguage::Rust);
+    assert!(content.contains("  #[serde(rename = \"self\")]\n  pub self_: i32,"));
+    assert!(content.contains("  pub r#type: i32,"));
+    assert!(content.contains("  pub r#try: i32,"));
+    assert!(content.contains("  #[serde(rename = \"createdAt\")]\n  pub created_at: i32,"));
+    assert!(content.contains("  #[serde(rename = \"created_at\")]\n  pub created_at2: i32,"));
+  }
+
+  #[test]
+  fn rust_imports_only_the_used_types() {
+    assert!(generate(TypeLanguage::Rust).starts_with("use serde::{Deserialize, Serialize};\n"));
+    let mut analyzer = SchemaAnalyzer::default();
+    analyzer.add(&doc! { "_id": ObjectId::new(), "tags": [{ "at": DateTime::now() }] });
+    let content = generate_types(&analyzer.into_schema(1), "items", TypeLanguage::Rust).content;
+    assert!(content.starts_with("use bson::{oid::ObjectId, DateTime};\n"));
+    let mut analyzer = SchemaAnalyzer::default();
+    analyzer.add(&doc! { "_id": ObjectId::new() });
+    let content = generate_types(&analyzer.into_schema(1), "items", TypeLanguage::Rust).content;
+    assert!(content.starts_with("use bson::oid::ObjectId;\n"));
+  }
+
+  #[test]
+  fn go_field_names_are_unique() {
+    let content = generate(TypeLanguage::Go);
+    assert!(content.contains("\tID  "));
+    assert!(content.contains("\tID2 "));
+    assert!(content.contains("\tCreatedAt  "));
+    assert!(content.contains("\tCreatedAt2 "));
+  }
+}
@@ -0,0 +1,130 @@
+use mongodb::{
+  bson::{doc, Bson, Document},
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::PError,
+  model::{escape_regex, get_number},
+};
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct CurrentOpFilter {
+  /// Hides the idle connections and the operations that are not running.
+  pub active_only: bool,
+  pub min_secs_running: Option<i64>,
+  pub namespace: Option<String>,
+  /// One of `command`, `query`, `insert`, `update`, `remove`, `getmore` or `none`.
+  pub op_type: Option<String>,
+  /// Prefix of the client address, e.g. `10.0.0.1` or `10.0.0.1:54321`.
+  pub client: Option<String>,
+}
+
+impl CurrentOpFilter {
+  fn to_match_stage(&self) -> Document {
+    let mut result = Document::new();
+    if self.active_only {
+      result.insert("active", true);
+    }
+    if let Some(min_secs_running) = self.min_secs_running {
+      result.insert("secs_running", doc! { "$gte": min_secs_running });
+    }
+    if let Some(namespace) = &self.namespace {
+      result.insert("ns", namespace);
+    }
+    if let Some(op_type) = &self.op_type {
+      result.insert("op", op_type);
+    }
+    if let Some(client) = &self.client {
+      let pattern = format!("^{}", escape_regex(client));
+      // `mongos` reports the client under `client_s`.
+      result.insert(
+        "$or",
+        vec![
+          doc! { "client": { "$regex": &pattern } },
+          doc! { "client_s": { "$regex": &pattern } },
+        ],
+      );
+    }
+    result
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CurrentOperation {
+  /// A number on `mongod`, a `<shard>:<opid>` string on `mongos`.
+  pub opid: Bson,
+  pub op_type: Option<String>,
+  pub namespace: Option<String>,
+  pub active: bool,
+  pub secs_running: Option<i64>,
+  pub microsecs_running: Option<i64>,
+  pub client: Option<String>,
+  pub app_name: Option<String>,
+  pub description: Option<String>,
+  pub connection_id: Option<i64>,
+  pub command: Option<Document>,
+  pub plan_summary: Option<String>,
+  pub num_yields: Option<i64>,
+  pub waiting_for_lock: bool,
+  pub locks: Option<Document>,
+  pub lock_stats: Option<Document>,
+}
+
+impl From<Document> for CurrentOperation {
+  fn from(document: Document) -> Self {
+    let get_string = |key: &str| document.get_str(key).ok().map(|v| v.to_string());
+    let get_integer = |key: &str| get_number(&document, key).map(|v| v as i64);
+    let get_document = |key: &str| document.get_document(key).ok().cloned();
+    CurrentOperation {
+      opid: document.get("opid").cloned().unwrap_or(Bson::Null),
+      op_type: get_string("op"),
+      namespace: get_string("ns").filter(|v| !v.is_empty()),
+      active: document.get_bool("active").unwrap_or(false),
+      secs_running: get_integer("secs_running"),
+      microsecs_running: get_integer("microsecs_running"),
+      client: get_string("client").or_else(|| get_string("client_s")),
+      app_name: get_string("appName"),
+      description: get_string("desc"),
+      connection_id: get_integer("connectionId"),
+      command: get_document("command"),
+      plan_summary: get_string("planSummary"),
+      num_yields: get_integer("numYields"),
+      waiting_for_lock: document.get_bool("waitingForLock").unwrap_or(false),
+      locks: get_document("locks"),
+      lock_stats: get_document("lockStats"),
+    }
+  }
+}
+
+pub fn get_current_operations(
+  client: &Client,
+  filter: &CurrentOpFilter,
+) -> Result<Vec<CurrentOperation>, PError> {
+  let stages = vec![
+    doc! {
+      "$currentOp": {
+        "allUsers": true,
+        "idleConnections": !filter.active_only,
+      }
+    },
+    doc! { "$match": filter.to_match_stage() },
+    doc! { "$sort": { "secs_running": -1 } },
+  ];
+  let result = client
+    .database("admin")
+    .aggregate(stages, None)
+    .and_then(|cursor| cursor.collect::<Result<Vec<Document>, _>>())?
+    .into_iter()
+    .map(CurrentOperation::from)
+    .collect();
+  Ok(result)
+}
+
+pub fn kill_operation(client: &Client, opid: Bson) -> Result<Document, PError> {
+  let result = client
+    .database("admin")
+    .run_command(doc! { "killOp": 1, "op": opid }, None)?;
+  Ok(result)
+}
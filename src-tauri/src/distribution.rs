@@ -0,0 +1,195 @@
+use mongodb::{
+  bson::{doc, Bson, Document},
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::PError,
+  model::get_number,
+  schema::{ValueCount, ARRAY_ELEMENTS},
+};
+
+const DEFAULT_TOP_N: i64 = 20;
+const DEFAULT_BUCKETS: i64 = 10;
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistributionKind {
+  /// The most frequent values, for strings, ObjectIds or any other type.
+  TopValues,
+  /// A histogram of the numbers, with buckets chosen by `$bucketAuto`.
+  Numeric,
+  /// A histogram of the dates by `date_unit`.
+  Date,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateUnit {
+  Day,
+  /// ISO 8601 weeks.
+  Week,
+  Month,
+}
+
+impl DateUnit {
+  fn format(&self) -> &'static str {
+    match self {
+      DateUnit::Day => "%Y-%m-%d",
+      DateUnit::Week => "%G-W%V",
+      DateUnit::Month => "%Y-%m",
+    }
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DistributionOptions {
+  /// Dotted path from the schema analysis, e.g. `items.[].price`.
+  pub path: String,
+  pub kind: DistributionKind,
+  pub top_n: Option<i64>,
+  pub buckets: Option<i64>,
+  pub date_unit: Option<DateUnit>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NumericBucket {
+  pub min: Bson,
+  pub max: Bson,
+  pub count: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DateBucket {
+  /// e.g. `2022-08-31`, `2022-W35` or `2022-08`.
+  pub label: String,
+  pub count: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ValueDistribution {
+  TopValues {
+    values: Vec<ValueCount>,
+  },
+  Numeric {
+    buckets: Vec<NumericBucket>,
+  },
+  Date {
+    unit: DateUnit,
+    buckets: Vec<DateBucket>,
+  },
+}
+
+/// Returns the `$unwind` stages needed to reach the elements of the arrays of `path`, and the
+/// path of the field once unwound.
+fn unwind_path(path: &str) -> (Vec<Document>, String) {
+  let mut stages = Vec::new();
+  let mut field_path = Vec::new();
+  for key in path.split('.') {
+    if key == ARRAY_ELEMENTS {
+      stages.push(doc! { "$unwind": format!("${}", field_path.join(".")) });
+    } else {
+      field_path.push(key);
+    }
+  }
+  (stages, field_path.join("."))
+}
+
+fn get_count(document: &Document) -> u64 {
+  get_number(document, "count").unwrap_or(0.0) as u64
+}
+
+/// Computes the distribution of the values at `path` of the documents matching the filter.
+pub fn get_value_distribution(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  documents_filter: Document,
+  options: &DistributionOptions,
+) -> Result<ValueDistribution, PError> {
+  let sizes = [
+    ("the number of top values", options.top_n),
+    ("the number of buckets", options.buckets),
+  ];
+  for (what, size) in sizes {
+    if matches!(size, Some(size) if size <= 0) {
+      return Err(PError::InvalidArgument(format!(
+        "{} must be positive",
+        what
+      )));
+    }
+  }
+  let (mut stages, field_path) = unwind_path(&options.path);
+  stages.insert(0, doc! { "$match": documents_filter });
+  let field = format!("${}", field_path);
+  let date_unit = options.date_unit.unwrap_or(DateUnit::Day);
+  match options.kind {
+    DistributionKind::TopValues => stages.extend(vec![
+      doc! { "$match": { &field_path: { "$exists": true } } },
+      doc! { "$group": { "_id": &field, "count": { "$sum": 1 } } },
+      doc! { "$sort": { "count": -1, "_id": 1 } },
+      doc! { "$limit": options.top_n.unwrap_or(DEFAULT_TOP_N) },
+    ]),
+    DistributionKind::Numeric => stages.extend(vec![
+      doc! { "$match": { &field_path: { "$type": "number" } } },
+      doc! {
+        "$bucketAuto": {
+          "groupBy": &field,
+          "buckets": options.buckets.unwrap_or(DEFAULT_BUCKETS),
+        }
+      },
+    ]),
+    DistributionKind::Date => stages.extend(vec![
+      doc! { "$match": { &field_path: { "$type": "date" } } },
+      doc! {
+        "$group": {
+          "_id": { "$dateToString": { "format": date_unit.format(), "date": &field } },
+          "count": { "$sum": 1 },
+        }
+      },
+      doc! { "$sort": { "_id": 1 } },
+    ]),
+  }
+  let result = client
+    .database(database_name)
+    .collection::<Document>(collection_name)
+    .aggregate(stages, None)
+    .and_then(|cursor| cursor.collect::<Result<Vec<_>, _>>())?;
+  let distribution = match options.kind {
+    DistributionKind::TopValues => ValueDistribution::TopValues {
+      values: result
+        .iter()
+        .map(|group| ValueCount {
+          value: group.get("_id").cloned().unwrap_or(Bson::Null),
+          count: get_count(group),
+        })
+        .collect(),
+    },
+    DistributionKind::Numeric => ValueDistribution::Numeric {
+      buckets: result
+        .iter()
+        .map(|bucket| {
+          let bounds = bucket.get_document("_id").cloned().unwrap_or_default();
+          NumericBucket {
+            min: bounds.get("min").cloned().unwrap_or(Bson::Null),
+            max: bounds.get("max").cloned().unwrap_or(Bson::Null),
+            count: get_count(bucket),
+          }
+        })
+        .collect(),
+    },
+    DistributionKind::Date => ValueDistribution::Date {
+      unit: date_unit,
+      buckets: result
+        .iter()
+        .map(|bucket| DateBucket {
+          label: bucket.get_str("_id").unwrap_or_default().to_string(),
+          count: get_count(bucket),
+        })
+        .collect(),
+    },
+  };
+  Ok(distribution)
+}
@@ -0,0 +1,217 @@
+use std::{
+  cmp::Reverse,
+  collections::{BTreeMap, BTreeSet},
+  fs,
+  path::PathBuf,
+};
+
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::PError,
+  model::BsonType,
+  schema::{CollectionSchema, FieldSchema},
+  store::current_time_millis,
+};
+
+/// Presence shifts smaller than this many percentage points are not reported by default.
+pub const DEFAULT_PRESENCE_THRESHOLD: f64 = 5.0;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SchemaSnapshotSummary {
+  pub id: String,
+  pub name: String,
+  pub database_name: String,
+  pub collection_name: String,
+  /// Milliseconds since epoch.
+  pub created_at: u64,
+  pub document_count: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SchemaSnapshot {
+  pub id: String,
+  pub name: String,
+  pub database_name: String,
+  pub collection_name: String,
+  pub created_at: u64,
+  pub schema: CollectionSchema,
+}
+
+impl SchemaSnapshot {
+  pub fn new(
+    name: String,
+    database_name: String,
+    collection_name: String,
+    schema: CollectionSchema,
+  ) -> Self {
+    SchemaSnapshot {
+      // Unique even for the snapshots taken within the same millisecond.
+      id: ObjectId::new().to_hex(),
+      name,
+      database_name,
+      collection_name,
+      created_at: current_time_millis(),
+      schema,
+    }
+  }
+
+  pub fn summary(&self) -> SchemaSnapshotSummary {
+    SchemaSnapshotSummary {
+      id: self.id.clone(),
+      name: self.name.clone(),
+      database_name: self.database_name.clone(),
+      collection_name: self.collection_name.clone(),
+      created_at: self.created_at,
+      document_count: self.schema.document_count,
+    }
+  }
+}
+
+/// Keeps every snapshot as a JSON file, so that they can be copied between machines.
+pub struct SchemaSnapshotStore {
+  directory: PathBuf,
+}
+
+impl SchemaSnapshotStore {
+  pub fn default_path() -> Option<PathBuf> {
+    tauri::api::path::data_dir().map(|p| p.join("pinky-pie").join("schema_snapshots"))
+  }
+
+  pub fn open(directory: PathBuf) -> Result<SchemaSnapshotStore, PError> {
+    fs::create_dir_all(&directory)?;
+    Ok(SchemaSnapshotStore { directory })
+  }
+
+  fn path(&self, id: &str) -> PathBuf {
+    // The ids are hexadecimal, anything else must not escape the directory.
+    let id = id
+      .chars()
+      .filter(|c| c.is_ascii_alphanumeric())
+      .collect::<String>();
+    self.directory.join(format!("{}.json", id))
+  }
+
+  pub fn save(&self, snapshot: &SchemaSnapshot) -> Result<(), PError> {
+    let content = serde_json::to_vec_pretty(snapshot)?;
+    fs::write(self.path(&snapshot.id), content)?;
+    Ok(())
+  }
+
+  pub fn load(&self, id: &str) -> Result<SchemaSnapshot, PError> {
+    let content = fs::read(self.path(id))?;
+    Ok(serde_json::from_slice(&content)?)
+  }
+
+  pub fn delete(&self, id: &str) -> Result<(), PError> {
+    fs::remove_file(self.path(id))?;
+    Ok(())
+  }
+
+  /// Returns the summaries of the snapshots, the latest first. Unreadable files are skipped.
+  pub fn list(&self) -> Result<Vec<SchemaSnapshotSummary>, PError> {
+    let mut result = fs::read_dir(&self.directory)?
+      .filter_map(|entry| {
+        let path = entry.ok()?.path();
+        let content = fs::read(path).ok()?;
+        serde_json::from_slice::<SchemaSnapshot>(&content).ok()
+      })
+      .map(|snapshot| snapshot.summary())
+      .collect::<Vec<_>>();
+    result.sort_by_key(|snapshot| Reverse(snapshot.created_at));
+    Ok(result)
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TypeChange {
+  pub path: String,
+  pub added_types: Vec<BsonType>,
+  pub removed_types: Vec<BsonType>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PresenceChange {
+  pub path: String,
+  /// Percentages of the documents containing the field.
+  pub before: f64,
+  pub after: f64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SchemaDrift {
+  pub added_fields: Vec<String>,
+  pub removed_fields: Vec<String>,
+  pub type_changes: Vec<TypeChange>,
+  pub presence_changes: Vec<PresenceChange>,
+}
+
+fn field_types(field: &FieldSchema) -> Vec<BsonType> {
+  field.types.iter().map(|t| t.bson_type).collect()
+}
+
+/// Lists what changed from `before` to `after`, every list is sorted by path.
+pub fn compare_schemas(
+  before: &CollectionSchema,
+  after: &CollectionSchema,
+  presence_threshold: f64,
+) -> SchemaDrift {
+  let before_fields = before
+    .fields
+    .iter()
+    .map(|f| (f.path.as_str(), f))
+    .collect::<BTreeMap<_, _>>();
+  let after_fields = after
+    .fields
+    .iter()
+    .map(|f| (f.path.as_str(), f))
+    .collect::<BTreeMap<_, _>>();
+  let paths = before_fields
+    .keys()
+    .chain(after_fields.keys())
+    .copied()
+    .collect::<BTreeSet<_>>();
+  let mut result = SchemaDrift {
+    added_fields: Vec::new(),
+    removed_fields: Vec::new(),
+    type_changes: Vec::new(),
+    presence_changes: Vec::new(),
+  };
+  for path in paths {
+    match (before_fields.get(path), after_fields.get(path)) {
+      (None, Some(_)) => result.added_fields.push(path.to_string()),
+      (Some(_), None) => result.removed_fields.push(path.to_string()),
+      (Some(before), Some(after)) => {
+        let before_types = field_types(before);
+        let after_types = field_types(after);
+        let added_types = after_types
+          .iter()
+          .filter(|t| !before_types.contains(t))
+          .copied()
+          .collect::<Vec<_>>();
+        let removed_types = before_types
+          .iter()
+          .filter(|t| !after_types.contains(t))
+          .copied()
+          .collect::<Vec<_>>();
+        if !added_types.is_empty() || !removed_types.is_empty() {
+          result.type_changes.push(TypeChange {
+            path: path.to_string(),
+            added_types,
+            removed_types,
+          });
+        }
+        if (after.presence - before.presence).abs() >= presence_threshold {
+          result.presence_changes.push(PresenceChange {
+            path: path.to_string(),
+            before: before.presence,
+            after: after.presence,
+          });
+        }
+      }
+      (None, None) => {}
+    }
+  }
+  result
+}
@@ -11,6 +11,20 @@ pub enum PError {
   DocumentCountFailed,
   MongodbError(String, Vec<String>),
   BsonSerializationError(String),
+  StoreNotAvailable,
+  StoreError(String),
+  PrometheusExporterError(String),
+  ConfirmationRequired,
+  WriteModeDisabled,
+  InvalidArgument(String),
+  SavedQueryNotFound(String),
+  MissingQueryParameter(String),
+  HistoryEntryNotFound(i64),
+  ShellSyntaxError {
+    message: String,
+    line: usize,
+    column: usize,
+  },
 }
 
 impl std::error::Error for PError {}
@@ -35,3 +49,21 @@ impl From<mongodb::bson::ser::Error> for PError {
     PError::BsonSerializationError(format!("{:#?}", err))
   }
 }
+
+impl From<rusqlite::Error> for PError {
+  fn from(err: rusqlite::Error) -> Self {
+    PError::StoreError(format!("{:#?}", err))
+  }
+}
+
+impl From<std::io::Error> for PError {
+  fn from(err: std::io::Error) -> Self {
+    PError::StoreError(format!("{:#?}", err))
+  }
+}
+
+impl From<serde_json::Error> for PError {
+  fn from(err: serde_json::Error) -> Self {
+    PError::StoreError(format!("{:#?}", err))
+  }
+}
@@ -0,0 +1,458 @@
+use std::{
+  path::PathBuf,
+  sync::{Arc, Mutex},
+  time::Instant,
+};
+
+use mongodb::{
+  bson::{doc, Bson, Document},
+  sync::Client,
+};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::PError,
+  query::{aggregate_documents, count_documents, find_documents, AggregateQuery, FindQuery},
+  query_shape::normalize,
+  store::current_time_millis,
+};
+
+/// The oldest unpinned entries are removed beyond this number.
+pub const MAX_HISTORY_ENTRIES: usize = 500;
+
+/// Number of entries returned by a search by default.
+pub const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// The arguments of a query executed through the commands.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum HistoryQuery {
+  Find {
+    query: Box<FindQuery>,
+    page: i64,
+    per_page: i64,
+  },
+  Count {
+    filter: Document,
+  },
+  Aggregate {
+    query: AggregateQuery,
+  },
+}
+
+impl HistoryQuery {
+  fn kind(&self) -> &'static str {
+    match self {
+      HistoryQuery::Find { .. } => "find",
+      HistoryQuery::Count { .. } => "count",
+      HistoryQuery::Aggregate { .. } => "aggregate",
+    }
+  }
+
+  /// The query without the literals of its filters, the history keeps one entry per shape. The
+  /// sort and projection are kept whole since their values change the result.
+  fn shape(&self) -> Document {
+    match self {
+      HistoryQuery::Find { query, .. } => {
+        let mut shape = doc! { "filter": normalize(&Bson::Document(query.filter.clone())) };
+        if let Some(sort) = &query.sort {
+          shape.insert("sort", sort.clone());
+        }
+        if let Some(projection) = &query.projection {
+          shape.insert("projection", projection.clone());
+        }
+        shape
+      }
+      HistoryQuery::Count { filter } => {
+        doc! { "filter": normalize(&Bson::Document(filter.clone())) }
+      }
+      HistoryQuery::Aggregate { query } => doc! {
+        "pipeline": query.stages.iter().map(stage_shape).collect::<Vec<_>>(),
+      },
+    }
+  }
+}
+
+fn stage_shape(stage: &Document) -> Bson {
+  match stage.keys().next().map(String::as_str) {
+    Some("$sort") | Some("$project") => Bson::Document(stage.clone()),
+    _ => normalize(&Bson::Document(stage.clone())),
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "result", rename_all = "snake_case")]
+pub enum QueryOutput {
+  Documents(Vec<Document>),
+  Count(u64),
+}
+
+impl QueryOutput {
+  pub fn result_count(&self) -> u64 {
+    match self {
+      QueryOutput::Documents(documents) => documents.len() as u64,
+      QueryOutput::Count(count) => *count,
+    }
+  }
+}
+
+pub fn run_history_query(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  query: HistoryQuery,
+) -> Result<QueryOutput, PError> {
+  let result = match query {
+    HistoryQuery::Find {
+      query,
+      page,
+      per_page,
+    } => QueryOutput::Documents(find_documents(
+      client,
+      database_name,
+      collection_name,
+      *query,
+      page,
+      per_page,
+    )?),
+    HistoryQuery::Count { filter } => QueryOutput::Count(count_documents(
+      client,
+      database_name,
+      collection_name,
+      filter,
+    )?),
+    HistoryQuery::Aggregate { query } => QueryOutput::Documents(aggregate_documents(
+      client,
+      database_name,
+      collection_name,
+      query,
+    )?),
+  };
+  Ok(result)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistoryEntry {
+  pub id: i64,
+  pub database_name: String,
+  pub collection_name: String,
+  /// The arguments of the latest execution.
+  pub query: HistoryQuery,
+  pub shape: Document,
+  /// Milliseconds spent by the latest execution.
+  pub duration: u64,
+  /// Number of documents returned, or counted, by the latest execution.
+  pub result_count: Option<u64>,
+  pub error: Option<String>,
+  /// Number of executions with this shape.
+  pub run_count: u64,
+  pub pinned: bool,
+  /// Milliseconds since epoch.
+  pub first_run_at: u64,
+  pub last_run_at: u64,
+}
+
+fn json_column<T: serde::de::DeserializeOwned>(row: &Row, idx: usize) -> rusqlite::Result<T> {
+  let content = row.get::<_, String>(idx)?;
+  serde_json::from_str(&content).map_err(|e| {
+    rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+  })
+}
+
+const ENTRY_COLUMNS: &str = "id, database_name, collection_name, arguments, shape, duration,
+  result_count, error, run_count, pinned, first_run_at, last_run_at";
+
+fn read_entry(row: &Row) -> rusqlite::Result<HistoryEntry> {
+  Ok(HistoryEntry {
+    id: row.get(0)?,
+    database_name: row.get(1)?,
+    collection_name: row.get(2)?,
+    query: json_column(row, 3)?,
+    shape: json_column(row, 4)?,
+    duration: row.get::<_, i64>(5)? as u64,
+    result_count: row.get::<_, Option<i64>>(6)?.map(|v| v as u64),
+    error: row.get(7)?,
+    run_count: row.get::<_, i64>(8)? as u64,
+    pinned: row.get(9)?,
+    first_run_at: row.get::<_, i64>(10)? as u64,
+    last_run_at: row.get::<_, i64>(11)? as u64,
+  })
+}
+
+/// Escapes `%`, `_` and `\` so that `value` is matched literally by `LIKE ... ESCAPE '\'`.
+fn escape_like(value: &str) -> String {
+  let mut result = String::with_capacity(value.len());
+  for c in value.chars() {
+    if "\\%_".contains(c) {
+      result.push('\\');
+    }
+    result.push(c);
+  }
+  result
+}
+
+pub struct QueryHistoryStore {
+  connection: Connection,
+}
+
+impl QueryHistoryStore {
+  pub fn default_path() -> Option<PathBuf> {
+    tauri::api::path::data_dir().map(|p| p.join("pinky-pie").join("history.sqlite3"))
+  }
+
+  pub fn open(path: PathBuf) -> Result<QueryHistoryStore, PError> {
+    if let Some(parent) = path.parent() {
+      std::fs::create_dir_all(parent)?;
+    }
+    let connection = Connection::open(path)?;
+    connection.execute_batch(
+      "CREATE TABLE IF NOT EXISTS query_history (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        shape_key TEXT NOT NULL,
+        kind TEXT NOT NULL,
+        database_name TEXT NOT NULL,
+        collection_name TEXT NOT NULL,
+        arguments TEXT NOT NULL,
+        shape TEXT NOT NULL,
+        duration INTEGER NOT NULL,
+        result_count INTEGER,
+        error TEXT,
+        run_count INTEGER NOT NULL,
+        pinned INTEGER NOT NULL,
+        first_run_at INTEGER NOT NULL,
+        last_run_at INTEGER NOT NULL
+      );
+      -- The pinned entries keep their arguments, a new run of the same shape gets its own entry.
+      CREATE UNIQUE INDEX IF NOT EXISTS query_history_shape_key
+        ON query_history (shape_key) WHERE pinned = 0;",
+    )?;
+    Ok(QueryHistoryStore { connection })
+  }
+
+  /// Adds an execution, replacing the arguments and outcome of the unpinned entry with the same
+  /// shape.
+  pub fn record(
+    &self,
+    database_name: &str,
+    collection_name: &str,
+    query: &HistoryQuery,
+    duration: u64,
+    outcome: Result<u64, &PError>,
+  ) -> Result<(), PError> {
+    let shape = query.shape();
+    let shape_key = format!(
+      "{}:{}.{}:{}",
+      query.kind(),
+      database_name,
+      collection_name,
+      shape
+    );
+    let (result_count, error) = match outcome {
+      Ok(count) => (Some(count as i64), None),
+      Err(e) => (None, Some(e.to_string())),
+    };
+    let now = current_time_millis() as i64;
+    self.connection.execute(
+      "INSERT INTO query_history (
+        shape_key, kind, database_name, collection_name, arguments, shape, duration,
+        result_count, error, run_count, pinned, first_run_at, last_run_at
+      ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 1, 0, ?10, ?10)
+      ON CONFLICT (shape_key) WHERE pinned = 0 DO UPDATE SET
+        arguments = excluded.arguments,
+        duration = excluded.duration,
+        result_count = excluded.result_count,
+        error = excluded.error,
+        run_count = run_count + 1,
+        last_run_at = excluded.last_run_at",
+      params![
+        shape_key,
+        query.kind(),
+        database_name,
+        collection_name,
+        serde_json::to_string(query)?,
+        serde_json::to_string(&shape)?,
+        duration as i64,
+        result_count,
+        error,
+        now,
+      ],
+    )?;
+    self.connection.execute(
+      "DELETE FROM query_history WHERE pinned = 0 AND id NOT IN (
+        SELECT id FROM query_history WHERE pinned = 0 ORDER BY last_run_at DESC LIMIT ?1
+      )",
+      params![MAX_HISTORY_ENTRIES as i64],
+    )?;
+    Ok(())
+  }
+
+  pub fn get(&self, id: i64) -> Result<HistoryEntry, PError> {
+    let entry = self
+      .connection
+      .query_row(
+        &format!("SELECT {} FROM query_history WHERE id = ?1", ENTRY_COLUMNS),
+        params![id],
+        read_entry,
+      )
+      .optional()?;
+    entry.ok_or(PError::HistoryEntryNotFound(id))
+  }
+
+  /// Returns the pinned entries first, then the most recently run ones. `text` is searched in
+  /// the namespace and the arguments.
+  pub fn search(
+    &self,
+    text: Option<&str>,
+    pinned_only: bool,
+    limit: usize,
+  ) -> Result<Vec<HistoryEntry>, PError> {
+    let pattern = text.map(|text| format!("%{}%", escape_like(text)));
+    let mut statement = self.connection.prepare(&format!(
+      "SELECT {} FROM query_history
+      WHERE (?1 IS NULL
+        OR database_name || '.' || collection_name LIKE ?1 ESCAPE '\\'
+        OR arguments LIKE ?1 ESCAPE '\\')
+      AND (?2 = 0 OR pinned = 1)
+      ORDER BY pinned DESC, last_run_at DESC
+      LIMIT ?3",
+      ENTRY_COLUMNS
+    ))?;
+    let result = statement
+      .query_map(params![pattern, pinned_only, limit as i64], read_entry)?
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(result)
+  }
+
+  /// Pinned entries are kept regardless of `MAX_HISTORY_ENTRIES`. An unpinned entry replaces the
+  /// one recorded with the same shape while it was pinned.
+  pub fn set_pinned(&self, id: i64, pinned: bool) -> Result<(), PError> {
+    if !pinned {
+      self.connection.execute(
+        "DELETE FROM query_history WHERE pinned = 0 AND id != ?1
+          AND shape_key = (SELECT shape_key FROM query_history WHERE id = ?1)",
+        params![id],
+      )?;
+    }
+    self.connection.execute(
+      "UPDATE query_history SET pinned = ?2 WHERE id = ?1",
+      params![id, pinned],
+    )?;
+    Ok(())
+  }
+
+  pub fn delete(&self, id: i64) -> Result<(), PError> {
+    self
+      .connection
+      .execute("DELETE FROM query_history WHERE id = ?1", params![id])?;
+    Ok(())
+  }
+}
+
+/// Records an execution that started at `start`. The query has already run, so a failure to
+/// record it is only logged.
+pub fn record_query(
+  store: &Arc<Mutex<Option<QueryHistoryStore>>>,
+  database_name: &str,
+  collection_name: &str,
+  query: &HistoryQuery,
+  start: Instant,
+  outcome: Result<u64, &PError>,
+) {
+  let duration = start.elapsed().as_millis() as u64;
+  let handle = &*store.lock().unwrap();
+  if let Some(store) = handle {
+    if let Err(e) = store.record(database_name, collection_name, query, duration, outcome) {
+      eprintln!("Failed to record the query history error:{}", e);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn find(filter: Document, sort: Document) -> HistoryQuery {
+    HistoryQuery::Find {
+      query: Box::new(FindQuery {
+        filter,
+        sort: Some(sort),
+        ..FindQuery::default()
+      }),
+      page: 0,
+      per_page: 20,
+    }
+  }
+
+  fn open_store(name: &str) -> QueryHistoryStore {
+    let path =
+      std::env::temp_dir().join(format!("pinky-pie-{}-{}.sqlite3", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    QueryHistoryStore::open(path).unwrap()
+  }
+
+  #[test]
+  fn sort_values_are_part_of_the_shape() {
+    let store = open_store("history-sort");
+    store
+      .record(
+        "db",
+        "coll",
+        &find(doc! { "a": 1 }, doc! { "a": 1 }),
+        1,
+        Ok(1),
+      )
+      .unwrap();
+    store
+      .record(
+        "db",
+        "coll",
+        &find(doc! { "a": 2 }, doc! { "a": -1 }),
+        1,
+        Ok(1),
+      )
+      .unwrap();
+    store
+      .record(
+        "db",
+        "coll",
+        &find(doc! { "a": 3 }, doc! { "a": -1 }),
+        1,
+        Ok(1),
+      )
+      .unwrap();
+    let entries = store.search(None, false, DEFAULT_HISTORY_LIMIT).unwrap();
+    assert_eq!(entries.len(), 2);
+  }
+
+  #[test]
+  fn pinned_entries_keep_their_arguments() {
+    let store = open_store("history-pinned");
+    store
+      .record("db", "coll", &find(doc! { "a": 1 }, doc! {}), 1, Ok(1))
+      .unwrap();
+    let pinned = store.search(None, false, DEFAULT_HISTORY_LIMIT).unwrap()[0].id;
+    store.set_pinned(pinned, true).unwrap();
+    store
+      .record("db", "coll", &find(doc! { "a": 2 }, doc! {}), 1, Ok(1))
+      .unwrap();
+
+    let entry = store.get(pinned).unwrap();
+    assert!(
+      matches!(entry.query, HistoryQuery::Find { query, .. } if query.filter == doc! { "a": 1 })
+    );
+    assert_eq!(entry.run_count, 1);
+    assert_eq!(
+      store
+        .search(None, false, DEFAULT_HISTORY_LIMIT)
+        .unwrap()
+        .len(),
+      2
+    );
+
+    store.set_pinned(pinned, false).unwrap();
+    let entries = store.search(None, false, DEFAULT_HISTORY_LIMIT).unwrap();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].id, pinned);
+  }
+}
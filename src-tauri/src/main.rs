@@ -7,23 +7,130 @@
 extern crate lazy_static;
 
 mod cmd;
+mod codegen;
+mod current_op;
+mod distribution;
+mod drift;
 mod error;
+mod history;
 mod model;
 mod mongodb_events;
+mod pipeline;
+mod pipeline_lint;
+mod profiler;
+mod prometheus;
+mod query;
+mod query_shape;
+mod replica_set;
+mod saved_queries;
+mod schema;
+mod server_status;
+mod sharding;
+mod shell;
+mod stats;
+mod store;
+mod validation;
 
 fn main() {
+  let app_state = model::AppState::default();
+  match store::MetricsStore::default_path().map(store::MetricsStore::open) {
+    Some(Ok(metrics_store)) => *app_state.metrics_store.lock().unwrap() = Some(metrics_store),
+    Some(Err(e)) => eprintln!("Cannot open the metrics store error:{}", e),
+    None => eprintln!("Cannot find a data directory for the metrics store"),
+  }
+  match drift::SchemaSnapshotStore::default_path().map(drift::SchemaSnapshotStore::open) {
+    Some(Ok(store)) => *app_state.schema_snapshot_store.lock().unwrap() = Some(store),
+    Some(Err(e)) => eprintln!("Cannot open the schema snapshot store error:{}", e),
+    None => eprintln!("Cannot find a data directory for the schema snapshots"),
+  }
+  match saved_queries::SavedQueryStore::default_path().map(saved_queries::SavedQueryStore::open) {
+    Some(Ok(store)) => *app_state.saved_query_store.lock().unwrap() = Some(store),
+    Some(Err(e)) => eprintln!("Cannot open the saved query store error:{}", e),
+    None => eprintln!("Cannot find a data directory for the saved queries"),
+  }
+  match history::QueryHistoryStore::default_path().map(history::QueryHistoryStore::open) {
+    Some(Ok(store)) => *app_state.query_history_store.lock().unwrap() = Some(store),
+    Some(Err(e)) => eprintln!("Cannot open the query history store error:{}", e),
+    None => eprintln!("Cannot find a data directory for the query history"),
+  }
+  store::spawn_rollup_thread(app_state.metrics_store.clone());
+  *app_state.server_status_interval.lock().unwrap() =
+    Some(server_status::DEFAULT_SERVER_STATUS_INTERVAL);
+  server_status::spawn_server_status_thread(
+    app_state.client.clone(),
+    app_state.server_status_interval.clone(),
+  );
+
   tauri::Builder::default()
-    .manage(model::AppState::default())
+    .manage(app_state)
     .invoke_handler(tauri::generate_handler![
       cmd::mongodb_connect,
+      cmd::mongodb_list_collections,
+      cmd::mongodb_refresh_database,
+      cmd::mongodb_get_collection_statistics,
+      cmd::mongodb_get_database_statistics,
       cmd::mongodb_find_documents,
       cmd::mongodb_count_documents,
       cmd::mongodb_aggregate_documents,
+      cmd::mongodb_preview_pipeline,
+      cmd::mongodb_lint_pipeline,
+      cmd::mongodb_list_saved_queries,
+      cmd::mongodb_get_saved_query,
+      cmd::mongodb_save_query,
+      cmd::mongodb_delete_saved_query,
+      cmd::mongodb_export_saved_queries,
+      cmd::mongodb_import_saved_queries,
+      cmd::mongodb_run_saved_query,
+      cmd::mongodb_search_query_history,
+      cmd::mongodb_pin_query_history_entry,
+      cmd::mongodb_delete_query_history_entry,
+      cmd::mongodb_rerun_query_history_entry,
+      cmd::mongodb_parse_shell_syntax,
+      cmd::mongodb_format_shell_syntax,
       cmd::mongodb_get_database_topology,
+      cmd::mongodb_get_topology_timeline,
       cmd::mongodb_analyze_documents,
+      cmd::mongodb_preview_validator,
+      cmd::mongodb_apply_validator,
+      cmd::mongodb_get_validation_rules,
+      cmd::mongodb_find_validation_violations,
+      cmd::mongodb_save_schema_snapshot,
+      cmd::mongodb_list_schema_snapshots,
+      cmd::mongodb_delete_schema_snapshot,
+      cmd::mongodb_compare_schema_snapshots,
+      cmd::mongodb_generate_types,
+      cmd::mongodb_get_value_distribution,
       cmd::mongodb_n_slowest_commands,
+      cmd::mongodb_get_commands_by_shape,
       cmd::mongodb_get_commands_statistics_per_sec,
-      cmd::mongodb_get_connection_heartbeat
+      cmd::mongodb_get_connection_heartbeat,
+      cmd::mongodb_get_server_heartbeats,
+      cmd::mongodb_get_connection_pools,
+      cmd::mongodb_get_server_status_series,
+      cmd::mongodb_get_server_status_interval,
+      cmd::mongodb_set_server_status_interval,
+      cmd::mongodb_get_current_operations,
+      cmd::mongodb_kill_operation,
+      cmd::mongodb_get_profiling_status,
+      cmd::mongodb_set_profiling_status,
+      cmd::mongodb_find_profile_entries,
+      cmd::mongodb_analyze_profile_entries,
+      cmd::mongodb_get_replica_set_status,
+      cmd::mongodb_get_replica_set_config,
+      cmd::mongodb_set_replica_set_write_mode,
+      cmd::mongodb_step_down_primary,
+      cmd::mongodb_freeze_member,
+      cmd::mongodb_list_shards,
+      cmd::mongodb_list_sharded_collections,
+      cmd::mongodb_get_balancer_status,
+      cmd::mongodb_list_zones,
+      cmd::mongodb_get_metrics_profiles,
+      cmd::mongodb_get_metrics_history,
+      cmd::mongodb_get_metrics_retention,
+      cmd::mongodb_set_metrics_retention,
+      cmd::mongodb_start_prometheus_exporter,
+      cmd::mongodb_stop_prometheus_exporter,
+      cmd::mongodb_get_prometheus_exporter_port
     ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
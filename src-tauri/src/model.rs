@@ -1,22 +1,77 @@
-use std::sync::{Arc, Mutex};
+use std::{
+  collections::BTreeMap,
+  convert::TryFrom,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
 
 use mongodb::{
-  bson::{Bson, Document},
-  results::CollectionSpecification,
+  bson::{doc, Bson, Document},
+  options::ListDatabasesOptions,
+  results::DatabaseSpecification,
   sync::Client,
 };
 use serde::{Deserialize, Serialize};
 
-use crate::error::PError;
+use crate::{
+  drift::SchemaSnapshotStore, error::PError, history::QueryHistoryStore,
+  prometheus::PrometheusExporter, saved_queries::SavedQueryStore, sharding::get_database_shards,
+  stats::get_collection_statistics, store::MetricsStore,
+};
 
 #[derive(Default)]
 pub struct AppState {
   pub client: Arc<Mutex<Option<Client>>>,
+  pub metrics_store: Arc<Mutex<Option<MetricsStore>>>,
+  pub schema_snapshot_store: Arc<Mutex<Option<SchemaSnapshotStore>>>,
+  pub saved_query_store: Arc<Mutex<Option<SavedQueryStore>>>,
+  pub query_history_store: Arc<Mutex<Option<QueryHistoryStore>>>,
+  pub prometheus_exporter: Arc<Mutex<Option<PrometheusExporter>>>,
+  /// Allows the commands that change the state of the replica set members.
+  pub replica_set_write_mode: Arc<Mutex<bool>>,
+  /// Interval between two `serverStatus` polls, `None` turns the poller off.
+  pub server_status_interval: Arc<Mutex<Option<Duration>>>,
 }
 
 pub type AppArg<'a> = tauri::State<'a, AppState>;
 
-#[derive(PartialEq, Eq, Hash, Serialize, Deserialize)]
+/// Escapes `value` so that it can be matched literally inside of a `$regex`.
+pub fn escape_regex(value: &str) -> String {
+  let mut result = String::with_capacity(value.len());
+  for c in value.chars() {
+    if "\\^$.|?*+()[]{}/".contains(c) {
+      result.push('\\');
+    }
+    result.push(c);
+  }
+  result
+}
+
+/// Reads a number whether it was encoded as an int32, int64 or double.
+pub fn as_number(value: &Bson) -> Option<f64> {
+  match value {
+    Bson::Int32(v) => Some(*v as f64),
+    Bson::Int64(v) => Some(*v as f64),
+    Bson::Double(v) => Some(*v),
+    _ => None,
+  }
+}
+
+/// Reads the number at the dotted `path` whether it was encoded as an int32, int64 or double.
+pub fn get_number(document: &Document, path: &str) -> Option<f64> {
+  let mut current = document;
+  let mut keys = path.split('.').peekable();
+  while let Some(key) = keys.next() {
+    let value = current.get(key)?;
+    if keys.peek().is_none() {
+      return as_number(value);
+    }
+    current = value.as_document()?;
+  }
+  None
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BsonType {
   /// 64-bit binary floating point
   Double,
@@ -62,6 +117,35 @@ pub enum BsonType {
   DbPointer,
 }
 
+impl BsonType {
+  /// The alias of the type in `$type` and `$jsonSchema`.
+  pub fn alias(&self) -> &'static str {
+    match self {
+      BsonType::Double => "double",
+      BsonType::String => "string",
+      BsonType::Array => "array",
+      BsonType::Document => "object",
+      BsonType::Boolean => "bool",
+      BsonType::Null => "null",
+      BsonType::RegularExpression => "regex",
+      BsonType::JavaScriptCode => "javascript",
+      BsonType::JavaScriptCodeWithScope => "javascriptWithScope",
+      BsonType::Int32 => "int",
+      BsonType::Int64 => "long",
+      BsonType::Timestamp => "timestamp",
+      BsonType::Binary => "binData",
+      BsonType::ObjectId => "objectId",
+      BsonType::DateTime => "date",
+      BsonType::Symbol => "symbol",
+      BsonType::Decimal128 => "decimal",
+      BsonType::Undefined => "undefined",
+      BsonType::MaxKey => "maxKey",
+      BsonType::MinKey => "minKey",
+      BsonType::DbPointer => "dbPointer",
+    }
+  }
+}
+
 impl From<&Bson> for BsonType {
   fn from(b: &Bson) -> Self {
     match b {
@@ -91,29 +175,185 @@ impl From<&Bson> for BsonType {
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionInformation {
+  pub name: String,
+  /// `collection` or `view`, unknown for the name only listings.
+  #[serde(rename = "type")]
+  pub collection_type: Option<String>,
+  /// Only loaded when asked for since it takes one `$collStats` per collection.
+  pub count: Option<u64>,
+  pub size: Option<u64>,
+  pub storage_size: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionPage {
+  pub collections: Vec<CollectionInformation>,
+  pub page: usize,
+  pub per_page: usize,
+  pub total: usize,
+}
+
+impl CollectionPage {
+  /// Checks the page arguments received from the frontend, the page size must be positive.
+  pub fn page_arguments(page: i64, per_page: i64) -> Result<(usize, usize), PError> {
+    match (usize::try_from(page), usize::try_from(per_page)) {
+      (Ok(page), Ok(per_page)) if per_page > 0 => Ok((page, per_page)),
+      _ => Err(PError::InvalidArgument(format!(
+        "invalid page {} of {} collections",
+        page, per_page
+      ))),
+    }
+  }
+
+  /// Lists one page of the collections of a database sorted by name. `listCollections` can
+  /// neither sort nor skip, so the names of every collection are listed to find the page, while
+  /// the specifications and sizes are only loaded for the collections of the page.
+  pub fn from_database(
+    client: &Client,
+    database_name: &str,
+    name_only: bool,
+    with_sizes: bool,
+    page: usize,
+    per_page: usize,
+  ) -> Result<Self, PError> {
+    let start = page
+      .checked_mul(per_page)
+      .ok_or_else(|| PError::InvalidArgument(format!("page {} is out of range", page)))?;
+    let database = client.database(database_name);
+    let mut names = database.list_collection_names(None)?;
+    names.sort();
+    let total = names.len();
+    let names = names
+      .into_iter()
+      .skip(start)
+      .take(per_page)
+      .collect::<Vec<_>>();
+    let mut collections = if name_only || names.is_empty() {
+      names
+        .into_iter()
+        .map(|name| CollectionInformation {
+          name,
+          collection_type: None,
+          count: None,
+          size: None,
+          storage_size: None,
+        })
+        .collect::<Vec<_>>()
+    } else {
+      let mut collections = database
+        .list_collections(doc! { "name": { "$in": names } }, None)?
+        .map(|specification| {
+          specification.map(|specification| CollectionInformation {
+            collection_type: mongodb::bson::to_bson(&specification.collection_type)
+              .ok()
+              .and_then(|v| v.as_str().map(|v| v.to_string())),
+            name: specification.name,
+            count: None,
+            size: None,
+            storage_size: None,
+          })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+      collections.sort_by(|l, r| l.name.cmp(&r.name));
+      collections
+    };
+    if with_sizes {
+      for collection in &mut collections {
+        // Views have no statistics.
+        if let Ok(statistics) = get_collection_statistics(client, database_name, &collection.name) {
+          collection.count = Some(statistics.count);
+          collection.size = Some(statistics.size);
+          collection.storage_size = Some(statistics.storage_size);
+        }
+      }
+    }
+    Ok(CollectionPage {
+      collections,
+      page,
+      per_page,
+      total,
+    })
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DatabaseInformation {
   pub name: String,
   pub size_on_disk: u64,
   pub empty: bool,
   pub shards: Option<Document>,
-  pub collections: Vec<CollectionSpecification>,
+  /// Loaded on demand, `None` until the database is expanded.
+  pub collections: Option<CollectionPage>,
+  /// Why the collections could not be listed, the other databases are not affected.
+  pub error: Option<String>,
 }
 
 impl DatabaseInformation {
-  pub fn from_client(client: &Client) -> Result<Document, PError> {
-    let databases = client.list_databases(None, None)?;
-    let mut document = Document::default();
-
-    for database in databases {
-      let collections = client
-        .database(&database.name)
-        .list_collections(None, None)?
-        .collect::<Result<Vec<_>, _>>()?;
-      let mut database_information = mongodb::bson::to_document(&database)?;
-      database_information.insert("collections", mongodb::bson::to_bson(&collections)?);
-      document.insert(database.name, database_information);
-    }
+  /// The chunks are only counted for the database `chunks_of`, see `get_database_shards`.
+  fn from_specifications(
+    client: &Client,
+    databases: Vec<DatabaseSpecification>,
+    chunks_of: Option<&str>,
+  ) -> BTreeMap<String, DatabaseInformation> {
+    let mut database_shards = get_database_shards(client, chunks_of);
+    databases
+      .into_iter()
+      .map(|database| {
+        let mut shards = database_shards.remove(&database.name);
+        if let Some(shards) = &mut shards {
+          // `listDatabases` only reports the size of the database on each shard.
+          for (shard, size_on_disk) in database.shards.iter().flatten() {
+            if let Ok(shard) = shards.get_document_mut(shard) {
+              shard.insert("sizeOnDisk", size_on_disk.clone());
+            }
+          }
+        }
+        let database_information = DatabaseInformation {
+          name: database.name.clone(),
+          size_on_disk: database.size_on_disk,
+          empty: database.empty,
+          shards: shards.or(database.shards),
+          collections: None,
+          error: None,
+        };
+        (database.name, database_information)
+      })
+      .collect()
+  }
+
+  /// Lists the databases the user is allowed to see, without their collections.
+  pub fn from_client(client: &Client) -> Result<BTreeMap<String, DatabaseInformation>, PError> {
+    let list_databases_options = ListDatabasesOptions::builder()
+      .authorized_databases(true)
+      .build();
+    let databases = client.list_databases(None, list_databases_options)?;
+    Ok(Self::from_specifications(client, databases, None))
+  }
 
-    Ok(document)
+  /// Reloads one database with the first page of its collections and the number of chunks on
+  /// each shard, `None` if it does not exist anymore.
+  pub fn refresh(
+    client: &Client,
+    database_name: &str,
+    name_only: bool,
+    with_sizes: bool,
+    per_page: usize,
+  ) -> Result<Option<DatabaseInformation>, PError> {
+    let databases = client.list_databases(doc! { "name": database_name }, None)?;
+    let mut database = match Self::from_specifications(client, databases, Some(database_name))
+      .remove(database_name)
+    {
+      Some(database) => database,
+      None => return Ok(None),
+    };
+    match CollectionPage::from_database(client, database_name, name_only, with_sizes, 0, per_page) {
+      Ok(collections) => database.collections = Some(collections),
+      Err(err) => database.error = Some(err.to_string()),
+    }
+    Ok(Some(database))
   }
 }
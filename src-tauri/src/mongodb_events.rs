@@ -1,23 +1,41 @@
 use std::{
-  collections::{BTreeMap, HashMap},
+  collections::{BTreeMap, HashMap, HashSet, VecDeque},
   sync::{Arc, Mutex},
-  time::{SystemTime, UNIX_EPOCH},
+  time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
 use mongodb::{
   bson::{Bson, Document},
   event::{
+    cmap::{
+      CmapEventHandler, ConnectionCheckedInEvent, ConnectionCheckedOutEvent,
+      ConnectionCheckoutFailedEvent, ConnectionCheckoutStartedEvent, ConnectionClosedEvent,
+      ConnectionCreatedEvent, PoolClearedEvent, PoolClosedEvent, PoolCreatedEvent, PoolReadyEvent,
+    },
     command::{
       CommandEventHandler, CommandFailedEvent, CommandStartedEvent, CommandSucceededEvent,
     },
     sdam::{
-      SdamEventHandler, ServerHeartbeatFailedEvent, ServerHeartbeatSucceededEvent,
-      TopologyDescriptionChangedEvent,
+      SdamEventHandler, ServerClosedEvent, ServerDescriptionChangedEvent,
+      ServerHeartbeatFailedEvent, ServerHeartbeatStartedEvent, ServerHeartbeatSucceededEvent,
+      ServerOpeningEvent, TopologyClosedEvent, TopologyDescriptionChangedEvent,
+      TopologyOpeningEvent, TopologyType,
     },
   },
+  options::ServerAddress,
   ServerType,
 };
 use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+  query_shape::{QueryShapeAggregator, QueryShapeStatistics, ShapedOperation},
+  server_status::is_server_status_poll,
+  store::current_time_millis,
+};
+
+/// Tauri event emitted for every failover, election or primary step down.
+pub const TOPOLOGY_FAILOVER_EVENT: &str = "mongodb_topology_failover";
 
 lazy_static! {
   pub static ref DATABASE_TOPOLOGY: Arc<Mutex<DatabaseTopology>> =
@@ -26,17 +44,39 @@ lazy_static! {
     Arc::new(Mutex::new(DatabaseHeartbeat::default()));
   pub static ref SERVER_METRIC: Arc<Mutex<DatabaseMetric>> =
     Arc::new(Mutex::new(DatabaseMetric::default()));
+  pub static ref CONNECTION_POOL: Arc<Mutex<DatabaseConnectionPool>> =
+    Arc::new(Mutex::new(DatabaseConnectionPool::default()));
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct DatabaseTopology {
+  topology_type: SerializableTopologyType,
+  set_name: Option<String>,
+  max_set_version: Option<i32>,
+  max_election_id: Option<String>,
+  compatibility_error: Option<String>,
   servers: Vec<ServerDescription>,
+  // The driver does not expose these through `ServerInfo`, so they are taken from the heartbeats.
+  hello_replies: HashMap<String, Document>,
+  errors: HashMap<String, String>,
+  timeline: VecDeque<TopologyEvent>,
+  /// The address of the latest primary seen, kept while the replica set has no primary.
+  last_primary: Option<String>,
 }
 
 impl DatabaseTopology {
-  pub fn replace_document(&mut self, event: TopologyDescriptionChangedEvent) {
-    let servers = event
-      .new_description
+  /// Replaces the servers with the new description and returns what changed since the previous one.
+  pub fn replace_document(&mut self, event: TopologyDescriptionChangedEvent) -> Vec<TopologyEvent> {
+    let previous_servers = std::mem::take(&mut self.servers);
+    let previous_election_id = self.max_election_id.clone();
+    let last_primary = self.last_primary.clone();
+    let description = &event.new_description;
+    self.topology_type = SerializableTopologyType::from(description.topology_type());
+    self.set_name = description.set_name().cloned();
+    self.max_set_version = description.max_set_version();
+    self.max_election_id = description.max_election_id().map(|v| v.to_hex());
+    self.compatibility_error = description.compatibility_error().cloned();
+    let mut servers = description
       .servers()
       .iter()
       .map(|(address, info)| ServerDescription {
@@ -44,38 +84,376 @@ impl DatabaseTopology {
         average_round_trip_time: info
           .average_round_trip_time()
           .map(|v| v.as_millis().to_string()),
-        last_update_time: info.last_update_time().map(|v| v.to_rfc3339_string()),
+        last_update_time: info
+          .last_update_time()
+          .and_then(|v| v.try_to_rfc3339_string().ok()),
         max_wire_version: info.max_wire_version(),
         min_wire_version: info.min_wire_version(),
-        replicat_set_name: info.replica_set_name().map(|s| s.to_string()),
-        replicate_set_version: info.replica_set_version(),
+        replica_set_name: info.replica_set_name().map(|s| s.to_string()),
+        replica_set_version: info.replica_set_version(),
         server_type: SerializableServerType::from(info.server_type()),
         tags: info.tags().map(|s| {
           s.iter()
             .map(|(k, v)| (k.clone(), Bson::String(v.clone())))
             .collect()
         }),
+        error: None,
+        election_id: None,
+        primary: None,
+        hosts: Vec::default(),
+        passives: Vec::default(),
+        arbiters: Vec::default(),
+        logical_session_timeout_minutes: None,
+        stale: false,
       })
       .collect::<Vec<_>>();
+    servers.sort_by(|l, r| l.address.cmp(&r.address));
     self.servers = servers;
+    self.refresh_servers();
+    if let Some(primary) = find_primary(&self.servers) {
+      self.last_primary = Some(primary.address.clone());
+    }
+
+    diff_servers(
+      &previous_servers,
+      &self.servers,
+      last_primary.as_deref(),
+      previous_election_id.as_deref(),
+      self.max_election_id.as_deref(),
+    )
+    .into_iter()
+    .map(|kind| self.push_timeline(kind))
+    .collect()
+  }
+
+  fn push_timeline(&mut self, kind: TopologyEventKind) -> TopologyEvent {
+    let event = TopologyEvent {
+      timestamp: current_time_millis(),
+      kind,
+    };
+    if self.timeline.len() == TOPOLOGY_TIMELINE_SIZE {
+      self.timeline.pop_front();
+    }
+    self.timeline.push_back(event.clone());
+    event
+  }
+
+  pub fn add_topology_opening_event(&mut self, event: TopologyOpeningEvent) {
+    self.push_timeline(TopologyEventKind::TopologyOpened {
+      topology_id: event.topology_id.to_hex(),
+    });
+  }
+
+  pub fn add_topology_closed_event(&mut self, event: TopologyClosedEvent) {
+    self.push_timeline(TopologyEventKind::TopologyClosed {
+      topology_id: event.topology_id.to_hex(),
+    });
+  }
+
+  pub fn add_server_opening_event(&mut self, event: ServerOpeningEvent) {
+    self.push_timeline(TopologyEventKind::ServerAdded {
+      address: event.address.to_string(),
+    });
+  }
+
+  pub fn add_server_closed_event(&mut self, event: ServerClosedEvent) {
+    self.push_timeline(TopologyEventKind::ServerRemoved {
+      address: event.address.to_string(),
+    });
+  }
+
+  pub fn add_server_description_changed_event(&mut self, event: ServerDescriptionChangedEvent) {
+    let previous = SerializableServerType::from(event.previous_description.server_type());
+    let new = SerializableServerType::from(event.new_description.server_type());
+    if previous != new {
+      self.push_timeline(TopologyEventKind::ServerTypeChanged {
+        address: event.address.to_string(),
+        previous,
+        new,
+      });
+    }
+  }
+
+  /// Returns the timeline entries that happened at or after `since` in milliseconds since epoch.
+  pub fn get_topology_timeline(&self, since: u64) -> Vec<TopologyEvent> {
+    self
+      .timeline
+      .iter()
+      .filter(|event| event.timestamp >= since)
+      .cloned()
+      .collect()
+  }
+
+  pub fn add_hello_reply(&mut self, address: String, reply: Document) {
+    self.errors.remove(&address);
+    self.hello_replies.insert(address, reply);
+    self.refresh_servers();
+  }
+
+  pub fn add_error(&mut self, address: String, error: String) {
+    self.errors.insert(address, error);
+    self.refresh_servers();
+  }
+
+  /// Fills in what is known from the latest heartbeat of every server.
+  fn refresh_servers(&mut self) {
+    for server in &mut self.servers {
+      server.error = self.errors.get(&server.address).cloned();
+      if let Some(reply) = self.hello_replies.get(&server.address) {
+        server.election_id = reply.get_object_id("electionId").ok().map(|v| v.to_hex());
+        server.primary = reply.get_str("primary").ok().map(|v| v.to_string());
+        server.hosts = get_string_array(reply, "hosts");
+        server.passives = get_string_array(reply, "passives");
+        server.arbiters = get_string_array(reply, "arbiters");
+        server.logical_session_timeout_minutes = match reply.get("logicalSessionTimeoutMinutes") {
+          Some(Bson::Int32(v)) => Some(*v as i64),
+          Some(Bson::Int64(v)) => Some(*v),
+          _ => None,
+        };
+      }
+      // A primary reporting an older (setVersion, electionId) than the topology has seen is stale.
+      server.stale = server.server_type == SerializableServerType::RsPrimary
+        && (server.replica_set_version, server.election_id.as_deref())
+          < (self.max_set_version, self.max_election_id.as_deref());
+    }
+  }
+
+  pub fn get_database_topology(&self) -> DatabaseTopologyDescription {
+    DatabaseTopologyDescription {
+      topology_type: self.topology_type.clone(),
+      set_name: self.set_name.clone(),
+      max_set_version: self.max_set_version,
+      max_election_id: self.max_election_id.clone(),
+      compatibility_error: self.compatibility_error.clone(),
+      servers: self.servers.clone(),
+    }
+  }
+
+  pub fn get_server_count(&self) -> usize {
+    self.servers.len()
   }
 
-  pub fn get_database_topology(&self) -> Vec<ServerDescription> {
-    self.servers.clone()
+  pub fn get_server_count_per_type(&self) -> BTreeMap<String, usize> {
+    let mut result = BTreeMap::new();
+    for server in &self.servers {
+      *result
+        .entry(server.server_type.name().to_string())
+        .or_default() += 1;
+    }
+    result
   }
 }
 
+/// Number of topology changes kept in the timeline.
+const TOPOLOGY_TIMELINE_SIZE: usize = 1000;
+
+/// A round trip time is considered a spike when it grows by this factor...
+const ROUND_TRIP_TIME_SPIKE_FACTOR: u64 = 3;
+/// ...and by at least this many milliseconds.
+const ROUND_TRIP_TIME_SPIKE_MIN_MILLIS: u64 = 10;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum TopologyEventKind {
+  TopologyOpened {
+    topology_id: String,
+  },
+  TopologyClosed {
+    topology_id: String,
+  },
+  ServerAdded {
+    address: String,
+  },
+  ServerRemoved {
+    address: String,
+  },
+  ServerTypeChanged {
+    address: String,
+    previous: SerializableServerType,
+    new: SerializableServerType,
+  },
+  PrimarySteppedDown {
+    address: String,
+  },
+  PrimaryElected {
+    address: String,
+    election_id: Option<String>,
+  },
+  Failover {
+    previous_primary: String,
+    new_primary: String,
+  },
+  RoundTripTimeSpike {
+    address: String,
+    previous: u64,
+    new: u64,
+  },
+}
+
+impl TopologyEventKind {
+  pub fn is_failover_or_election(&self) -> bool {
+    matches!(
+      self,
+      TopologyEventKind::PrimarySteppedDown { .. }
+        | TopologyEventKind::PrimaryElected { .. }
+        | TopologyEventKind::Failover { .. }
+    )
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TopologyEvent {
+  /// Milliseconds since epoch when the change was observed.
+  pub timestamp: u64,
+  pub kind: TopologyEventKind,
+}
+
+fn find_primary(servers: &[ServerDescription]) -> Option<&ServerDescription> {
+  servers
+    .iter()
+    .find(|server| server.server_type == SerializableServerType::RsPrimary)
+}
+
+fn parse_round_trip_time(server: &ServerDescription) -> Option<u64> {
+  server.average_round_trip_time.as_ref()?.parse().ok()
+}
+
+/// Compares two consecutive descriptions. `last_primary` is the primary seen before `servers`,
+/// possibly in an older description, so that the first primary found after connecting is not
+/// reported as an election.
+fn diff_servers(
+  previous_servers: &[ServerDescription],
+  servers: &[ServerDescription],
+  last_primary: Option<&str>,
+  previous_election_id: Option<&str>,
+  election_id: Option<&str>,
+) -> Vec<TopologyEventKind> {
+  let mut result = Vec::new();
+  for server in servers {
+    let previous = match previous_servers
+      .iter()
+      .find(|s| s.address == server.address)
+    {
+      Some(previous) => previous,
+      None => continue,
+    };
+    if let (Some(previous_rtt), Some(rtt)) = (
+      parse_round_trip_time(previous),
+      parse_round_trip_time(server),
+    ) {
+      if rtt > previous_rtt * ROUND_TRIP_TIME_SPIKE_FACTOR
+        && rtt - previous_rtt >= ROUND_TRIP_TIME_SPIKE_MIN_MILLIS
+      {
+        result.push(TopologyEventKind::RoundTripTimeSpike {
+          address: server.address.clone(),
+          previous: previous_rtt,
+          new: rtt,
+        });
+      }
+    }
+  }
+  let previous_primary = find_primary(previous_servers).map(|s| s.address.as_str());
+  let primary = find_primary(servers).map(|s| s.address.as_str());
+  if let Some(previous_primary) = previous_primary {
+    if primary != Some(previous_primary) {
+      result.push(TopologyEventKind::PrimarySteppedDown {
+        address: previous_primary.to_string(),
+      });
+    }
+  }
+  if let (Some(last_primary), Some(primary)) = (last_primary, primary) {
+    // A new election id with the same primary means that it was re-elected.
+    if last_primary != primary || previous_election_id != election_id {
+      result.push(TopologyEventKind::PrimaryElected {
+        address: primary.to_string(),
+        election_id: election_id.map(|v| v.to_string()),
+      });
+    }
+    if last_primary != primary {
+      result.push(TopologyEventKind::Failover {
+        previous_primary: last_primary.to_string(),
+        new_primary: primary.to_string(),
+      });
+    }
+  }
+  result
+}
+
+fn get_string_array(document: &Document, key: &str) -> Vec<String> {
+  document
+    .get_array(key)
+    .map(|v| {
+      v.iter()
+        .filter_map(|v| v.as_str().map(|v| v.to_string()))
+        .collect()
+    })
+    .unwrap_or_default()
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SerializableTopologyType {
+  Single,
+  ReplicaSetNoPrimary,
+  ReplicaSetWithPrimary,
+  Sharded,
+  LoadBalanced,
+  #[default]
+  Unknown,
+}
+
+impl From<TopologyType> for SerializableTopologyType {
+  fn from(t: TopologyType) -> Self {
+    match t {
+      TopologyType::Single => SerializableTopologyType::Single,
+      TopologyType::ReplicaSetNoPrimary => SerializableTopologyType::ReplicaSetNoPrimary,
+      TopologyType::ReplicaSetWithPrimary => SerializableTopologyType::ReplicaSetWithPrimary,
+      TopologyType::Sharded => SerializableTopologyType::Sharded,
+      TopologyType::LoadBalanced => SerializableTopologyType::LoadBalanced,
+      _ => SerializableTopologyType::Unknown,
+    }
+  }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum SerializableServerType {
   Standalone,
-  Other(String),
+  Mongos,
+  RsPrimary,
+  RsSecondary,
+  RsArbiter,
+  RsOther,
+  RsGhost,
+  LoadBalancer,
+  Unknown,
+}
+
+impl SerializableServerType {
+  pub fn name(&self) -> &'static str {
+    match self {
+      SerializableServerType::Standalone => "Standalone",
+      SerializableServerType::Mongos => "Mongos",
+      SerializableServerType::RsPrimary => "RsPrimary",
+      SerializableServerType::RsSecondary => "RsSecondary",
+      SerializableServerType::RsArbiter => "RsArbiter",
+      SerializableServerType::RsOther => "RsOther",
+      SerializableServerType::RsGhost => "RsGhost",
+      SerializableServerType::LoadBalancer => "LoadBalancer",
+      SerializableServerType::Unknown => "Unknown",
+    }
+  }
 }
 
 impl From<ServerType> for SerializableServerType {
   fn from(s: ServerType) -> Self {
     match s {
       ServerType::Standalone => SerializableServerType::Standalone,
-      o => SerializableServerType::Other(format!("{:#?}", o)),
+      ServerType::Mongos => SerializableServerType::Mongos,
+      ServerType::RsPrimary => SerializableServerType::RsPrimary,
+      ServerType::RsSecondary => SerializableServerType::RsSecondary,
+      ServerType::RsArbiter => SerializableServerType::RsArbiter,
+      ServerType::RsOther => SerializableServerType::RsOther,
+      ServerType::RsGhost => SerializableServerType::RsGhost,
+      ServerType::LoadBalancer => SerializableServerType::LoadBalancer,
+      _ => SerializableServerType::Unknown,
     }
   }
 }
@@ -87,73 +465,538 @@ pub struct ServerDescription {
   pub last_update_time: Option<String>,
   pub max_wire_version: Option<i32>,
   pub min_wire_version: Option<i32>,
-  pub replicat_set_name: Option<String>,
-  pub replicate_set_version: Option<i32>,
+  pub replica_set_name: Option<String>,
+  pub replica_set_version: Option<i32>,
   pub server_type: SerializableServerType,
   pub tags: Option<Document>,
+  pub error: Option<String>,
+  pub election_id: Option<String>,
+  pub primary: Option<String>,
+  pub hosts: Vec<String>,
+  pub passives: Vec<String>,
+  pub arbiters: Vec<String>,
+  pub logical_session_timeout_minutes: Option<i64>,
+  pub stale: bool,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DatabaseTopologyDescription {
+  pub topology_type: SerializableTopologyType,
+  pub set_name: Option<String>,
+  pub max_set_version: Option<i32>,
+  pub max_election_id: Option<String>,
+  pub compatibility_error: Option<String>,
+  pub servers: Vec<ServerDescription>,
+}
+
+/// Number of heartbeats kept for each server.
+const HEARTBEAT_HISTORY_SIZE: usize = 100;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HeartbeatRecord {
+  /// Milliseconds since epoch when the heartbeat finished.
+  pub timestamp: u64,
+  /// Nanoseconds taken by the heartbeat.
+  pub duration: u64,
+  pub error: Option<String>,
+  /// The driver only polls for now, see RUST-560, so this is never set.
+  pub awaited: bool,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct HeartbeatStatistics {
+  pub succeeded: usize,
+  pub failed: usize,
+  /// Durations of the successful heartbeats in nanoseconds.
+  pub min_duration: u64,
+  pub average_duration: u64,
+  pub max_duration: u64,
+  /// Mean absolute difference between consecutive successful heartbeat durations.
+  pub jitter: u64,
+  pub current_failure_streak: usize,
+  pub longest_failure_streak: usize,
+  pub last_error: Option<String>,
+}
+
+impl HeartbeatStatistics {
+  fn from_records(records: &VecDeque<HeartbeatRecord>) -> HeartbeatStatistics {
+    let mut statistics = HeartbeatStatistics::default();
+    let mut total_duration = 0;
+    let mut total_jitter = 0;
+    let mut previous_duration = None;
+    let mut failure_streak = 0;
+    for record in records {
+      match &record.error {
+        Some(error) => {
+          statistics.failed += 1;
+          statistics.last_error = Some(error.clone());
+          failure_streak += 1;
+          statistics.longest_failure_streak = statistics.longest_failure_streak.max(failure_streak);
+        }
+        None => {
+          if statistics.succeeded == 0 {
+            statistics.min_duration = record.duration;
+          }
+          statistics.succeeded += 1;
+          statistics.min_duration = statistics.min_duration.min(record.duration);
+          statistics.max_duration = statistics.max_duration.max(record.duration);
+          total_duration += record.duration;
+          if let Some(previous_duration) = previous_duration {
+            total_jitter += (record.duration as i64 - previous_duration as i64).unsigned_abs();
+          }
+          previous_duration = Some(record.duration);
+          failure_streak = 0;
+        }
+      }
+    }
+    statistics.current_failure_streak = failure_streak;
+    statistics.average_duration = total_duration
+      .checked_div(statistics.succeeded as u64)
+      .unwrap_or(0);
+    statistics.jitter = total_jitter
+      .checked_div(statistics.succeeded.saturating_sub(1) as u64)
+      .unwrap_or(0);
+    statistics
+  }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
-pub enum FinishedHeartbeat {
-  SUCCEEDED(usize),
-  FAILED(usize),
+pub struct ServerHeartbeats {
+  pub heartbeats: Vec<HeartbeatRecord>,
+  pub statistics: HeartbeatStatistics,
+  /// Milliseconds since epoch when the heartbeat that is still running was started.
+  pub in_progress_since: Option<u64>,
+}
+
+/// Heartbeat counters accumulated since the last time they were taken by the metrics store.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct HeartbeatRollup {
+  pub succeeded: u64,
+  pub failed: u64,
+  pub total_duration: u64,
 }
 
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct DatabaseHeartbeat {
-  pub duration: Vec<FinishedHeartbeat>,
+  pub servers: BTreeMap<String, VecDeque<HeartbeatRecord>>,
+  pub in_progress: BTreeMap<String, u64>,
+  pub rollup: HeartbeatRollup,
+  pub started_total: u64,
+  pub succeeded_total: u64,
+  pub failed_total: u64,
 }
 
 impl DatabaseHeartbeat {
-  pub fn add_succeeded_event(&mut self, event: ServerHeartbeatSucceededEvent) {
-    self.duration.push(FinishedHeartbeat::SUCCEEDED(
-      event.duration.as_nanos() as usize
-    ));
-    if self.duration.len() == 100 {
-      self.duration.remove(0);
+  pub fn add_started_event(&mut self, event: ServerHeartbeatStartedEvent) {
+    self
+      .in_progress
+      .insert(event.server_address.to_string(), current_time_millis());
+    self.started_total += 1;
+  }
+
+  fn add_record(&mut self, address: String, record: HeartbeatRecord) {
+    self.in_progress.remove(&address);
+    let records = self.servers.entry(address).or_default();
+    if records.len() == HEARTBEAT_HISTORY_SIZE {
+      records.pop_front();
     }
+    records.push_back(record);
+  }
+
+  pub fn add_succeeded_event(&mut self, event: ServerHeartbeatSucceededEvent) {
+    let duration = event.duration.as_nanos() as u64;
+    self.add_record(
+      event.server_address.to_string(),
+      HeartbeatRecord {
+        timestamp: current_time_millis(),
+        duration,
+        error: None,
+        awaited: false,
+      },
+    );
+    self.succeeded_total += 1;
+    self.rollup.succeeded += 1;
+    self.rollup.total_duration += duration;
   }
 
   pub fn add_failed_event(&mut self, event: ServerHeartbeatFailedEvent) {
-    self
-      .duration
-      .push(FinishedHeartbeat::FAILED(event.duration.as_nanos() as usize));
-    if self.duration.len() == 100 {
-      self.duration.remove(0);
-    }
+    let duration = event.duration.as_nanos() as u64;
+    self.add_record(
+      event.server_address.to_string(),
+      HeartbeatRecord {
+        timestamp: current_time_millis(),
+        duration,
+        error: Some(format!("{}", event.failure)),
+        awaited: false,
+      },
+    );
+    self.failed_total += 1;
+    self.rollup.failed += 1;
+    self.rollup.total_duration += duration;
+  }
+
+  pub fn take_rollup(&mut self) -> HeartbeatRollup {
+    std::mem::take(&mut self.rollup)
   }
 
+  /// Returns the latest heartbeats of every server as `(succeeded, failed)` durations.
   pub fn get_connection_heartbeat(&self) -> Vec<(usize, usize)> {
+    let mut records = self.servers.values().flatten().collect::<Vec<_>>();
+    records.sort_by_key(|record| record.timestamp);
+    let skip = records.len().saturating_sub(HEARTBEAT_HISTORY_SIZE);
+    records
+      .into_iter()
+      .skip(skip)
+      .map(|record| match record.error {
+        None => (record.duration as usize, 0),
+        Some(_) => (0, record.duration as usize),
+      })
+      .collect()
+  }
+
+  pub fn get_server_heartbeats(&self) -> BTreeMap<String, ServerHeartbeats> {
     self
-      .duration
+      .servers
       .iter()
-      .map(|v| match v {
-        FinishedHeartbeat::SUCCEEDED(s) => (*s, 0),
-        FinishedHeartbeat::FAILED(s) => (0, *s),
+      .map(|(address, records)| {
+        (
+          address.clone(),
+          ServerHeartbeats {
+            heartbeats: records.iter().cloned().collect(),
+            statistics: HeartbeatStatistics::from_records(records),
+            in_progress_since: self.in_progress.get(address).cloned(),
+          },
+        )
       })
       .collect()
   }
 }
 
-pub struct ServerInfoHandler;
+pub struct ServerInfoHandler {
+  pub app_handle: AppHandle,
+}
 
 impl SdamEventHandler for ServerInfoHandler {
-  fn handle_topology_description_changed_event(&self, event: TopologyDescriptionChangedEvent) {
+  fn handle_server_description_changed_event(&self, event: ServerDescriptionChangedEvent) {
+    let mut handle = DATABASE_TOPOLOGY.as_ref().lock().unwrap();
+    handle.add_server_description_changed_event(event);
+  }
+
+  fn handle_server_opening_event(&self, event: ServerOpeningEvent) {
     let mut handle = DATABASE_TOPOLOGY.as_ref().lock().unwrap();
-    handle.replace_document(event);
+    handle.add_server_opening_event(event);
+  }
+
+  fn handle_server_closed_event(&self, event: ServerClosedEvent) {
+    let mut handle = DATABASE_TOPOLOGY.as_ref().lock().unwrap();
+    handle.add_server_closed_event(event);
+  }
+
+  fn handle_topology_opening_event(&self, event: TopologyOpeningEvent) {
+    let mut handle = DATABASE_TOPOLOGY.as_ref().lock().unwrap();
+    handle.add_topology_opening_event(event);
+  }
+
+  fn handle_topology_closed_event(&self, event: TopologyClosedEvent) {
+    let mut handle = DATABASE_TOPOLOGY.as_ref().lock().unwrap();
+    handle.add_topology_closed_event(event);
+  }
+
+  fn handle_server_heartbeat_started_event(&self, event: ServerHeartbeatStartedEvent) {
+    let mut handle = DATABASE_HEARTBEAT.as_ref().lock().unwrap();
+    handle.add_started_event(event);
+  }
+
+  fn handle_topology_description_changed_event(&self, event: TopologyDescriptionChangedEvent) {
+    let events = {
+      let mut handle = DATABASE_TOPOLOGY.as_ref().lock().unwrap();
+      handle.replace_document(event)
+    };
+    for event in events {
+      if event.kind.is_failover_or_election() {
+        if let Err(e) = self.app_handle.emit_all(TOPOLOGY_FAILOVER_EVENT, event) {
+          eprintln!("Failed to emit the topology event error:{}", e);
+        }
+      }
+    }
   }
 
   fn handle_server_heartbeat_failed_event(&self, event: ServerHeartbeatFailedEvent) {
+    {
+      let mut handle = DATABASE_TOPOLOGY.as_ref().lock().unwrap();
+      handle.add_error(
+        event.server_address.to_string(),
+        format!("{}", event.failure),
+      );
+    }
     let mut handle = DATABASE_HEARTBEAT.as_ref().lock().unwrap();
     handle.add_failed_event(event);
   }
 
   fn handle_server_heartbeat_succeeded_event(&self, event: ServerHeartbeatSucceededEvent) {
+    {
+      let mut handle = DATABASE_TOPOLOGY.as_ref().lock().unwrap();
+      handle.add_hello_reply(event.server_address.to_string(), event.reply.clone());
+    }
     let mut handle = DATABASE_HEARTBEAT.as_ref().lock().unwrap();
     handle.add_succeeded_event(event);
   }
 }
 
+/// The driver's default `maxPoolSize`.
+const DEFAULT_MAX_POOL_SIZE: u32 = 10;
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct ConnectionPoolStatistics {
+  pub max_pool_size: u32,
+  pub min_pool_size: u32,
+  pub ready: bool,
+  pub closed: bool,
+  /// Connections that are currently open, whether checked out or idle.
+  pub total_connections: u64,
+  pub checked_out: u64,
+  /// Share of `max_pool_size` that is checked out, between 0 and 1.
+  pub utilization: f64,
+  pub connections_created: u64,
+  pub connections_closed: u64,
+  pub checkouts_started: u64,
+  pub checkouts_succeeded: u64,
+  pub checkouts_failed: u64,
+  pub pool_cleared: u64,
+  pub checkout_wait_time: CheckoutWaitTime,
+  pub checkout_failures: BTreeMap<String, u64>,
+  pub pool_cleared_incidents: VecDeque<PoolClearedIncident>,
+}
+
+/// Number of checkout wait times kept for each server to compute the percentiles.
+const CHECKOUT_WAIT_TIME_HISTORY_SIZE: usize = 1000;
+/// Number of pool cleared incidents kept for each server.
+const POOL_CLEARED_INCIDENT_HISTORY_SIZE: usize = 100;
+
+/// Percentiles of the recent checkout wait times in nanoseconds.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct CheckoutWaitTime {
+  pub p50: u64,
+  pub p90: u64,
+  pub p99: u64,
+  pub max: u64,
+}
+
+impl CheckoutWaitTime {
+  fn from_wait_times(wait_times: &VecDeque<u64>) -> CheckoutWaitTime {
+    let mut wait_times = wait_times.iter().cloned().collect::<Vec<_>>();
+    if wait_times.is_empty() {
+      return CheckoutWaitTime::default();
+    }
+    wait_times.sort_unstable();
+    let percentile = |p: usize| wait_times[(wait_times.len() - 1) * p / 100];
+    CheckoutWaitTime {
+      p50: percentile(50),
+      p90: percentile(90),
+      p99: percentile(99),
+      max: wait_times[wait_times.len() - 1],
+    }
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PoolClearedIncident {
+  /// Milliseconds since epoch when the pool was cleared.
+  pub timestamp: u64,
+  pub service_id: Option<String>,
+  /// Connections that were checked out when the pool was cleared.
+  pub checked_out: u64,
+}
+
+#[derive(Default, Debug, Serialize, Deserialize)]
+pub struct DatabaseConnectionPool {
+  pools: BTreeMap<String, ConnectionPoolStatistics>,
+  // Checkout events do not carry an id, so the checkouts of a server are assumed to finish in the
+  // order that they started.
+  #[serde(skip)]
+  pending_checkouts: HashMap<String, VecDeque<Instant>>,
+  wait_times: HashMap<String, VecDeque<u64>>,
+}
+
+impl DatabaseConnectionPool {
+  fn get_pool(&mut self, address: &ServerAddress) -> &mut ConnectionPoolStatistics {
+    self
+      .pools
+      .entry(address.to_string())
+      .or_insert_with(|| ConnectionPoolStatistics {
+        max_pool_size: DEFAULT_MAX_POOL_SIZE,
+        ..ConnectionPoolStatistics::default()
+      })
+  }
+
+  pub fn add_pool_created_event(&mut self, event: PoolCreatedEvent) {
+    let pool = self.get_pool(&event.address);
+    *pool = ConnectionPoolStatistics {
+      max_pool_size: event
+        .options
+        .as_ref()
+        .and_then(|o| o.max_pool_size)
+        .unwrap_or(DEFAULT_MAX_POOL_SIZE),
+      min_pool_size: event
+        .options
+        .as_ref()
+        .and_then(|o| o.min_pool_size)
+        .unwrap_or(0),
+      ..ConnectionPoolStatistics::default()
+    };
+  }
+
+  pub fn add_pool_ready_event(&mut self, event: PoolReadyEvent) {
+    self.get_pool(&event.address).ready = true;
+  }
+
+  pub fn add_pool_cleared_event(&mut self, event: PoolClearedEvent) {
+    let pool = self.get_pool(&event.address);
+    pool.ready = false;
+    pool.pool_cleared += 1;
+    if pool.pool_cleared_incidents.len() == POOL_CLEARED_INCIDENT_HISTORY_SIZE {
+      pool.pool_cleared_incidents.pop_front();
+    }
+    let incident = PoolClearedIncident {
+      timestamp: current_time_millis(),
+      service_id: event.service_id.map(|v| v.to_hex()),
+      checked_out: pool.checked_out,
+    };
+    pool.pool_cleared_incidents.push_back(incident);
+  }
+
+  /// Returns how long the oldest pending checkout of `address` has waited.
+  fn finish_checkout(&mut self, address: &ServerAddress) -> Option<u64> {
+    let address = address.to_string();
+    let started = self.pending_checkouts.get_mut(&address)?.pop_front()?;
+    let wait_time = started.elapsed().as_nanos() as u64;
+    let wait_times = self.wait_times.entry(address).or_default();
+    if wait_times.len() == CHECKOUT_WAIT_TIME_HISTORY_SIZE {
+      wait_times.pop_front();
+    }
+    wait_times.push_back(wait_time);
+    Some(wait_time)
+  }
+
+  pub fn add_pool_closed_event(&mut self, event: PoolClosedEvent) {
+    let pool = self.get_pool(&event.address);
+    pool.ready = false;
+    pool.closed = true;
+  }
+
+  pub fn add_connection_created_event(&mut self, event: ConnectionCreatedEvent) {
+    let pool = self.get_pool(&event.address);
+    pool.connections_created += 1;
+    pool.total_connections += 1;
+  }
+
+  pub fn add_connection_closed_event(&mut self, event: ConnectionClosedEvent) {
+    let pool = self.get_pool(&event.address);
+    pool.connections_closed += 1;
+    pool.total_connections = pool.total_connections.saturating_sub(1);
+  }
+
+  pub fn add_connection_checkout_started_event(&mut self, event: ConnectionCheckoutStartedEvent) {
+    self
+      .pending_checkouts
+      .entry(event.address.to_string())
+      .or_default()
+      .push_back(Instant::now());
+    self.get_pool(&event.address).checkouts_started += 1;
+  }
+
+  pub fn add_connection_checkout_failed_event(&mut self, event: ConnectionCheckoutFailedEvent) {
+    self.finish_checkout(&event.address);
+    let pool = self.get_pool(&event.address);
+    pool.checkouts_failed += 1;
+    *pool
+      .checkout_failures
+      .entry(format!("{:?}", event.reason))
+      .or_default() += 1;
+  }
+
+  pub fn add_connection_checked_out_event(&mut self, event: ConnectionCheckedOutEvent) {
+    self.finish_checkout(&event.address);
+    let pool = self.get_pool(&event.address);
+    pool.checkouts_succeeded += 1;
+    pool.checked_out += 1;
+  }
+
+  pub fn add_connection_checked_in_event(&mut self, event: ConnectionCheckedInEvent) {
+    let pool = self.get_pool(&event.address);
+    pool.checked_out = pool.checked_out.saturating_sub(1);
+  }
+
+  pub fn get_connection_pools(&self) -> BTreeMap<String, ConnectionPoolStatistics> {
+    self
+      .pools
+      .iter()
+      .map(|(address, pool)| {
+        let mut pool = pool.clone();
+        pool.utilization = pool.checked_out as f64 / pool.max_pool_size.max(1) as f64;
+        if let Some(wait_times) = self.wait_times.get(address) {
+          pool.checkout_wait_time = CheckoutWaitTime::from_wait_times(wait_times);
+        }
+        (address.clone(), pool)
+      })
+      .collect()
+  }
+}
+
+pub struct ConnectionPoolHandler;
+
+impl CmapEventHandler for ConnectionPoolHandler {
+  fn handle_pool_created_event(&self, event: PoolCreatedEvent) {
+    let mut handle = CONNECTION_POOL.as_ref().lock().unwrap();
+    handle.add_pool_created_event(event);
+  }
+
+  fn handle_pool_ready_event(&self, event: PoolReadyEvent) {
+    let mut handle = CONNECTION_POOL.as_ref().lock().unwrap();
+    handle.add_pool_ready_event(event);
+  }
+
+  fn handle_pool_cleared_event(&self, event: PoolClearedEvent) {
+    let mut handle = CONNECTION_POOL.as_ref().lock().unwrap();
+    handle.add_pool_cleared_event(event);
+  }
+
+  fn handle_pool_closed_event(&self, event: PoolClosedEvent) {
+    let mut handle = CONNECTION_POOL.as_ref().lock().unwrap();
+    handle.add_pool_closed_event(event);
+  }
+
+  fn handle_connection_created_event(&self, event: ConnectionCreatedEvent) {
+    let mut handle = CONNECTION_POOL.as_ref().lock().unwrap();
+    handle.add_connection_created_event(event);
+  }
+
+  fn handle_connection_closed_event(&self, event: ConnectionClosedEvent) {
+    let mut handle = CONNECTION_POOL.as_ref().lock().unwrap();
+    handle.add_connection_closed_event(event);
+  }
+
+  fn handle_connection_checkout_started_event(&self, event: ConnectionCheckoutStartedEvent) {
+    let mut handle = CONNECTION_POOL.as_ref().lock().unwrap();
+    handle.add_connection_checkout_started_event(event);
+  }
+
+  fn handle_connection_checkout_failed_event(&self, event: ConnectionCheckoutFailedEvent) {
+    let mut handle = CONNECTION_POOL.as_ref().lock().unwrap();
+    handle.add_connection_checkout_failed_event(event);
+  }
+
+  fn handle_connection_checked_out_event(&self, event: ConnectionCheckedOutEvent) {
+    let mut handle = CONNECTION_POOL.as_ref().lock().unwrap();
+    handle.add_connection_checked_out_event(event);
+  }
+
+  fn handle_connection_checked_in_event(&self, event: ConnectionCheckedInEvent) {
+    let mut handle = CONNECTION_POOL.as_ref().lock().unwrap();
+    handle.add_connection_checked_in_event(event);
+  }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CommandStatusFailed {
   pub time_taken: u64,
@@ -177,16 +1020,23 @@ pub enum CommandStatus {
 pub struct CommandStatistics {
   pub request_id: i32,
   pub name: String,
+  pub namespace: String,
   pub status: CommandStatus,
   pub command: Document,
   pub intercepted_time: usize,
 }
 
 impl CommandStatistics {
-  fn new(request_id: i32, name: String, command: Document) -> CommandStatistics {
+  fn new(request_id: i32, name: String, db: String, command: Document) -> CommandStatistics {
+    // Collection-level commands carry the collection name as the value of the command name.
+    let namespace = match command.get_str(&name) {
+      Ok(collection) => format!("{}.{}", db, collection),
+      Err(_) => db,
+    };
     CommandStatistics {
       request_id,
       name,
+      namespace,
       command,
       status: CommandStatus::STARTED,
       intercepted_time: SystemTime::now()
@@ -214,19 +1064,39 @@ impl FinishedCommandInfo {
   }
 }
 
+/// Command counters for the commands intercepted within a time range.
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct CommandsRollup {
+  pub started: u64,
+  pub succeeded: u64,
+  pub failed: u64,
+  pub total_time_taken: u64,
+}
+
 #[derive(Default, Clone, Debug, Serialize, Deserialize)]
 pub struct DatabaseMetric {
   commands: HashMap<i32, CommandStatistics>,
   // FIXME: Prevents duplicate keys, reimplement using a simple Vec
   slowest_commands: BTreeMap<u64, i32>,
+  /// Request ids of the `serverStatus` polls in flight, which are not counted.
+  ignored_commands: HashSet<i32>,
 }
 
 impl DatabaseMetric {
   pub fn add_init_command(&mut self, event: CommandStartedEvent) {
+    if is_server_status_poll(&event.command_name, &event.db) {
+      self.ignored_commands.insert(event.request_id);
+      return;
+    }
     // Insert into commands
     let old_cmd_stat = self.commands.insert(
       event.request_id,
-      CommandStatistics::new(event.request_id, event.command_name, event.command),
+      CommandStatistics::new(
+        event.request_id,
+        event.command_name,
+        event.db,
+        event.command,
+      ),
     );
     if let Some(cmd_stat) = old_cmd_stat {
       eprintln!(
@@ -237,6 +1107,9 @@ impl DatabaseMetric {
   }
 
   pub fn add_failed_command(&mut self, event: CommandFailedEvent) {
+    if self.ignored_commands.remove(&event.request_id) {
+      return;
+    }
     if let Some(cmd_stat) = self.commands.get_mut(&event.request_id) {
       let time_taken = event.duration.as_nanos() as u64;
       cmd_stat.status = CommandStatus::FAILED(CommandStatusFailed {
@@ -253,6 +1126,9 @@ impl DatabaseMetric {
   }
 
   pub fn add_successful_command(&mut self, event: CommandSucceededEvent) {
+    if self.ignored_commands.remove(&event.request_id) {
+      return;
+    }
     if let Some(cmd_stat) = self.commands.get_mut(&event.request_id) {
       let time_taken = event.duration.as_nanos() as u64;
       cmd_stat.status = CommandStatus::SUCCESSFUL(CommandStatuSuccessful {
@@ -301,6 +1177,68 @@ impl DatabaseMetric {
     result
   }
 
+  /// Counts the commands intercepted within `[from, until)`, both in milliseconds since epoch.
+  pub fn get_commands_rollup(&self, from: usize, until: usize) -> CommandsRollup {
+    let mut rollup = CommandsRollup::default();
+    for cmd_stat in self.commands.values() {
+      if cmd_stat.intercepted_time < from || cmd_stat.intercepted_time >= until {
+        continue;
+      }
+      rollup.started += 1;
+      match cmd_stat.status {
+        CommandStatus::STARTED => {}
+        CommandStatus::FAILED(CommandStatusFailed { time_taken, .. }) => {
+          rollup.failed += 1;
+          rollup.total_time_taken += time_taken;
+        }
+        CommandStatus::SUCCESSFUL(CommandStatuSuccessful { time_taken, .. }) => {
+          rollup.succeeded += 1;
+          rollup.total_time_taken += time_taken;
+        }
+      }
+    }
+    rollup
+  }
+
+  /// Returns the name, namespace and time taken in nanoseconds of every finished command.
+  pub fn get_finished_commands(&self) -> Vec<(&str, &str, u64)> {
+    self
+      .commands
+      .values()
+      .filter_map(|cmd_stat| match cmd_stat.status {
+        CommandStatus::STARTED => None,
+        CommandStatus::FAILED(CommandStatusFailed { time_taken, .. })
+        | CommandStatus::SUCCESSFUL(CommandStatuSuccessful { time_taken, .. }) => Some((
+          cmd_stat.name.as_str(),
+          cmd_stat.namespace.as_str(),
+          time_taken,
+        )),
+      })
+      .collect()
+  }
+
+  /// Groups the finished commands by query shape, the same way as the profiled operations.
+  pub fn get_commands_by_shape(&self) -> Vec<QueryShapeStatistics> {
+    let mut aggregator = QueryShapeAggregator::default();
+    for cmd_stat in self.commands.values() {
+      let time_taken = match cmd_stat.status {
+        CommandStatus::STARTED => continue,
+        CommandStatus::FAILED(CommandStatusFailed { time_taken, .. })
+        | CommandStatus::SUCCESSFUL(CommandStatuSuccessful { time_taken, .. }) => time_taken,
+      };
+      aggregator.add(ShapedOperation {
+        command_name: &cmd_stat.name,
+        namespace: &cmd_stat.namespace,
+        command: &cmd_stat.command,
+        millis: time_taken as f64 / 1e6,
+        docs_examined: None,
+        n_returned: None,
+        plan_summary: None,
+      });
+    }
+    aggregator.into_statistics()
+  }
+
   pub fn get_n_slowest_commands(&self, n: usize) -> Vec<FinishedCommandInfo> {
     self
       .slowest_commands
@@ -346,3 +1284,103 @@ impl CommandEventHandler for CommandInfoHandler {
     handle.add_failed_command(event);
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn server(address: &str, server_type: SerializableServerType) -> ServerDescription {
+    ServerDescription {
+      address: address.to_string(),
+      average_round_trip_time: None,
+      last_update_time: None,
+      max_wire_version: None,
+      min_wire_version: None,
+      replica_set_name: None,
+      replica_set_version: None,
+      server_type,
+      tags: None,
+      error: None,
+      election_id: None,
+      primary: None,
+      hosts: Vec::new(),
+      passives: Vec::new(),
+      arbiters: Vec::new(),
+      logical_session_timeout_minutes: None,
+      stale: false,
+    }
+  }
+
+  #[test]
+  fn first_primary_is_not_an_election() {
+    let servers = vec![
+      server("a:27017", SerializableServerType::RsPrimary),
+      server("b:27017", SerializableServerType::RsSecondary),
+    ];
+    assert!(diff_servers(&[], &servers, None, None, Some("1")).is_empty());
+
+    let unknown = vec![
+      server("a:27017", SerializableServerType::Unknown),
+      server("b:27017", SerializableServerType::Unknown),
+    ];
+    assert!(diff_servers(&unknown, &servers, None, None, Some("1")).is_empty());
+  }
+
+  #[test]
+  fn same_primary_and_election_id_is_not_an_election() {
+    let servers = vec![server("a:27017", SerializableServerType::RsPrimary)];
+    assert!(diff_servers(&servers, &servers, Some("a:27017"), Some("1"), Some("1")).is_empty());
+  }
+
+  #[test]
+  fn new_election_id_on_the_same_primary_is_an_election() {
+    let servers = vec![server("a:27017", SerializableServerType::RsPrimary)];
+    let events = diff_servers(&servers, &servers, Some("a:27017"), Some("1"), Some("2"));
+    assert!(matches!(
+      events.as_slice(),
+      [TopologyEventKind::PrimaryElected { address, election_id: Some(election_id) }]
+        if address == "a:27017" && election_id == "2"
+    ));
+  }
+
+  #[test]
+  fn new_primary_is_a_failover() {
+    let previous = vec![
+      server("a:27017", SerializableServerType::RsPrimary),
+      server("b:27017", SerializableServerType::RsSecondary),
+    ];
+    let servers = vec![
+      server("a:27017", SerializableServerType::RsSecondary),
+      server("b:27017", SerializableServerType::RsPrimary),
+    ];
+    let events = diff_servers(&previous, &servers, Some("a:27017"), Some("1"), Some("2"));
+    assert!(matches!(
+      events.as_slice(),
+      [
+        TopologyEventKind::PrimarySteppedDown { .. },
+        TopologyEventKind::PrimaryElected { .. },
+        TopologyEventKind::Failover { previous_primary, new_primary },
+      ] if previous_primary == "a:27017" && new_primary == "b:27017"
+    ));
+  }
+
+  #[test]
+  fn primary_elected_after_a_description_without_primary() {
+    let previous = vec![
+      server("a:27017", SerializableServerType::Unknown),
+      server("b:27017", SerializableServerType::RsSecondary),
+    ];
+    let servers = vec![
+      server("a:27017", SerializableServerType::Unknown),
+      server("b:27017", SerializableServerType::RsPrimary),
+    ];
+    let events = diff_servers(&previous, &servers, Some("a:27017"), Some("1"), Some("2"));
+    assert!(matches!(
+      events.as_slice(),
+      [
+        TopologyEventKind::PrimaryElected { .. },
+        TopologyEventKind::Failover { .. },
+      ]
+    ));
+  }
+}
@@ -0,0 +1,240 @@
+use std::time::Instant;
+
+use mongodb::{
+  bson::{doc, Bson, Document},
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::PError, model::get_number, pipeline_lint::FIRST_STAGES};
+
+/// Number of output documents returned for every stage by default.
+const DEFAULT_PREVIEW_SIZE: i64 = 10;
+
+/// Stages writing their input to a collection, a preview never runs them.
+const WRITE_STAGES: [&str; 2] = ["$out", "$merge"];
+
+/// Documents counted for every stage by default, counting scans the whole input otherwise.
+const DEFAULT_COUNT_LIMIT: i64 = 10_000;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewOptions {
+  /// Number of input documents the documents are previewed from, all of them when `None`.
+  pub sample_size: Option<i64>,
+  /// Number of output documents returned for every stage, `DEFAULT_PREVIEW_SIZE` when `None`.
+  pub preview_size: Option<i64>,
+  #[serde(default)]
+  pub with_count: bool,
+  /// Documents counted for every stage, `DEFAULT_COUNT_LIMIT` when `None`.
+  pub count_limit: Option<i64>,
+}
+
+impl PreviewOptions {
+  /// Rejects the sizes that the server would reject as a `$limit`.
+  pub fn validate(&self) -> Result<(), PError> {
+    let sizes = [
+      ("the sample size", self.sample_size),
+      ("the preview size", self.preview_size),
+      ("the count limit", self.count_limit),
+    ];
+    for (what, size) in sizes {
+      if matches!(size, Some(size) if size <= 0) {
+        return Err(PError::InvalidArgument(format!(
+          "{} must be positive",
+          what
+        )));
+      }
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StagePreview {
+  pub index: usize,
+  /// The stage name, e.g. `$match`.
+  pub operator: String,
+  /// First output documents of the pipeline ending with this stage.
+  pub documents: Vec<Document>,
+  /// Number of documents output by the stage, `None` when it was not counted, skipped or failed.
+  pub count: Option<u64>,
+  /// The stage outputs more documents than the count limit, `count` is the limit.
+  pub count_capped: bool,
+  /// Milliseconds spent fetching `documents` from the pipeline up to this stage.
+  pub duration: u64,
+  /// Milliseconds spent counting the output of the stage.
+  pub count_duration: Option<u64>,
+  /// `$out` and `$merge` are skipped, the documents are the ones they would have written.
+  pub skipped: bool,
+  pub error: Option<PError>,
+}
+
+fn stage_operator(stage: &Document) -> String {
+  stage.keys().next().cloned().unwrap_or_default()
+}
+
+fn run_pipeline(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  stages: Vec<Document>,
+) -> Result<Vec<Document>, PError> {
+  let result = client
+    .database(database_name)
+    .collection::<Document>(collection_name)
+    .aggregate(stages, None)
+    .and_then(|cursor| cursor.collect::<Result<Vec<_>, _>>())?;
+  Ok(result)
+}
+
+fn has_text_search(query: &Document) -> bool {
+  query.iter().any(|(key, value)| match key.as_str() {
+    "$text" => true,
+    "$and" | "$or" | "$nor" => value
+      .as_array()
+      .map(|clauses| {
+        clauses
+          .iter()
+          .filter_map(Bson::as_document)
+          .any(has_text_search)
+      })
+      .unwrap_or(false),
+    _ => false,
+  })
+}
+
+/// Where the documents read from the collection can be limited, after the first stage when it
+/// has to stay first.
+fn input_limit_position(stages: &[Document]) -> usize {
+  let first = match stages.first() {
+    Some(first) => first,
+    None => return 0,
+  };
+  let must_be_first = FIRST_STAGES.contains(&stage_operator(first).as_str())
+    || matches!(first.get_document("$match"), Ok(query) if has_text_search(query));
+  if must_be_first {
+    1
+  } else {
+    0
+  }
+}
+
+/// Returns the first `preview_size` documents output by `stages`, run on the first
+/// `sample_size` input documents when it is set.
+fn sample_prefix(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  stages: &[Document],
+  sample_size: Option<i64>,
+  preview_size: i64,
+) -> Result<Vec<Document>, PError> {
+  let mut sample_stages = stages.to_vec();
+  if let Some(sample_size) = sample_size {
+    sample_stages.insert(input_limit_position(stages), doc! { "$limit": sample_size });
+  }
+  sample_stages.push(doc! { "$limit": preview_size });
+  run_pipeline(client, database_name, collection_name, sample_stages)
+}
+
+/// Counts the documents output by `stages` up to `count_limit`, and whether there are more.
+fn count_prefix(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  stages: &[Document],
+  count_limit: i64,
+) -> Result<(u64, bool), PError> {
+  let mut count_stages = stages.to_vec();
+  count_stages.push(doc! { "$limit": count_limit.saturating_add(1) });
+  count_stages.push(doc! { "$count": "count" });
+  let count = run_pipeline(client, database_name, collection_name, count_stages)?
+    .first()
+    .and_then(|count| get_number(count, "count"))
+    .unwrap_or(0.0) as u64;
+  if count > count_limit as u64 {
+    Ok((count_limit as u64, true))
+  } else {
+    Ok((count, false))
+  }
+}
+
+/// Runs every prefix of the pipeline, so that the output of each stage can be inspected. The
+/// documents are previewed from the first `sample_size` input documents, while the output of
+/// each stage is counted over the whole collection up to `count_limit` when `with_count` is set.
+/// The stages after a failing one are not run.
+pub fn preview_pipeline(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  stages: Vec<Document>,
+  options: &PreviewOptions,
+) -> Vec<StagePreview> {
+  let preview_size = options.preview_size.unwrap_or(DEFAULT_PREVIEW_SIZE);
+  let count_limit =
+    Some(options.count_limit.unwrap_or(DEFAULT_COUNT_LIMIT)).filter(|_| options.with_count);
+  let mut result = Vec::with_capacity(stages.len());
+  // The stages run so far, without the skipped ones.
+  let mut prefix = Vec::with_capacity(stages.len());
+  let mut previous_documents = Vec::new();
+  for (index, stage) in stages.into_iter().enumerate() {
+    let operator = stage_operator(&stage);
+    let mut preview = StagePreview {
+      index,
+      operator,
+      documents: Vec::new(),
+      count: None,
+      count_capped: false,
+      duration: 0,
+      count_duration: None,
+      skipped: false,
+      error: None,
+    };
+    if WRITE_STAGES.contains(&preview.operator.as_str()) {
+      preview.documents = previous_documents.clone();
+      preview.skipped = true;
+      result.push(preview);
+      continue;
+    }
+    prefix.push(stage);
+    let start = Instant::now();
+    let documents = sample_prefix(
+      client,
+      database_name,
+      collection_name,
+      &prefix,
+      options.sample_size,
+      preview_size,
+    );
+    preview.duration = start.elapsed().as_millis() as u64;
+    let documents = match documents {
+      Ok(documents) => documents,
+      Err(e) => {
+        preview.error = Some(e);
+        result.push(preview);
+        break;
+      }
+    };
+    if let Some(count_limit) = count_limit {
+      let start = Instant::now();
+      let count = count_prefix(client, database_name, collection_name, &prefix, count_limit);
+      preview.count_duration = Some(start.elapsed().as_millis() as u64);
+      match count {
+        Ok((count, count_capped)) => {
+          preview.count = Some(count);
+          preview.count_capped = count_capped;
+        }
+        Err(e) => {
+          preview.error = Some(e);
+          result.push(preview);
+          break;
+        }
+      }
+    }
+    previous_documents = documents.clone();
+    preview.documents = documents;
+    result.push(preview);
+  }
+  result
+}
@@ -0,0 +1,1105 @@
+use mongodb::{
+  bson::{Bson, Document},
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+const STAGES: [&str; 39] = [
+  "$addFields",
+  "$bucket",
+  "$bucketAuto",
+  "$changeStream",
+  "$collStats",
+  "$count",
+  "$currentOp",
+  "$densify",
+  "$documents",
+  "$facet",
+  "$fill",
+  "$geoNear",
+  "$graphLookup",
+  "$group",
+  "$indexStats",
+  "$limit",
+  "$listLocalSessions",
+  "$listSessions",
+  "$lookup",
+  "$match",
+  "$merge",
+  "$out",
+  "$planCacheStats",
+  "$project",
+  "$redact",
+  "$replaceRoot",
+  "$replaceWith",
+  "$sample",
+  "$search",
+  "$searchMeta",
+  "$set",
+  "$setWindowFields",
+  "$shardedDataDistribution",
+  "$skip",
+  "$sort",
+  "$sortByCount",
+  "$unionWith",
+  "$unset",
+  "$unwind",
+];
+
+/// Stages that only work at the beginning of the pipeline.
+pub(crate) const FIRST_STAGES: [&str; 11] = [
+  "$changeStream",
+  "$collStats",
+  "$currentOp",
+  "$documents",
+  "$geoNear",
+  "$indexStats",
+  "$listLocalSessions",
+  "$listSessions",
+  "$planCacheStats",
+  "$search",
+  "$searchMeta",
+];
+
+/// Stages that only work at the end of the pipeline.
+const LAST_STAGES: [&str; 2] = ["$out", "$merge"];
+
+const ACCUMULATORS: [&str; 21] = [
+  "$accumulator",
+  "$addToSet",
+  "$avg",
+  "$bottom",
+  "$bottomN",
+  "$count",
+  "$first",
+  "$firstN",
+  "$last",
+  "$lastN",
+  "$max",
+  "$maxN",
+  "$mergeObjects",
+  "$min",
+  "$minN",
+  "$push",
+  "$stdDevPop",
+  "$stdDevSamp",
+  "$sum",
+  "$top",
+  "$topN",
+];
+
+/// Operators taking the whole query, the other ones apply to a field.
+const QUERY_OPERATORS: [&str; 9] = [
+  "$and",
+  "$comment",
+  "$expr",
+  "$jsonSchema",
+  "$nor",
+  "$or",
+  "$sampleRate",
+  "$text",
+  "$where",
+];
+
+const FIELD_OPERATORS: [&str; 27] = [
+  "$all",
+  "$bitsAllClear",
+  "$bitsAllSet",
+  "$bitsAnyClear",
+  "$bitsAnySet",
+  "$elemMatch",
+  "$eq",
+  "$exists",
+  "$geoIntersects",
+  "$geoWithin",
+  "$gt",
+  "$gte",
+  "$in",
+  "$lt",
+  "$lte",
+  "$maxDistance",
+  "$minDistance",
+  "$mod",
+  "$ne",
+  "$near",
+  "$nearSphere",
+  "$nin",
+  "$not",
+  "$options",
+  "$regex",
+  "$size",
+  "$type",
+];
+
+/// Expression operators whose arguments are numbers, a string argument is a forgotten `$`.
+const ARITHMETIC_OPERATORS: [&str; 16] = [
+  "$abs",
+  "$add",
+  "$ceil",
+  "$divide",
+  "$exp",
+  "$floor",
+  "$ln",
+  "$log",
+  "$log10",
+  "$mod",
+  "$multiply",
+  "$pow",
+  "$round",
+  "$sqrt",
+  "$subtract",
+  "$trunc",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IssueSeverity {
+  /// The server would reject the pipeline.
+  Error,
+  /// The pipeline runs but most likely not as intended, or slower than it could.
+  Warning,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PipelineIssue {
+  pub stage: usize,
+  /// JSON pointer into the array of stages, e.g. `/2/$group/total/$sum`.
+  pub pointer: String,
+  pub severity: IssueSeverity,
+  pub message: String,
+}
+
+/// Appends `key` to a JSON pointer, escaping it as in RFC 6901.
+fn pointer_push(pointer: &str, key: &str) -> String {
+  format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"))
+}
+
+fn type_name(value: &Bson) -> &'static str {
+  match value {
+    Bson::Document(_) => "a document",
+    Bson::Array(_) => "an array",
+    Bson::String(_) => "a string",
+    Bson::Boolean(_) => "a boolean",
+    Bson::Null => "null",
+    Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_) | Bson::Decimal128(_) => "a number",
+    _ => "an unexpected value",
+  }
+}
+
+fn as_integer(value: &Bson) -> Option<i64> {
+  match value {
+    Bson::Int32(v) => Some(i64::from(*v)),
+    Bson::Int64(v) => Some(*v),
+    Bson::Double(v) if v.fract() == 0.0 => Some(*v as i64),
+    _ => None,
+  }
+}
+
+fn is_positive_integer(value: &Bson) -> bool {
+  matches!(as_integer(value), Some(v) if v > 0)
+}
+
+/// Whether a `$project` value keeps the field as it is, i.e. `1` or `true`.
+fn is_inclusion(value: &Bson) -> bool {
+  value == &Bson::Boolean(true) || matches!(as_integer(value), Some(v) if v != 0)
+}
+
+/// Whether `value` is a string that looks like a field name, e.g. `price` or `item.price`.
+fn looks_like_field_name(value: &str) -> bool {
+  !value.is_empty()
+    && !value.starts_with('$')
+    && value
+      .chars()
+      .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+}
+
+/// Whether two dotted paths refer to the same field, or one contains the other.
+fn paths_overlap(a: &str, b: &str) -> bool {
+  a == b || a.starts_with(&format!("{}.", b)) || b.starts_with(&format!("{}.", a))
+}
+
+struct Linter {
+  issues: Vec<PipelineIssue>,
+  stage: usize,
+}
+
+impl Linter {
+  fn add(&mut self, pointer: &str, severity: IssueSeverity, message: String) {
+    self.issues.push(PipelineIssue {
+      stage: self.stage,
+      pointer: pointer.to_string(),
+      severity,
+      message,
+    });
+  }
+
+  fn error(&mut self, pointer: &str, message: String) {
+    self.add(pointer, IssueSeverity::Error, message);
+  }
+
+  fn warning(&mut self, pointer: &str, message: String) {
+    self.add(pointer, IssueSeverity::Warning, message);
+  }
+
+  /// Returns the value as a document, or reports that `what` must be one.
+  fn expect_document<'a>(
+    &mut self,
+    pointer: &str,
+    what: &str,
+    value: &'a Bson,
+  ) -> Option<&'a Document> {
+    let result = value.as_document();
+    if result.is_none() {
+      self.error(
+        pointer,
+        format!("{} must be a document, not {}", what, type_name(value)),
+      );
+    }
+    result
+  }
+
+  fn expect_keys(&mut self, pointer: &str, what: &str, document: &Document, keys: &[&str]) {
+    for key in keys {
+      if !document.contains_key(key) {
+        self.error(pointer, format!("{} requires `{}`", what, key));
+      }
+    }
+  }
+
+  fn expect_field_path(&mut self, pointer: &str, what: &str, value: &Bson) {
+    match value {
+      Bson::String(path) if path.starts_with('$') => {}
+      Bson::String(path) => self.error(
+        pointer,
+        format!(
+          "{} must be a field path, use `${}` instead of `{}`",
+          what, path, path
+        ),
+      ),
+      _ => self.error(
+        pointer,
+        format!(
+          "{} must be a field path string, not {}",
+          what,
+          type_name(value)
+        ),
+      ),
+    }
+  }
+
+  /// `idx` is the index of the stage in its own pipeline, which is nested or not.
+  fn check_stage(&mut self, pointer: &str, operator: &str, value: &Bson, idx: usize) {
+    match operator {
+      "$match" => {
+        if let Some(query) = self.expect_document(pointer, "$match", value) {
+          self.check_query(pointer, query, idx);
+        }
+      }
+      "$project" | "$addFields" | "$set" => {
+        if let Some(fields) = self.expect_document(pointer, operator, value) {
+          if fields.is_empty() {
+            self.error(pointer, format!("{} requires at least one field", operator));
+          }
+          for (key, value) in fields {
+            self.check_projected_value(&pointer_push(pointer, key), operator, key, value);
+          }
+        }
+      }
+      "$unset" => match value {
+        Bson::String(_) => {}
+        Bson::Array(fields) => {
+          for (idx, field) in fields.iter().enumerate() {
+            if field.as_str().is_none() {
+              self.error(
+                &pointer_push(pointer, &idx.to_string()),
+                format!("$unset fields must be strings, not {}", type_name(field)),
+              );
+            }
+          }
+        }
+        _ => self.error(
+          pointer,
+          format!(
+            "$unset must be a string or an array, not {}",
+            type_name(value)
+          ),
+        ),
+      },
+      "$group" => {
+        if let Some(group) = self.expect_document(pointer, "$group", value) {
+          self.check_group(pointer, group);
+        }
+      }
+      "$sort" => {
+        if let Some(sort) = self.expect_document(pointer, "$sort", value) {
+          if sort.is_empty() {
+            self.error(pointer, "$sort requires at least one field".to_string());
+          }
+          for (key, direction) in sort {
+            let valid = match direction {
+              Bson::Document(meta) => meta.contains_key("$meta"),
+              direction => matches!(as_integer(direction), Some(1) | Some(-1)),
+            };
+            if !valid {
+              self.error(
+                &pointer_push(pointer, key),
+                format!("$sort direction of `{}` must be 1, -1 or {{ $meta }}", key),
+              );
+            }
+          }
+        }
+      }
+      "$limit" if !is_positive_integer(value) => self.error(
+        pointer,
+        format!("$limit must be a positive integer, not {}", value),
+      ),
+      "$skip" if !matches!(as_integer(value), Some(v) if v >= 0) => self.error(
+        pointer,
+        format!("$skip must be a non-negative integer, not {}", value),
+      ),
+      "$count" => match value {
+        Bson::String(name) if name.is_empty() || name.starts_with('$') || name.contains('.') => {
+          self.error(
+            pointer,
+            "$count must be a field name without `$` or `.`".to_string(),
+          )
+        }
+        Bson::String(_) => {}
+        _ => self.error(
+          pointer,
+          format!("$count must be a string, not {}", type_name(value)),
+        ),
+      },
+      "$unwind" => match value {
+        Bson::Document(unwind) => {
+          self.expect_keys(pointer, "$unwind", unwind, &["path"]);
+          if let Some(path) = unwind.get("path") {
+            self.expect_field_path(&pointer_push(pointer, "path"), "$unwind path", path);
+          }
+        }
+        value => self.expect_field_path(pointer, "$unwind", value),
+      },
+      "$lookup" => {
+        if let Some(lookup) = self.expect_document(pointer, "$lookup", value) {
+          self.check_lookup(pointer, lookup);
+        }
+      }
+      "$graphLookup" => {
+        if let Some(lookup) = self.expect_document(pointer, "$graphLookup", value) {
+          self.expect_keys(
+            pointer,
+            "$graphLookup",
+            lookup,
+            &[
+              "from",
+              "startWith",
+              "connectFromField",
+              "connectToField",
+              "as",
+            ],
+          );
+        }
+      }
+      "$replaceRoot" => {
+        if let Some(replace) = self.expect_document(pointer, "$replaceRoot", value) {
+          self.expect_keys(pointer, "$replaceRoot", replace, &["newRoot"]);
+          if let Some(new_root) = replace.get("newRoot") {
+            self.check_new_root(&pointer_push(pointer, "newRoot"), new_root);
+          }
+        }
+      }
+      "$replaceWith" => self.check_new_root(pointer, value),
+      "$sample" => {
+        if let Some(sample) = self.expect_document(pointer, "$sample", value) {
+          match sample.get("size") {
+            Some(size) if is_positive_integer(size) => {}
+            _ => self.error(
+              &pointer_push(pointer, "size"),
+              "$sample size must be a positive integer".to_string(),
+            ),
+          }
+        }
+      }
+      "$facet" => {
+        if let Some(facet) = self.expect_document(pointer, "$facet", value) {
+          for (name, stages) in facet {
+            let facet_pointer = pointer_push(pointer, name);
+            match stages.as_array() {
+              Some(stages) => self.check_stages(&facet_pointer, stages),
+              None => self.error(
+                &facet_pointer,
+                format!("$facet `{}` must be an array of stages", name),
+              ),
+            }
+          }
+        }
+      }
+      "$bucket" => {
+        if let Some(bucket) = self.expect_document(pointer, "$bucket", value) {
+          self.expect_keys(pointer, "$bucket", bucket, &["groupBy", "boundaries"]);
+          if let Some(group_by) = bucket.get("groupBy") {
+            self.check_expression(&pointer_push(pointer, "groupBy"), group_by, true);
+          }
+          if let Some(boundaries) = bucket.get("boundaries") {
+            if boundaries.as_array().map(Vec::len).unwrap_or(0) < 2 {
+              self.error(
+                &pointer_push(pointer, "boundaries"),
+                "$bucket boundaries must be an array of at least two values".to_string(),
+              );
+            }
+          }
+        }
+      }
+      "$bucketAuto" => {
+        if let Some(bucket) = self.expect_document(pointer, "$bucketAuto", value) {
+          self.expect_keys(pointer, "$bucketAuto", bucket, &["groupBy", "buckets"]);
+          if let Some(group_by) = bucket.get("groupBy") {
+            self.check_expression(&pointer_push(pointer, "groupBy"), group_by, true);
+          }
+          if let Some(buckets) = bucket.get("buckets") {
+            if !is_positive_integer(buckets) {
+              self.error(
+                &pointer_push(pointer, "buckets"),
+                "$bucketAuto buckets must be a positive integer".to_string(),
+              );
+            }
+          }
+        }
+      }
+      "$sortByCount" => self.check_expression(pointer, value, true),
+      "$unionWith" => match value {
+        Bson::String(_) => {}
+        Bson::Document(union) => {
+          self.expect_keys(pointer, "$unionWith", union, &["coll"]);
+          if let Some(stages) = union.get("pipeline") {
+            match stages.as_array() {
+              Some(stages) => self.check_stages(&pointer_push(pointer, "pipeline"), stages),
+              None => self.error(
+                &pointer_push(pointer, "pipeline"),
+                "$unionWith pipeline must be an array of stages".to_string(),
+              ),
+            }
+          }
+        }
+        _ => self.error(
+          pointer,
+          format!(
+            "$unionWith must be a collection name or a document, not {}",
+            type_name(value)
+          ),
+        ),
+      },
+      "$out" if !matches!(value, Bson::String(_) | Bson::Document(_)) => self.error(
+        pointer,
+        format!(
+          "$out must be a collection name or a document, not {}",
+          type_name(value)
+        ),
+      ),
+      "$merge" => match value {
+        Bson::String(_) => {}
+        Bson::Document(merge) => self.expect_keys(pointer, "$merge", merge, &["into"]),
+        _ => self.error(
+          pointer,
+          format!(
+            "$merge must be a collection name or a document, not {}",
+            type_name(value)
+          ),
+        ),
+      },
+      _ => {}
+    }
+  }
+
+  fn check_query(&mut self, pointer: &str, query: &Document, idx: usize) {
+    for (key, value) in query {
+      let key_pointer = pointer_push(pointer, key);
+      if !key.starts_with('$') {
+        if let Bson::Document(condition) = value {
+          self.check_field_condition(&key_pointer, condition);
+        }
+        continue;
+      }
+      match key.as_str() {
+        "$and" | "$or" | "$nor" => match value.as_array() {
+          Some(clauses) if !clauses.is_empty() => {
+            for (clause_idx, clause) in clauses.iter().enumerate() {
+              let clause_pointer = pointer_push(&key_pointer, &clause_idx.to_string());
+              if let Some(clause) = self.expect_document(&clause_pointer, key, clause) {
+                self.check_query(&clause_pointer, clause, idx);
+              }
+            }
+          }
+          _ => self.error(
+            &key_pointer,
+            format!("{} must be a non-empty array of queries", key),
+          ),
+        },
+        "$expr" => self.check_expression(&key_pointer, value, false),
+        "$text" if idx != 0 => self.error(
+          &key_pointer,
+          "$text is only allowed in the first stage of the pipeline".to_string(),
+        ),
+        key if QUERY_OPERATORS.contains(&key) => {}
+        key if FIELD_OPERATORS.contains(&key) => self.error(
+          &key_pointer,
+          format!(
+            "{} must be applied to a field, e.g. {{ field: {{ {}: ... }} }}",
+            key, key
+          ),
+        ),
+        key => self.error(&key_pointer, format!("unknown query operator {}", key)),
+      }
+    }
+  }
+
+  /// Checks `{ $gt: 1, $lt: 5 }` in `{ field: { $gt: 1, $lt: 5 } }`.
+  fn check_field_condition(&mut self, pointer: &str, condition: &Document) {
+    // `{ field: { a: 1 } }` is an equality match on an embedded document.
+    if !condition.keys().any(|key| key.starts_with('$')) {
+      return;
+    }
+    for (key, value) in condition {
+      let key_pointer = pointer_push(pointer, key);
+      match key.as_str() {
+        "$in" | "$nin" | "$all" if value.as_array().is_none() => {
+          self.error(&key_pointer, format!("{} must be an array", key))
+        }
+        "$not" => {
+          if let Bson::Document(condition) = value {
+            self.check_field_condition(&key_pointer, condition);
+          }
+        }
+        key if FIELD_OPERATORS.contains(&key) => {}
+        key if key.starts_with('$') => {
+          self.error(&key_pointer, format!("unknown query operator {}", key))
+        }
+        key => self.error(
+          &key_pointer,
+          format!("`{}` cannot be mixed with query operators", key),
+        ),
+      }
+    }
+  }
+
+  fn check_projected_value(&mut self, pointer: &str, operator: &str, key: &str, value: &Bson) {
+    match value {
+      Bson::String(s) if operator == "$project" && looks_like_field_name(s) => self.warning(
+        pointer,
+        format!(
+          "`{}` is set to the string \"{}\", use `${}` to reference the field",
+          key, s, s
+        ),
+      ),
+      value => self.check_expression(pointer, value, false),
+    }
+  }
+
+  fn check_group(&mut self, pointer: &str, group: &Document) {
+    if !group.contains_key("_id") {
+      self.error(pointer, "$group requires `_id`".to_string());
+    }
+    for (key, value) in group {
+      let key_pointer = pointer_push(pointer, key);
+      if key == "_id" {
+        if let Bson::String(s) = value {
+          if looks_like_field_name(s) {
+            self.warning(
+              &key_pointer,
+              format!(
+                "$group _id is the constant \"{}\", use `${}` to group by the field",
+                s, s
+              ),
+            );
+          }
+        } else {
+          self.check_expression(&key_pointer, value, false);
+        }
+        continue;
+      }
+      let accumulator = value
+        .as_document()
+        .filter(|accumulator| accumulator.len() == 1)
+        .and_then(|accumulator| accumulator.iter().next());
+      match accumulator {
+        Some((name, argument)) if ACCUMULATORS.contains(&name.as_str()) => {
+          let argument_pointer = pointer_push(&key_pointer, name);
+          match argument {
+            Bson::String(s) if looks_like_field_name(s) => self.warning(
+              &argument_pointer,
+              format!(
+                "{} of the string \"{}\", use `${}` to reference the field",
+                name, s, s
+              ),
+            ),
+            argument => self.check_expression(&argument_pointer, argument, false),
+          }
+        }
+        Some((name, _)) => self.error(&key_pointer, format!("{} is not an accumulator", name)),
+        None => self.error(
+          &key_pointer,
+          format!(
+            "$group field `{}` must be an accumulator, e.g. {{ $sum: 1 }}",
+            key
+          ),
+        ),
+      }
+    }
+  }
+
+  fn check_lookup(&mut self, pointer: &str, lookup: &Document) {
+    self.expect_keys(pointer, "$lookup", lookup, &["from", "as"]);
+    let has_local = lookup.contains_key("localField") || lookup.contains_key("foreignField");
+    if has_local {
+      self.expect_keys(pointer, "$lookup", lookup, &["localField", "foreignField"]);
+    } else if !lookup.contains_key("pipeline") {
+      self.error(
+        pointer,
+        "$lookup requires `localField` and `foreignField`, or `pipeline`".to_string(),
+      );
+    }
+    for key in ["localField", "foreignField", "as"] {
+      match lookup.get(key) {
+        Some(Bson::String(field)) if field.starts_with('$') => self.error(
+          &pointer_push(pointer, key),
+          format!("$lookup {} is a field name, write `{}`", key, &field[1..]),
+        ),
+        Some(Bson::String(_)) | None => {}
+        Some(value) => self.error(
+          &pointer_push(pointer, key),
+          format!("$lookup {} must be a string, not {}", key, type_name(value)),
+        ),
+      }
+    }
+    if let Some(stages) = lookup.get("pipeline") {
+      match stages.as_array() {
+        Some(stages) => self.check_stages(&pointer_push(pointer, "pipeline"), stages),
+        None => self.error(
+          &pointer_push(pointer, "pipeline"),
+          "$lookup pipeline must be an array of stages".to_string(),
+        ),
+      }
+    }
+  }
+
+  fn check_new_root(&mut self, pointer: &str, value: &Bson) {
+    match value {
+      Bson::String(s) if !s.starts_with('$') => self.error(
+        pointer,
+        format!(
+          "the new root must be a document, use `${}` to reference the field",
+          s
+        ),
+      ),
+      value => self.check_expression(pointer, value, false),
+    }
+  }
+
+  /// Looks for forgotten `$` in an aggregation expression. With `field_expected`, a string
+  /// without `$` is most likely a field name.
+  fn check_expression(&mut self, pointer: &str, value: &Bson, field_expected: bool) {
+    match value {
+      Bson::String(s) if field_expected && looks_like_field_name(s) => self.warning(
+        pointer,
+        format!(
+          "\"{}\" is a constant, use `${}` to reference the field",
+          s, s
+        ),
+      ),
+      Bson::Document(expression) => {
+        let operator = expression
+          .iter()
+          .next()
+          .filter(|(key, _)| expression.len() == 1 && key.starts_with('$'));
+        if let Some((operator, arguments)) = operator {
+          if operator == "$literal" {
+            return;
+          }
+          let argument_pointer = pointer_push(pointer, operator);
+          let field_expected = ARITHMETIC_OPERATORS.contains(&operator.as_str());
+          match arguments {
+            Bson::Array(arguments) => {
+              for (idx, argument) in arguments.iter().enumerate() {
+                self.check_expression(
+                  &pointer_push(&argument_pointer, &idx.to_string()),
+                  argument,
+                  field_expected,
+                );
+              }
+            }
+            argument => self.check_expression(&argument_pointer, argument, field_expected),
+          }
+        } else {
+          for (key, value) in expression {
+            self.check_expression(&pointer_push(pointer, key), value, false);
+          }
+        }
+      }
+      Bson::Array(values) => {
+        for (idx, value) in values.iter().enumerate() {
+          self.check_expression(&pointer_push(pointer, &idx.to_string()), value, false);
+        }
+      }
+      _ => {}
+    }
+  }
+
+  /// Checks the stages of a nested pipeline, their issues get the index of the parent stage.
+  fn check_stages(&mut self, pointer: &str, stages: &[Bson]) {
+    let count = stages.len();
+    for (idx, stage) in stages.iter().enumerate() {
+      let stage_pointer = pointer_push(pointer, &idx.to_string());
+      let stage = match self.expect_document(&stage_pointer, "a stage", stage) {
+        Some(stage) => stage,
+        None => continue,
+      };
+      self.check_stage_document(&stage_pointer, stage, idx, count, true);
+    }
+  }
+
+  fn check_stage_document(
+    &mut self,
+    pointer: &str,
+    stage: &Document,
+    idx: usize,
+    count: usize,
+    nested: bool,
+  ) {
+    let (operator, value) = match stage.iter().next() {
+      Some(entry) if stage.len() == 1 => entry,
+      _ => {
+        self.error(
+          pointer,
+          format!(
+            "a stage must have exactly one operator, found {} keys",
+            stage.len()
+          ),
+        );
+        return;
+      }
+    };
+    let operator_pointer = pointer_push(pointer, operator);
+    if !STAGES.contains(&operator.as_str()) {
+      self.error(&operator_pointer, format!("unknown stage {}", operator));
+      return;
+    }
+    if FIRST_STAGES.contains(&operator.as_str()) && idx != 0 {
+      self.error(
+        &operator_pointer,
+        format!("{} must be the first stage of the pipeline", operator),
+      );
+    }
+    if LAST_STAGES.contains(&operator.as_str()) {
+      if nested {
+        self.error(
+          &operator_pointer,
+          format!("{} cannot be used in a nested pipeline", operator),
+        );
+      } else if idx + 1 != count {
+        self.error(
+          &operator_pointer,
+          format!("{} must be the last stage of the pipeline", operator),
+        );
+      }
+    }
+    self.check_stage(&operator_pointer, operator, value, idx);
+  }
+
+  /// Reports the `$match` stages that filter on fields the previous stage does not change.
+  fn check_match_placement(&mut self, stages: &[Document]) {
+    for idx in 1..stages.len() {
+      let query = match stages[idx].get_document("$match") {
+        Ok(query) => query,
+        Err(_) => continue,
+      };
+      let fields = match query_fields(query) {
+        Some(fields) if !fields.is_empty() => fields,
+        _ => continue,
+      };
+      let previous = &stages[idx - 1];
+      let (operator, value) = match previous.iter().next() {
+        Some(entry) => entry,
+        None => continue,
+      };
+      let message = match operator.as_str() {
+        "$group" => group_pushdown(value, &fields).map(|rewritten| {
+          format!(
+            "this $match only filters on the group key, filtering on {} before the $group \
+             avoids grouping documents that are discarded",
+            rewritten.join(", ")
+          )
+        }),
+        "$project" | "$addFields" | "$set" | "$unwind" | "$lookup"
+          if fields
+            .iter()
+            .all(|field| passes_unchanged(operator, value, field)) =>
+        {
+          Some(format!(
+            "this $match does not depend on the previous {}, moving it before lets it use an \
+             index and process fewer documents",
+            operator
+          ))
+        }
+        _ => None,
+      };
+      if let Some(message) = message {
+        self.stage = idx;
+        self.warning(&format!("/{}/$match", idx), message);
+      }
+    }
+  }
+}
+
+/// Top level field paths filtered by a query, `None` when it contains `$expr` or `$where`.
+fn query_fields(query: &Document) -> Option<Vec<String>> {
+  let mut result = Vec::new();
+  for (key, value) in query {
+    match key.as_str() {
+      "$and" | "$or" | "$nor" => {
+        for clause in value.as_array()? {
+          result.extend(query_fields(clause.as_document()?)?);
+        }
+      }
+      "$comment" => {}
+      key if key.starts_with('$') => return None,
+      key => result.push(key.to_string()),
+    }
+  }
+  Some(result)
+}
+
+/// Whether the field at `path` goes through the stage unchanged, so that filtering on it can
+/// happen before the stage.
+fn passes_unchanged(operator: &str, value: &Bson, path: &str) -> bool {
+  let document = value.as_document();
+  let changed = match operator {
+    "$project" => {
+      let projection = match document {
+        Some(projection) => projection,
+        None => return false,
+      };
+      let is_inclusion_projection = projection
+        .iter()
+        .any(|(key, value)| key != "_id" && is_inclusion(value));
+      if is_inclusion_projection {
+        // Only the fields included as a whole are kept unchanged, `_id` unless excluded.
+        return if path == "_id" || path.starts_with("_id.") {
+          !matches!(projection.get("_id"), Some(id) if !is_inclusion(id))
+        } else {
+          projection.iter().any(|(key, value)| {
+            is_inclusion(value) && (path == key || path.starts_with(&format!("{}.", key)))
+          })
+        };
+      }
+      // An exclusion removes fields, filtering on them afterwards matches nothing.
+      projection.keys().cloned().collect()
+    }
+    "$addFields" | "$set" => match document {
+      Some(fields) => fields.keys().cloned().collect(),
+      None => return false,
+    },
+    "$unwind" => {
+      let unwind_path = match value {
+        Bson::String(unwind_path) => unwind_path.as_str(),
+        Bson::Document(unwind) => unwind.get_str("path").unwrap_or_default(),
+        _ => return false,
+      };
+      let mut changed = vec![unwind_path.trim_start_matches('$').to_string()];
+      if let Some(index_field) = document.and_then(|u| u.get_str("includeArrayIndex").ok()) {
+        changed.push(index_field.to_string());
+      }
+      changed
+    }
+    "$lookup" => match document.and_then(|lookup| lookup.get_str("as").ok()) {
+      Some(as_field) => vec![as_field.to_string()],
+      None => return false,
+    },
+    _ => return false,
+  };
+  !changed.iter().any(|field| paths_overlap(path, field))
+}
+
+/// Rewrites the group key fields of a `$match` following `$group` into the grouped fields, when
+/// the group key is a field or a document of fields.
+fn group_pushdown(group: &Bson, fields: &[String]) -> Option<Vec<String>> {
+  let id = group.as_document()?.get("_id")?;
+  fields
+    .iter()
+    .map(|field| {
+      let source = if field == "_id" {
+        id.as_str()?
+      } else {
+        let key = field.strip_prefix("_id.")?;
+        id.as_document()?.get_str(key).ok()?
+      };
+      // `$$ROOT` and the other variables are not fields.
+      source
+        .strip_prefix('$')
+        .filter(|s| !s.starts_with('$'))
+        .map(|s| format!("`{}`", s))
+    })
+    .collect()
+}
+
+/// Checks the stages without connecting to the server.
+pub fn lint_pipeline(stages: &[Document]) -> Vec<PipelineIssue> {
+  let mut linter = Linter {
+    issues: Vec::new(),
+    stage: 0,
+  };
+  for (idx, stage) in stages.iter().enumerate() {
+    linter.stage = idx;
+    linter.check_stage_document(&format!("/{}", idx), stage, idx, stages.len(), false);
+  }
+  linter.check_match_placement(stages);
+  linter.issues.sort_by_key(|issue| issue.stage);
+  linter.issues
+}
+
+/// Reports the `$lookup` stages whose `foreignField` is not the first key of an index of the
+/// joined collection, every input document then scans it.
+pub fn lint_lookup_indexes(
+  client: &Client,
+  database_name: &str,
+  stages: &[Document],
+) -> Vec<PipelineIssue> {
+  let mut result = Vec::new();
+  for (idx, stage) in stages.iter().enumerate() {
+    let lookup = match stage.get_document("$lookup") {
+      Ok(lookup) => lookup,
+      Err(_) => continue,
+    };
+    let (from, foreign_field) = match (lookup.get_str("from"), lookup.get_str("foreignField")) {
+      (Ok(from), Ok(foreign_field)) => (from, foreign_field),
+      _ => continue,
+    };
+    if foreign_field == "_id" {
+      continue;
+    }
+    let indexes = client
+      .database(database_name)
+      .collection::<Document>(from)
+      .list_indexes(None)
+      .and_then(|cursor| cursor.collect::<Result<Vec<_>, _>>());
+    // A missing collection has no index, the lookup simply finds nothing.
+    let indexes = match indexes {
+      Ok(indexes) => indexes,
+      Err(_) => continue,
+    };
+    let indexed = indexes
+      .iter()
+      .any(|index| index.keys.keys().next().map(String::as_str) == Some(foreign_field));
+    if !indexed {
+      result.push(PipelineIssue {
+        stage: idx,
+        pointer: format!("/{}/$lookup/foreignField", idx),
+        severity: IssueSeverity::Warning,
+        message: format!(
+          "no index of `{}` starts with `{}`, the $lookup scans the collection for every document",
+          from, foreign_field
+        ),
+      });
+    }
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use mongodb::bson::doc;
+
+  use super::*;
+
+  fn issues(stages: &[Document]) -> Vec<(usize, String, IssueSeverity)> {
+    lint_pipeline(stages)
+      .into_iter()
+      .map(|issue| (issue.stage, issue.pointer, issue.severity))
+      .collect()
+  }
+
+  #[test]
+  fn unknown_stage() {
+    assert_eq!(
+      issues(&[doc! { "$limit": 1 }, doc! { "$foo": {} }]),
+      vec![(1, "/1/$foo".to_string(), IssueSeverity::Error)]
+    );
+  }
+
+  #[test]
+  fn out_must_be_last() {
+    assert_eq!(
+      issues(&[doc! { "$out": "target" }, doc! { "$limit": 1 }]),
+      vec![(0, "/0/$out".to_string(), IssueSeverity::Error)]
+    );
+    assert!(issues(&[doc! { "$limit": 1 }, doc! { "$out": "target" }]).is_empty());
+  }
+
+  #[test]
+  fn text_must_be_first() {
+    let text = doc! { "$match": { "$text": { "$search": "coffee" } } };
+    assert!(issues(&[text.clone(), doc! { "$limit": 1 }]).is_empty());
+    assert_eq!(
+      issues(&[doc! { "$limit": 1 }, text]),
+      vec![(1, "/1/$match/$text".to_string(), IssueSeverity::Error)]
+    );
+  }
+
+  #[test]
+  fn text_inside_of_or() {
+    let text = doc! {
+      "$match": { "$or": [{ "a": 1 }, { "$text": { "$search": "coffee" } }] },
+    };
+    assert!(issues(&[text.clone(), doc! { "$limit": 1 }]).is_empty());
+    let text = doc! {
+      "$match": { "$or": [{ "$text": { "$search": "coffee" } }, { "a": 1 }] },
+    };
+    assert_eq!(
+      issues(&[doc! { "$limit": 1 }, text]),
+      vec![(1, "/1/$match/$or/0/$text".to_string(), IssueSeverity::Error)]
+    );
+  }
+
+  #[test]
+  fn text_in_nested_pipelines_uses_their_own_index() {
+    let text = doc! { "$match": { "$text": { "$search": "coffee" } } };
+    let lookup = doc! {
+      "$lookup": { "from": "other", "as": "joined", "pipeline": [text.clone()] },
+    };
+    assert!(issues(&[doc! { "$limit": 1 }, lookup]).is_empty());
+    let facet = doc! {
+      "$facet": { "first": [text.clone()], "second": [{ "$limit": 1 }, text] },
+    };
+    assert_eq!(
+      issues(&[doc! { "$limit": 1 }, facet]),
+      vec![(
+        1,
+        "/1/$facet/second/1/$match/$text".to_string(),
+        IssueSeverity::Error
+      )]
+    );
+  }
+
+  #[test]
+  fn missing_dollar_in_field_path() {
+    assert_eq!(
+      issues(&[doc! { "$group": { "_id": "category", "total": { "$sum": 1 } } }]),
+      vec![(0, "/0/$group/_id".to_string(), IssueSeverity::Warning)]
+    );
+  }
+
+  #[test]
+  fn match_after_an_unrelated_stage_can_move() {
+    assert_eq!(
+      issues(&[
+        doc! { "$addFields": { "total": { "$add": ["$a", "$b"] } } },
+        doc! { "$match": { "status": "active" } },
+      ]),
+      vec![(1, "/1/$match".to_string(), IssueSeverity::Warning)]
+    );
+    assert!(issues(&[
+      doc! { "$addFields": { "status": "active" } },
+      doc! { "$match": { "status": "active" } },
+    ])
+    .is_empty());
+  }
+}
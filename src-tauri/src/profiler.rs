@@ -0,0 +1,143 @@
+use mongodb::{
+  bson::{doc, DateTime, Document},
+  options::FindOptions,
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::PError,
+  model::get_number,
+  query_shape::{QueryShapeAggregator, QueryShapeStatistics, ShapedOperation},
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProfilingStatus {
+  /// 0 is off, 1 profiles the operations slower than `slowms` and 2 profiles everything.
+  pub level: i32,
+  pub slowms: i64,
+  pub sample_rate: f64,
+}
+
+impl From<&Document> for ProfilingStatus {
+  fn from(document: &Document) -> Self {
+    ProfilingStatus {
+      level: get_number(document, "was").unwrap_or(0.0) as i32,
+      slowms: get_number(document, "slowms").unwrap_or(0.0) as i64,
+      sample_rate: get_number(document, "sampleRate").unwrap_or(1.0),
+    }
+  }
+}
+
+pub fn get_profiling_status(
+  client: &Client,
+  database_name: &str,
+) -> Result<ProfilingStatus, PError> {
+  let result = client
+    .database(database_name)
+    .run_command(doc! { "profile": -1 }, None)?;
+  Ok(ProfilingStatus::from(&result))
+}
+
+/// Changes the profiling level and returns the status from before the change.
+pub fn set_profiling_status(
+  client: &Client,
+  database_name: &str,
+  level: i32,
+  slowms: Option<i64>,
+) -> Result<ProfilingStatus, PError> {
+  let mut command = doc! { "profile": level };
+  if let Some(slowms) = slowms {
+    command.insert("slowms", slowms);
+  }
+  let result = client.database(database_name).run_command(command, None)?;
+  Ok(ProfilingStatus::from(&result))
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct ProfileFilter {
+  pub namespace: Option<String>,
+  /// One of `command`, `query`, `insert`, `update`, `remove` or `getmore`.
+  pub op: Option<String>,
+  pub min_millis: Option<i64>,
+  /// Milliseconds since epoch.
+  pub since: Option<i64>,
+  pub until: Option<i64>,
+}
+
+impl ProfileFilter {
+  fn to_filter(&self) -> Document {
+    let mut result = Document::new();
+    if let Some(namespace) = &self.namespace {
+      result.insert("ns", namespace);
+    }
+    if let Some(op) = &self.op {
+      result.insert("op", op);
+    }
+    if let Some(min_millis) = self.min_millis {
+      result.insert("millis", doc! { "$gte": min_millis });
+    }
+    let mut ts = Document::new();
+    if let Some(since) = self.since {
+      ts.insert("$gte", DateTime::from_millis(since));
+    }
+    if let Some(until) = self.until {
+      ts.insert("$lte", DateTime::from_millis(until));
+    }
+    if !ts.is_empty() {
+      result.insert("ts", ts);
+    }
+    result
+  }
+}
+
+pub fn find_profile_entries(
+  client: &Client,
+  database_name: &str,
+  filter: &ProfileFilter,
+  limit: i64,
+) -> Result<Vec<Document>, PError> {
+  let find_options = FindOptions::builder()
+    .sort(doc! { "ts": -1 })
+    .limit(limit)
+    .build();
+  let result = client
+    .database(database_name)
+    .collection::<Document>("system.profile")
+    .find(filter.to_filter(), find_options)
+    .and_then(|cursor| cursor.collect::<Result<Vec<_>, _>>())?;
+  Ok(result)
+}
+
+/// Groups the profiled operations by query shape.
+pub fn analyze_profile_entries(
+  client: &Client,
+  database_name: &str,
+  filter: &ProfileFilter,
+) -> Result<Vec<QueryShapeStatistics>, PError> {
+  let cursor = client
+    .database(database_name)
+    .collection::<Document>("system.profile")
+    .find(filter.to_filter(), None)?;
+  let mut aggregator = QueryShapeAggregator::default();
+  for entry in cursor {
+    let entry = entry?;
+    let command = entry.get_document("command").cloned().unwrap_or_default();
+    // Commands are named after their first field, the other operations after their `op`.
+    let op = entry.get_str("op").unwrap_or_default();
+    let command_name = match command.keys().next() {
+      Some(name) if op == "command" => name.clone(),
+      _ => op.to_string(),
+    };
+    aggregator.add(ShapedOperation {
+      command_name: &command_name,
+      namespace: entry.get_str("ns").unwrap_or_default(),
+      command: &command,
+      millis: get_number(&entry, "millis").unwrap_or(0.0),
+      docs_examined: get_number(&entry, "docsExamined").map(|v| v as u64),
+      n_returned: get_number(&entry, "nreturned").map(|v| v as u64),
+      plan_summary: entry.get_str("planSummary").ok().map(|v| v.to_string()),
+    });
+  }
+  Ok(aggregator.into_statistics())
+}
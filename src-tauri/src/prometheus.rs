@@ -0,0 +1,194 @@
+use std::{
+  collections::BTreeMap,
+  fmt::Write,
+  sync::Arc,
+  thread::{self, JoinHandle},
+  time::Duration,
+};
+
+use tiny_http::{Header, Response, Server};
+
+use crate::{
+  error::PError,
+  mongodb_events::{DATABASE_HEARTBEAT, DATABASE_TOPOLOGY, SERVER_METRIC},
+};
+
+/// Upper bounds of the command duration histogram buckets, in seconds.
+const COMMAND_DURATION_BUCKETS: [f64; 10] =
+  [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0];
+
+/// The socket of a stopped exporter is closed by a thread of `tiny_http` shortly after, so binding
+/// the same port again is retried for a while.
+const BIND_ATTEMPTS: u32 = 20;
+const BIND_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Serves the collected metrics in the Prometheus text format on `127.0.0.1:<port>/metrics`.
+pub struct PrometheusExporter {
+  server: Arc<Server>,
+  listener: Option<JoinHandle<()>>,
+  port: u16,
+}
+
+fn bind(port: u16) -> Result<Server, PError> {
+  let mut attempt = 1;
+  loop {
+    match Server::http(("127.0.0.1", port)) {
+      Ok(server) => return Ok(server),
+      Err(_) if attempt < BIND_ATTEMPTS => {
+        attempt += 1;
+        thread::sleep(BIND_RETRY_DELAY);
+      }
+      Err(e) => return Err(PError::PrometheusExporterError(format!("{:#?}", e))),
+    }
+  }
+}
+
+impl PrometheusExporter {
+  pub fn start(port: u16) -> Result<PrometheusExporter, PError> {
+    let server = Arc::new(bind(port)?);
+    let listener = server.clone();
+    let listener = thread::spawn(move || {
+      for request in listener.incoming_requests() {
+        // Scrapers may add a query string, e.g. `/metrics?name[]=...`.
+        let response = if request.url().split('?').next() == Some("/metrics") {
+          Response::from_string(render_metrics()).with_header(
+            Header::from_bytes(&b"Content-Type"[..], &b"text/plain; version=0.0.4"[..]).unwrap(),
+          )
+        } else {
+          Response::from_string("Not Found").with_status_code(404)
+        };
+        if let Err(e) = request.respond(response) {
+          eprintln!("Failed to respond to a metrics request error:{:?}", e);
+        }
+      }
+    });
+    Ok(PrometheusExporter {
+      server,
+      listener: Some(listener),
+      port,
+    })
+  }
+
+  pub fn get_port(&self) -> u16 {
+    self.port
+  }
+}
+
+impl Drop for PrometheusExporter {
+  /// Waits for the listener thread, so that the server and its socket are released on return.
+  fn drop(&mut self) {
+    self.server.unblock();
+    if let Some(listener) = self.listener.take() {
+      if listener.join().is_err() {
+        eprintln!("The metrics listener thread panicked");
+      }
+    }
+  }
+}
+
+fn escape_label(value: &str) -> String {
+  value
+    .replace('\\', "\\\\")
+    .replace('"', "\\\"")
+    .replace('\n', "\\n")
+}
+
+pub fn render_metrics() -> String {
+  let mut result = String::new();
+
+  let histograms = {
+    let handle = SERVER_METRIC.lock().unwrap();
+    let mut histograms: BTreeMap<(String, String), (Vec<u64>, f64, u64)> = BTreeMap::new();
+    for (name, namespace, time_taken) in handle.get_finished_commands() {
+      let seconds = time_taken as f64 / 1e9;
+      let (buckets, sum, count) = histograms
+        .entry((name.to_string(), namespace.to_string()))
+        .or_insert_with(|| (vec![0; COMMAND_DURATION_BUCKETS.len()], 0.0, 0));
+      for (bucket, bound) in buckets.iter_mut().zip(COMMAND_DURATION_BUCKETS.iter()) {
+        if seconds <= *bound {
+          *bucket += 1;
+        }
+      }
+      *sum += seconds;
+      *count += 1;
+    }
+    histograms
+  };
+  let _ = writeln!(
+    result,
+    "# HELP pinky_pie_command_duration_seconds Duration of the commands sent by the application."
+  );
+  let _ = writeln!(
+    result,
+    "# TYPE pinky_pie_command_duration_seconds histogram"
+  );
+  for ((name, namespace), (buckets, sum, count)) in histograms {
+    let labels = format!(
+      "command=\"{}\",namespace=\"{}\"",
+      escape_label(&name),
+      escape_label(&namespace)
+    );
+    for (bucket, bound) in buckets.iter().zip(COMMAND_DURATION_BUCKETS.iter()) {
+      let _ = writeln!(
+        result,
+        "pinky_pie_command_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+        labels, bound, bucket
+      );
+    }
+    let _ = writeln!(
+      result,
+      "pinky_pie_command_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+      labels, count
+    );
+    let _ = writeln!(
+      result,
+      "pinky_pie_command_duration_seconds_sum{{{}}} {}",
+      labels, sum
+    );
+    let _ = writeln!(
+      result,
+      "pinky_pie_command_duration_seconds_count{{{}}} {}",
+      labels, count
+    );
+  }
+
+  let (succeeded_total, failed_total) = {
+    let handle = DATABASE_HEARTBEAT.lock().unwrap();
+    (handle.succeeded_total, handle.failed_total)
+  };
+  let _ = writeln!(
+    result,
+    "# HELP pinky_pie_heartbeats_total Server heartbeats observed by the driver."
+  );
+  let _ = writeln!(result, "# TYPE pinky_pie_heartbeats_total counter");
+  let _ = writeln!(
+    result,
+    "pinky_pie_heartbeats_total{{result=\"succeeded\"}} {}",
+    succeeded_total
+  );
+  let _ = writeln!(
+    result,
+    "pinky_pie_heartbeats_total{{result=\"failed\"}} {}",
+    failed_total
+  );
+
+  let server_count_per_type = DATABASE_TOPOLOGY
+    .lock()
+    .unwrap()
+    .get_server_count_per_type();
+  let _ = writeln!(
+    result,
+    "# HELP pinky_pie_servers Servers in the current topology by server type."
+  );
+  let _ = writeln!(result, "# TYPE pinky_pie_servers gauge");
+  for (server_type, count) in server_count_per_type {
+    let _ = writeln!(
+      result,
+      "pinky_pie_servers{{type=\"{}\"}} {}",
+      escape_label(&server_type),
+      count
+    );
+  }
+
+  result
+}
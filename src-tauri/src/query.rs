@@ -0,0 +1,76 @@
+use mongodb::{
+  bson::Document,
+  options::{AggregateOptions, Collation, FindOptions},
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::error::PError;
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct FindQuery {
+  pub filter: Document,
+  pub projection: Option<Document>,
+  pub sort: Option<Document>,
+  pub collation: Option<Collation>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct AggregateQuery {
+  pub stages: Vec<Document>,
+  pub collation: Option<Collation>,
+}
+
+/// Returns the page `page` of the documents matching the query, `per_page` at a time.
+pub fn find_documents(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  query: FindQuery,
+  page: i64,
+  per_page: i64,
+) -> Result<Vec<Document>, PError> {
+  let find_options = FindOptions::builder()
+    .limit(per_page)
+    .skip((per_page * page) as u64)
+    .projection(query.projection)
+    .sort(query.sort)
+    .collation(query.collation)
+    .build();
+  let result = client
+    .database(database_name)
+    .collection(collection_name)
+    .find(query.filter, find_options)
+    .and_then(|cursor| cursor.collect::<Result<Vec<_>, _>>())?;
+  Ok(result)
+}
+
+pub fn count_documents(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  documents_filter: Document,
+) -> Result<u64, PError> {
+  let result = client
+    .database(database_name)
+    .collection::<Document>(collection_name)
+    .count_documents(documents_filter, None)?;
+  Ok(result)
+}
+
+pub fn aggregate_documents(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  query: AggregateQuery,
+) -> Result<Vec<Document>, PError> {
+  let aggregate_options = AggregateOptions::builder()
+    .collation(query.collation)
+    .build();
+  let result = client
+    .database(database_name)
+    .collection::<Document>(collection_name)
+    .aggregate(query.stages, aggregate_options)
+    .and_then(|cursor| cursor.collect::<Result<Vec<Document>, _>>())?;
+  Ok(result)
+}
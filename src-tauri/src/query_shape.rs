@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+
+use mongodb::bson::{Bson, Document};
+use serde::{Deserialize, Serialize};
+
+/// Parts of a command that decide how it is executed, the remaining fields are ignored.
+const QUERY_SHAPE_KEYS: [&str; 7] = [
+  "filter",
+  "query",
+  "q",
+  "sort",
+  "projection",
+  "pipeline",
+  "key",
+];
+
+/// Replaces every literal of `value` with `"?"` while keeping the field names and operators.
+pub fn normalize(value: &Bson) -> Bson {
+  match value {
+    Bson::Document(document) => Bson::Document(
+      document
+        .iter()
+        .map(|(k, v)| (k.clone(), normalize(v)))
+        .collect(),
+    ),
+    Bson::Array(array) if array.iter().all(|v| v.as_document().is_some()) => {
+      Bson::Array(array.iter().map(normalize).collect())
+    }
+    // Lists of literals such as the operand of `$in` only differ by their values.
+    Bson::Array(_) => Bson::Array(vec![Bson::String("?".to_string())]),
+    _ => Bson::String("?".to_string()),
+  }
+}
+
+/// Returns the shape of a find, count, update, delete, distinct or aggregate command.
+pub fn query_shape(command: &Document) -> Document {
+  QUERY_SHAPE_KEYS
+    .iter()
+    .filter_map(|key| command.get(*key).map(|v| (key.to_string(), normalize(v))))
+    .collect()
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueryShapeStatistics {
+  pub command_name: String,
+  pub namespace: String,
+  pub shape: Document,
+  pub count: u64,
+  pub total_millis: f64,
+  pub mean_millis: f64,
+  pub max_millis: f64,
+  /// Only known for the operations read from `system.profile`.
+  pub docs_examined: Option<u64>,
+  pub n_returned: Option<u64>,
+  pub docs_examined_per_returned: Option<f64>,
+  /// The plan summary of the latest operation with this shape.
+  pub plan_summary: Option<String>,
+}
+
+/// One finished operation to be grouped by its shape.
+pub struct ShapedOperation<'a> {
+  pub command_name: &'a str,
+  pub namespace: &'a str,
+  pub command: &'a Document,
+  pub millis: f64,
+  pub docs_examined: Option<u64>,
+  pub n_returned: Option<u64>,
+  pub plan_summary: Option<String>,
+}
+
+#[derive(Default)]
+pub struct QueryShapeAggregator {
+  shapes: BTreeMap<String, QueryShapeStatistics>,
+}
+
+impl QueryShapeAggregator {
+  pub fn add(&mut self, operation: ShapedOperation) {
+    let ShapedOperation {
+      command_name,
+      namespace,
+      command,
+      millis,
+      docs_examined,
+      n_returned,
+      plan_summary,
+    } = operation;
+    let shape = query_shape(command);
+    let key = format!("{}|{}|{}", command_name, namespace, shape);
+    let statistics = self
+      .shapes
+      .entry(key)
+      .or_insert_with(|| QueryShapeStatistics {
+        command_name: command_name.to_string(),
+        namespace: namespace.to_string(),
+        shape,
+        count: 0,
+        total_millis: 0.0,
+        mean_millis: 0.0,
+        max_millis: 0.0,
+        docs_examined: None,
+        n_returned: None,
+        docs_examined_per_returned: None,
+        plan_summary: None,
+      });
+    statistics.count += 1;
+    statistics.total_millis += millis;
+    statistics.max_millis = statistics.max_millis.max(millis);
+    if let Some(docs_examined) = docs_examined {
+      *statistics.docs_examined.get_or_insert(0) += docs_examined;
+    }
+    if let Some(n_returned) = n_returned {
+      *statistics.n_returned.get_or_insert(0) += n_returned;
+    }
+    if plan_summary.is_some() {
+      statistics.plan_summary = plan_summary;
+    }
+  }
+
+  /// Returns the statistics of every shape, the ones that took the most time in total first.
+  pub fn into_statistics(self) -> Vec<QueryShapeStatistics> {
+    let mut result = self
+      .shapes
+      .into_values()
+      .map(|mut statistics| {
+        statistics.mean_millis = statistics.total_millis / statistics.count as f64;
+        statistics.docs_examined_per_returned =
+          match (statistics.docs_examined, statistics.n_returned) {
+            (Some(docs_examined), Some(n_returned)) if n_returned > 0 => {
+              Some(docs_examined as f64 / n_returned as f64)
+            }
+            _ => None,
+          };
+        statistics
+      })
+      .collect::<Vec<_>>();
+    result.sort_by(|l, r| r.total_millis.total_cmp(&l.total_millis));
+    result
+  }
+}
@@ -0,0 +1,202 @@
+use std::sync::Arc;
+
+use mongodb::{
+  bson::{doc, Bson, Document},
+  options::SelectionCriteria,
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{error::PError, model::get_number};
+
+/// `replSetGetStatus` state of a primary.
+const PRIMARY_STATE: i32 = 1;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplicaSetConfigMember {
+  pub id: i32,
+  pub host: String,
+  pub priority: f64,
+  pub votes: i32,
+  pub hidden: bool,
+  pub arbiter_only: bool,
+  pub build_indexes: bool,
+  /// `secondaryDelaySecs`, or `slaveDelay` before MongoDB 5.0.
+  pub delay_secs: i64,
+  pub tags: Option<Document>,
+}
+
+impl From<&Document> for ReplicaSetConfigMember {
+  fn from(document: &Document) -> Self {
+    ReplicaSetConfigMember {
+      id: get_number(document, "_id").unwrap_or(0.0) as i32,
+      host: document.get_str("host").unwrap_or_default().to_string(),
+      priority: get_number(document, "priority").unwrap_or(1.0),
+      votes: get_number(document, "votes").unwrap_or(1.0) as i32,
+      hidden: document.get_bool("hidden").unwrap_or(false),
+      arbiter_only: document.get_bool("arbiterOnly").unwrap_or(false),
+      build_indexes: document.get_bool("buildIndexes").unwrap_or(true),
+      delay_secs: get_number(document, "secondaryDelaySecs")
+        .or_else(|| get_number(document, "slaveDelay"))
+        .unwrap_or(0.0) as i64,
+      tags: document.get_document("tags").ok().cloned(),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplicaSetConfig {
+  pub name: String,
+  pub version: i64,
+  pub term: Option<i64>,
+  pub protocol_version: Option<i64>,
+  pub members: Vec<ReplicaSetConfigMember>,
+  pub settings: Option<Document>,
+}
+
+impl From<&Document> for ReplicaSetConfig {
+  fn from(document: &Document) -> Self {
+    ReplicaSetConfig {
+      name: document.get_str("_id").unwrap_or_default().to_string(),
+      version: get_number(document, "version").unwrap_or(0.0) as i64,
+      term: get_number(document, "term").map(|v| v as i64),
+      protocol_version: get_number(document, "protocolVersion").map(|v| v as i64),
+      members: document
+        .get_array("members")
+        .map(|members| {
+          members
+            .iter()
+            .filter_map(Bson::as_document)
+            .map(ReplicaSetConfigMember::from)
+            .collect()
+        })
+        .unwrap_or_default(),
+      settings: document.get_document("settings").ok().cloned(),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplicaSetMember {
+  pub id: i32,
+  pub name: String,
+  pub state: i32,
+  pub state_str: String,
+  pub healthy: bool,
+  pub is_self: bool,
+  pub uptime_secs: Option<i64>,
+  /// Milliseconds since epoch of the last operation applied by the member.
+  pub optime_date: Option<i64>,
+  /// How far behind the primary the member is in milliseconds, unknown without a primary.
+  pub replication_lag: Option<i64>,
+  pub sync_source: Option<String>,
+  pub ping_ms: Option<i64>,
+  pub last_heartbeat_message: Option<String>,
+  /// What the member is configured with, missing if it is not in the configuration anymore.
+  pub config: Option<ReplicaSetConfigMember>,
+}
+
+impl From<&Document> for ReplicaSetMember {
+  fn from(document: &Document) -> Self {
+    let get_string = |key: &str| {
+      document
+        .get_str(key)
+        .ok()
+        .filter(|v| !v.is_empty())
+        .map(|v| v.to_string())
+    };
+    ReplicaSetMember {
+      id: get_number(document, "_id").unwrap_or(0.0) as i32,
+      name: get_string("name").unwrap_or_default(),
+      state: get_number(document, "state").unwrap_or(0.0) as i32,
+      state_str: get_string("stateStr").unwrap_or_default(),
+      healthy: get_number(document, "health").unwrap_or(0.0) == 1.0,
+      is_self: document.get_bool("self").unwrap_or(false),
+      uptime_secs: get_number(document, "uptime").map(|v| v as i64),
+      optime_date: document
+        .get_datetime("optimeDate")
+        .ok()
+        .map(|v| v.timestamp_millis()),
+      replication_lag: None,
+      sync_source: get_string("syncSourceHost").or_else(|| get_string("syncingTo")),
+      ping_ms: get_number(document, "pingMs").map(|v| v as i64),
+      last_heartbeat_message: get_string("lastHeartbeatMessage"),
+      config: None,
+    }
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplicaSetStatus {
+  pub name: String,
+  pub term: Option<i64>,
+  pub my_state: i32,
+  pub primary: Option<String>,
+  pub members: Vec<ReplicaSetMember>,
+}
+
+pub fn get_replica_set_config(client: &Client) -> Result<ReplicaSetConfig, PError> {
+  let result = client
+    .database("admin")
+    .run_command(doc! { "replSetGetConfig": 1 }, None)?;
+  let config = result.get_document("config").cloned().unwrap_or_default();
+  Ok(ReplicaSetConfig::from(&config))
+}
+
+pub fn get_replica_set_status(client: &Client) -> Result<ReplicaSetStatus, PError> {
+  let result = client
+    .database("admin")
+    .run_command(doc! { "replSetGetStatus": 1 }, None)?;
+  let config = get_replica_set_config(client)?;
+  let mut members = result
+    .get_array("members")
+    .map(|members| {
+      members
+        .iter()
+        .filter_map(Bson::as_document)
+        .map(ReplicaSetMember::from)
+        .collect::<Vec<_>>()
+    })
+    .unwrap_or_default();
+  let primary = members.iter().find(|m| m.state == PRIMARY_STATE).cloned();
+  for member in &mut members {
+    member.config = config.members.iter().find(|m| m.id == member.id).cloned();
+    member.replication_lag = match (&primary, member.optime_date) {
+      (Some(primary), Some(optime_date)) => primary
+        .optime_date
+        .map(|primary_optime_date| (primary_optime_date - optime_date).max(0)),
+      _ => None,
+    };
+  }
+  Ok(ReplicaSetStatus {
+    name: result.get_str("set").unwrap_or_default().to_string(),
+    term: get_number(&result, "term").map(|v| v as i64),
+    my_state: get_number(&result, "myState").unwrap_or(0.0) as i32,
+    primary: primary.map(|m| m.name),
+    members,
+  })
+}
+
+/// Asks the primary to step down, it cannot be re-elected for `step_down_secs`.
+pub fn step_down_primary(
+  client: &Client,
+  step_down_secs: i64,
+  secondary_catch_up_period_secs: Option<i64>,
+) -> Result<Document, PError> {
+  let mut command = doc! { "replSetStepDown": step_down_secs };
+  if let Some(secondary_catch_up_period_secs) = secondary_catch_up_period_secs {
+    command.insert("secondaryCatchUpPeriodSecs", secondary_catch_up_period_secs);
+  }
+  let result = client.database("admin").run_command(command, None)?;
+  Ok(result)
+}
+
+/// Prevents the member at `host` from seeking election for `freeze_secs`, 0 unfreezes it.
+pub fn freeze_member(client: &Client, host: String, freeze_secs: i64) -> Result<Document, PError> {
+  let selection_criteria =
+    SelectionCriteria::Predicate(Arc::new(move |server| server.address().to_string() == host));
+  let result = client
+    .database("admin")
+    .run_command(doc! { "replSetFreeze": freeze_secs }, selection_criteria)?;
+  Ok(result)
+}
@@ -0,0 +1,527 @@
+use std::{
+  collections::{BTreeSet, HashMap},
+  fs,
+  path::PathBuf,
+};
+
+use mongodb::{
+  bson::{Bson, Document},
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::PError,
+  history::HistoryQuery,
+  query::{aggregate_documents, find_documents, AggregateQuery, FindQuery},
+  store::current_time_millis,
+};
+
+const PLACEHOLDER_START: &str = "{{";
+const PLACEHOLDER_END: &str = "}}";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SavedQueryBody {
+  Find(Box<FindQuery>),
+  Aggregate(AggregateQuery),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct QueryParameter {
+  /// Referenced as `{{name}}` in the query.
+  pub name: String,
+  /// Used when no value is given at run time.
+  pub default: Option<Bson>,
+  pub description: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedQuery {
+  /// Unique in the library, queries are run by name.
+  pub name: String,
+  pub database_name: String,
+  pub collection_name: String,
+  #[serde(default)]
+  pub tags: Vec<String>,
+  #[serde(default)]
+  pub parameters: Vec<QueryParameter>,
+  pub query: SavedQueryBody,
+  /// Milliseconds since epoch, set by the store.
+  #[serde(default)]
+  pub created_at: u64,
+  #[serde(default)]
+  pub updated_at: u64,
+}
+
+/// Calls `f` with the trimmed name of every `{{placeholder}}` of `value`.
+fn for_each_placeholder(value: &str, f: &mut dyn FnMut(&str)) {
+  let mut rest = value;
+  while let Some(start) = rest.find(PLACEHOLDER_START) {
+    let after_start = &rest[start + PLACEHOLDER_START.len()..];
+    match after_start.find(PLACEHOLDER_END) {
+      Some(end) => {
+        f(after_start[..end].trim());
+        rest = &after_start[end + PLACEHOLDER_END.len()..];
+      }
+      None => break,
+    }
+  }
+}
+
+fn collect_placeholders(value: &Bson, names: &mut BTreeSet<String>) {
+  match value {
+    Bson::String(s) => for_each_placeholder(s, &mut |name| {
+      names.insert(name.to_string());
+    }),
+    Bson::Document(document) => {
+      for (key, value) in document {
+        for_each_placeholder(key, &mut |name| {
+          names.insert(name.to_string());
+        });
+        collect_placeholders(value, names);
+      }
+    }
+    Bson::Array(values) => {
+      for value in values {
+        collect_placeholders(value, names);
+      }
+    }
+    _ => {}
+  }
+}
+
+fn lookup<'a>(values: &'a HashMap<String, Bson>, name: &str) -> Result<&'a Bson, PError> {
+  values
+    .get(name)
+    .ok_or_else(|| PError::MissingQueryParameter(name.to_string()))
+}
+
+/// Replaces the placeholders of a string. A string made of a single placeholder becomes the
+/// value itself, so that numbers, dates or ObjectIds keep their type.
+fn fill_string(value: &str, values: &HashMap<String, Bson>) -> Result<Bson, PError> {
+  let trimmed = value.trim();
+  if trimmed.starts_with(PLACEHOLDER_START)
+    && trimmed.ends_with(PLACEHOLDER_END)
+    && trimmed.len() >= PLACEHOLDER_START.len() + PLACEHOLDER_END.len()
+  {
+    let name = &trimmed[PLACEHOLDER_START.len()..trimmed.len() - PLACEHOLDER_END.len()];
+    if !name.contains(PLACEHOLDER_START) && !name.contains(PLACEHOLDER_END) {
+      return lookup(values, name.trim()).cloned();
+    }
+  }
+  let mut result = String::with_capacity(value.len());
+  let mut rest = value;
+  while let Some(start) = rest.find(PLACEHOLDER_START) {
+    let after_start = &rest[start + PLACEHOLDER_START.len()..];
+    let end = match after_start.find(PLACEHOLDER_END) {
+      Some(end) => end,
+      None => break,
+    };
+    result.push_str(&rest[..start]);
+    match lookup(values, after_start[..end].trim())? {
+      Bson::String(s) => result.push_str(s),
+      other => result.push_str(&other.to_string()),
+    }
+    rest = &after_start[end + PLACEHOLDER_END.len()..];
+  }
+  result.push_str(rest);
+  Ok(Bson::String(result))
+}
+
+fn fill_value(value: &Bson, values: &HashMap<String, Bson>) -> Result<Bson, PError> {
+  match value {
+    Bson::String(s) => fill_string(s, values),
+    Bson::Document(document) => fill_document(document, values).map(Bson::Document),
+    Bson::Array(array) => array
+      .iter()
+      .map(|value| fill_value(value, values))
+      .collect::<Result<Vec<_>, _>>()
+      .map(Bson::Array),
+    value => Ok(value.clone()),
+  }
+}
+
+fn fill_document(document: &Document, values: &HashMap<String, Bson>) -> Result<Document, PError> {
+  let mut result = Document::new();
+  for (key, value) in document {
+    let key = match fill_string(key, values)? {
+      Bson::String(key) => key,
+      other => other.to_string(),
+    };
+    result.insert(key, fill_value(value, values)?);
+  }
+  Ok(result)
+}
+
+impl SavedQuery {
+  fn documents(&self) -> Vec<&Document> {
+    match &self.query {
+      SavedQueryBody::Find(find) => std::iter::once(&find.filter)
+        .chain(find.projection.as_ref())
+        .chain(find.sort.as_ref())
+        .collect(),
+      SavedQueryBody::Aggregate(aggregate) => aggregate.stages.iter().collect(),
+    }
+  }
+
+  /// Names of the placeholders used in the query.
+  pub fn placeholders(&self) -> BTreeSet<String> {
+    let mut result = BTreeSet::new();
+    for document in self.documents() {
+      collect_placeholders(&Bson::Document(document.clone()), &mut result);
+    }
+    result
+  }
+
+  /// Declares the placeholders that are used but missing from the parameters.
+  fn declare_placeholders(&mut self) {
+    for name in self.placeholders() {
+      if !self.parameters.iter().any(|p| p.name == name) {
+        self.parameters.push(QueryParameter {
+          name,
+          default: None,
+          description: None,
+        });
+      }
+    }
+  }
+
+  /// Returns the query with its placeholders replaced by `arguments`, or by the defaults.
+  pub fn resolve(&self, arguments: HashMap<String, Bson>) -> Result<SavedQueryBody, PError> {
+    let mut values = self
+      .parameters
+      .iter()
+      .filter_map(|p| p.default.clone().map(|default| (p.name.clone(), default)))
+      .collect::<HashMap<_, _>>();
+    values.extend(arguments);
+    let result = match &self.query {
+      SavedQueryBody::Find(find) => SavedQueryBody::Find(Box::new(FindQuery {
+        filter: fill_document(&find.filter, &values)?,
+        projection: find
+          .projection
+          .as_ref()
+          .map(|projection| fill_document(projection, &values))
+          .transpose()?,
+        sort: find
+          .sort
+          .as_ref()
+          .map(|sort| fill_document(sort, &values))
+          .transpose()?,
+        collation: find.collation.clone(),
+      })),
+      SavedQueryBody::Aggregate(aggregate) => SavedQueryBody::Aggregate(AggregateQuery {
+        stages: aggregate
+          .stages
+          .iter()
+          .map(|stage| fill_document(stage, &values))
+          .collect::<Result<Vec<_>, _>>()?,
+        collation: aggregate.collation.clone(),
+      }),
+    };
+    Ok(result)
+  }
+}
+
+impl SavedQueryBody {
+  /// The history entry of a run of the resolved query.
+  pub fn to_history_query(&self, page: i64, per_page: i64) -> HistoryQuery {
+    match self {
+      SavedQueryBody::Find(find) => HistoryQuery::Find {
+        query: find.clone(),
+        page,
+        per_page,
+      },
+      SavedQueryBody::Aggregate(aggregate) => HistoryQuery::Aggregate {
+        query: aggregate.clone(),
+      },
+    }
+  }
+}
+
+/// Runs the resolved query of a saved query against its namespace, `page` and `per_page` only
+/// apply to a find.
+pub fn run_saved_query(
+  client: &Client,
+  saved_query: &SavedQuery,
+  query: SavedQueryBody,
+  page: i64,
+  per_page: i64,
+) -> Result<Vec<Document>, PError> {
+  let database_name = &saved_query.database_name;
+  let collection_name = &saved_query.collection_name;
+  match query {
+    SavedQueryBody::Find(find) => find_documents(
+      client,
+      database_name,
+      collection_name,
+      *find,
+      page,
+      per_page,
+    ),
+    SavedQueryBody::Aggregate(aggregate) => {
+      aggregate_documents(client, database_name, collection_name, aggregate)
+    }
+  }
+}
+
+/// Keeps the whole library in a single JSON file, sorted by name.
+pub struct SavedQueryStore {
+  path: PathBuf,
+}
+
+impl SavedQueryStore {
+  pub fn default_path() -> Option<PathBuf> {
+    tauri::api::path::data_dir().map(|p| p.join("pinky-pie").join("saved_queries.json"))
+  }
+
+  pub fn open(path: PathBuf) -> Result<SavedQueryStore, PError> {
+    if let Some(directory) = path.parent() {
+      fs::create_dir_all(directory)?;
+    }
+    Ok(SavedQueryStore { path })
+  }
+
+  fn read(&self) -> Result<Vec<SavedQuery>, PError> {
+    if !self.path.exists() {
+      return Ok(Vec::new());
+    }
+    let content = fs::read(&self.path)?;
+    Ok(serde_json::from_slice(&content)?)
+  }
+
+  fn write(&self, queries: &mut Vec<SavedQuery>) -> Result<(), PError> {
+    queries.sort_by(|a, b| a.name.cmp(&b.name));
+    let content = serde_json::to_vec_pretty(queries)?;
+    // Replaces the library at once, so that a crash while writing does not lose it.
+    let temporary_path = self.path.with_extension("json.tmp");
+    fs::write(&temporary_path, content)?;
+    fs::rename(&temporary_path, &self.path)?;
+    Ok(())
+  }
+
+  /// Returns the queries with the tag, or all of them.
+  pub fn list(&self, tag: Option<&str>) -> Result<Vec<SavedQuery>, PError> {
+    let mut result = self.read()?;
+    if let Some(tag) = tag {
+      result.retain(|query| query.tags.iter().any(|t| t == tag));
+    }
+    Ok(result)
+  }
+
+  pub fn get(&self, name: &str) -> Result<SavedQuery, PError> {
+    self
+      .read()?
+      .into_iter()
+      .find(|query| query.name == name)
+      .ok_or_else(|| PError::SavedQueryNotFound(name.to_string()))
+  }
+
+  /// Creates the query, or replaces the one with the same name.
+  pub fn save(&self, mut saved_query: SavedQuery) -> Result<SavedQuery, PError> {
+    let mut queries = self.read()?;
+    let now = current_time_millis();
+    saved_query.declare_placeholders();
+    saved_query.created_at = queries
+      .iter()
+      .find(|query| query.name == saved_query.name)
+      .map(|query| query.created_at)
+      .unwrap_or(now);
+    saved_query.updated_at = now;
+    queries.retain(|query| query.name != saved_query.name);
+    queries.push(saved_query.clone());
+    self.write(&mut queries)?;
+    Ok(saved_query)
+  }
+
+  pub fn delete(&self, name: &str) -> Result<(), PError> {
+    let mut queries = self.read()?;
+    let count = queries.len();
+    queries.retain(|query| query.name != name);
+    if queries.len() == count {
+      return Err(PError::SavedQueryNotFound(name.to_string()));
+    }
+    self.write(&mut queries)
+  }
+
+  /// Returns the queries as a JSON array, all of them when `names` is empty.
+  pub fn export(&self, names: &[String]) -> Result<String, PError> {
+    let mut queries = self.read()?;
+    if !names.is_empty() {
+      queries.retain(|query| names.contains(&query.name));
+    }
+    Ok(serde_json::to_string_pretty(&queries)?)
+  }
+
+  /// Adds the queries of an exported JSON array and returns the names of the imported ones. The
+  /// queries whose name is already used are skipped unless `overwrite` is set.
+  pub fn import(&self, content: &str, overwrite: bool) -> Result<Vec<String>, PError> {
+    let imported = serde_json::from_str::<Vec<SavedQuery>>(content)?;
+    let mut queries = self.read()?;
+    let mut result = Vec::new();
+    let now = current_time_millis();
+    for mut saved_query in imported {
+      let exists = queries.iter().any(|query| query.name == saved_query.name);
+      if exists && !overwrite {
+        continue;
+      }
+      queries.retain(|query| query.name != saved_query.name);
+      saved_query.declare_placeholders();
+      if saved_query.created_at == 0 {
+        saved_query.created_at = now;
+        saved_query.updated_at = now;
+      }
+      result.push(saved_query.name.clone());
+      queries.push(saved_query);
+    }
+    self.write(&mut queries)?;
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use mongodb::bson::{doc, oid::ObjectId};
+
+  use super::*;
+
+  fn values(entries: Vec<(&str, Bson)>) -> HashMap<String, Bson> {
+    entries
+      .into_iter()
+      .map(|(name, value)| (name.to_string(), value))
+      .collect()
+  }
+
+  fn saved_query(name: &str, filter: Document, parameters: Vec<QueryParameter>) -> SavedQuery {
+    SavedQuery {
+      name: name.to_string(),
+      database_name: "db".to_string(),
+      collection_name: "coll".to_string(),
+      tags: Vec::new(),
+      parameters,
+      query: SavedQueryBody::Find(Box::new(FindQuery {
+        filter,
+        ..FindQuery::default()
+      })),
+      created_at: 0,
+      updated_at: 0,
+    }
+  }
+
+  fn filter(query: &SavedQueryBody) -> &Document {
+    match query {
+      SavedQueryBody::Find(find) => &find.filter,
+      SavedQueryBody::Aggregate(_) => panic!("expected a find"),
+    }
+  }
+
+  fn open_store(name: &str) -> SavedQueryStore {
+    let path = std::env::temp_dir().join(format!("pinky-pie-{}-{}.json", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    SavedQueryStore::open(path).unwrap()
+  }
+
+  #[test]
+  fn a_whole_string_placeholder_keeps_the_type() {
+    let id = ObjectId::new();
+    let values = values(vec![("id", Bson::ObjectId(id)), ("n", Bson::Int32(3))]);
+    assert_eq!(
+      fill_string(" {{ id }} ", &values).unwrap(),
+      Bson::ObjectId(id)
+    );
+    assert_eq!(
+      fill_string("n is {{n}}, not {{ n }}", &values).unwrap(),
+      Bson::String("n is 3, not 3".to_string())
+    );
+    assert_eq!(
+      fill_string("{{n}} and {{n}}", &values).unwrap(),
+      Bson::String("3 and 3".to_string())
+    );
+  }
+
+  #[test]
+  fn placeholders_in_keys() {
+    let values = values(vec![
+      ("field", Bson::String("status".to_string())),
+      ("value", Bson::Int64(1)),
+    ]);
+    assert_eq!(
+      fill_document(&doc! { "{{field}}": { "$gte": "{{value}}" } }, &values).unwrap(),
+      doc! { "status": { "$gte": 1_i64 } }
+    );
+  }
+
+  #[test]
+  fn missing_parameters_and_defaults() {
+    let query = saved_query(
+      "q",
+      doc! { "a": "{{a}}", "b": "{{b}}" },
+      vec![QueryParameter {
+        name: "a".to_string(),
+        default: Some(Bson::Int32(1)),
+        description: None,
+      }],
+    );
+    assert!(matches!(
+      query.resolve(HashMap::new()),
+      Err(PError::MissingQueryParameter(name)) if name == "b"
+    ));
+    let resolved = query.resolve(values(vec![("b", Bson::Int32(2))])).unwrap();
+    assert_eq!(filter(&resolved), &doc! { "a": 1, "b": 2 });
+    let resolved = query
+      .resolve(values(vec![("a", Bson::Int32(3)), ("b", Bson::Int32(2))]))
+      .unwrap();
+    assert_eq!(filter(&resolved), &doc! { "a": 3, "b": 2 });
+  }
+
+  #[test]
+  fn used_placeholders_are_declared() {
+    let mut query = saved_query(
+      "q",
+      doc! { "{{key}}": { "$in": ["{{a}}", "x{{b}}"] } },
+      vec![QueryParameter {
+        name: "a".to_string(),
+        default: Some(Bson::Int32(1)),
+        description: Some("kept".to_string()),
+      }],
+    );
+    query.declare_placeholders();
+    let names = query
+      .parameters
+      .iter()
+      .map(|p| p.name.as_str())
+      .collect::<Vec<_>>();
+    assert_eq!(names, vec!["a", "b", "key"]);
+    assert_eq!(query.parameters[0].description.as_deref(), Some("kept"));
+  }
+
+  #[test]
+  fn import_skips_or_overwrites_the_existing_queries() {
+    let store = open_store("saved-queries-import");
+    store
+      .save(saved_query("existing", doc! { "a": 1 }, Vec::new()))
+      .unwrap();
+    let content = serde_json::to_string(&vec![
+      saved_query("existing", doc! { "a": 2 }, Vec::new()),
+      saved_query("new", doc! { "b": "{{b}}" }, Vec::new()),
+    ])
+    .unwrap();
+
+    assert_eq!(store.import(&content, false).unwrap(), vec!["new"]);
+    assert_eq!(
+      filter(&store.get("existing").unwrap().query),
+      &doc! { "a": 1 }
+    );
+    assert_eq!(store.get("new").unwrap().parameters[0].name, "b");
+
+    assert_eq!(
+      store.import(&content, true).unwrap(),
+      vec!["existing", "new"]
+    );
+    assert_eq!(
+      filter(&store.get("existing").unwrap().query),
+      &doc! { "a": 2 }
+    );
+    assert_eq!(store.list(None).unwrap().len(), 2);
+  }
+}
@@ -0,0 +1,411 @@
+use std::{
+  cmp::{Ordering, Reverse},
+  collections::{BTreeMap, HashMap, HashSet},
+};
+
+use mongodb::{
+  bson::{doc, Bson, Document},
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::PError,
+  model::{as_number, BsonType},
+};
+
+pub const DEFAULT_SAMPLE_SIZE: i64 = 1000;
+/// Suffix of the path of the elements of an array, e.g. `tags.[]`.
+pub const ARRAY_ELEMENTS: &str = "[]";
+const TOP_VALUES: usize = 10;
+/// Distinct values remembered for each type of a field, the cardinality is a lower bound past it.
+const MAX_DISTINCT_VALUES: usize = 10_000;
+
+/// Orders two values of the same type, `None` for the types without a meaningful order.
+fn compare(l: &Bson, r: &Bson) -> Option<Ordering> {
+  match (l, r) {
+    (Bson::String(l), Bson::String(r)) => Some(l.cmp(r)),
+    (Bson::Boolean(l), Bson::Boolean(r)) => Some(l.cmp(r)),
+    (Bson::DateTime(l), Bson::DateTime(r)) => Some(l.cmp(r)),
+    (Bson::ObjectId(l), Bson::ObjectId(r)) => Some(l.bytes().cmp(&r.bytes())),
+    (Bson::Timestamp(l), Bson::Timestamp(r)) => {
+      Some((l.time, l.increment).cmp(&(r.time, r.increment)))
+    }
+    _ => as_number(l)?.partial_cmp(&as_number(r)?),
+  }
+}
+
+/// Whether the values of the type are worth counting, documents and arrays are analyzed field by
+/// field instead.
+fn is_scalar(value: &Bson) -> bool {
+  !matches!(
+    value,
+    Bson::Document(_) | Bson::Array(_) | Bson::Binary(_) | Bson::JavaScriptCodeWithScope(_)
+  )
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValueCount {
+  pub value: Bson,
+  pub count: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TypeStatistics {
+  pub bson_type: BsonType,
+  pub count: u64,
+  pub min: Option<Bson>,
+  pub max: Option<Bson>,
+  pub top_values: Vec<ValueCount>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StringLengthBucket {
+  pub min_length: usize,
+  pub max_length: usize,
+  pub count: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StringLengthStatistics {
+  pub min: usize,
+  pub max: usize,
+  pub mean: f64,
+  /// Buckets of lengths doubling in size: 0, 1, 2-3, 4-7, 8-15...
+  pub histogram: Vec<StringLengthBucket>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DateRange {
+  /// Milliseconds since epoch.
+  pub min: i64,
+  pub max: i64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FieldSchema {
+  /// Dotted path of the field, the elements of an array are under `<array>.[]`.
+  pub path: String,
+  /// The keys leading to the field, unlike `path` it tells `{ "a.b": 1 }` and
+  /// `{ "a": { "b": 1 } }` apart.
+  #[serde(default)]
+  pub segments: Vec<String>,
+  /// Number of documents containing the field.
+  pub count: u64,
+  /// Percentage of the sampled documents containing the field.
+  pub presence: f64,
+  /// Number of values, larger than `count` for the fields inside of arrays.
+  pub values: u64,
+  pub types: Vec<TypeStatistics>,
+  pub cardinality: u64,
+  /// The field has more distinct values than remembered, `cardinality` is a lower bound.
+  pub cardinality_capped: bool,
+  pub string_lengths: Option<StringLengthStatistics>,
+  pub date_range: Option<DateRange>,
+}
+
+impl FieldSchema {
+  /// The segments of the object containing the field, `None` at the root of the documents.
+  pub fn parent_segments(&self) -> Option<&[String]> {
+    match self.segments.as_slice() {
+      [] | [_] => None,
+      [parent @ .., _] => Some(parent),
+    }
+  }
+
+  /// The key of the field in its parent object, `[]` for the elements of an array.
+  pub fn key(&self) -> &str {
+    self.segments.last().map(String::as_str).unwrap_or_default()
+  }
+
+  /// The segments of the elements of the field, when it is an array.
+  pub fn items_segments(&self) -> Vec<String> {
+    let mut result = self.segments.clone();
+    result.push(ARRAY_ELEMENTS.to_string());
+    result
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectionSchema {
+  pub sample_size: i64,
+  /// Number of documents actually analyzed, lower than `sample_size` for small collections.
+  pub document_count: u64,
+  /// Sorted by segments, a field comes before its nested fields.
+  pub fields: Vec<FieldSchema>,
+}
+
+#[derive(Default)]
+struct TypeAccumulator {
+  count: u64,
+  min: Option<Bson>,
+  max: Option<Bson>,
+  distinct_values: HashMap<String, ValueCount>,
+  capped: bool,
+}
+
+impl TypeAccumulator {
+  fn add(&mut self, value: &Bson) {
+    self.count += 1;
+    if !is_scalar(value) {
+      return;
+    }
+    let replaces = |other: &Option<Bson>, ordering| match other {
+      Some(other) => compare(value, other) == Some(ordering),
+      None => true,
+    };
+    if replaces(&self.min, Ordering::Less) {
+      self.min = Some(value.clone());
+    }
+    if replaces(&self.max, Ordering::Greater) {
+      self.max = Some(value.clone());
+    }
+    let key = value.to_string();
+    let distinct_values = self.distinct_values.len();
+    match self.distinct_values.get_mut(&key) {
+      Some(value_count) => value_count.count += 1,
+      None if distinct_values < MAX_DISTINCT_VALUES => {
+        self.distinct_values.insert(
+          key,
+          ValueCount {
+            value: value.clone(),
+            count: 1,
+          },
+        );
+      }
+      None => self.capped = true,
+    }
+  }
+}
+
+#[derive(Default)]
+struct FieldAccumulator {
+  count: u64,
+  values: u64,
+  types: HashMap<BsonType, TypeAccumulator>,
+  string_length_sum: u64,
+  string_length_min: Option<usize>,
+  string_length_max: usize,
+  string_length_histogram: BTreeMap<u32, u64>,
+}
+
+impl FieldAccumulator {
+  fn add(&mut self, value: &Bson) {
+    self.values += 1;
+    self
+      .types
+      .entry(BsonType::from(value))
+      .or_default()
+      .add(value);
+    if let Bson::String(value) = value {
+      let length = value.chars().count();
+      self.string_length_sum += length as u64;
+      self.string_length_min = Some(self.string_length_min.map_or(length, |v| v.min(length)));
+      self.string_length_max = self.string_length_max.max(length);
+      // 0 for the empty strings, n for the lengths between 2^(n-1) and 2^n - 1.
+      let bucket = usize::BITS - length.leading_zeros();
+      *self.string_length_histogram.entry(bucket).or_default() += 1;
+    }
+  }
+
+  fn into_schema(self, segments: Vec<String>, document_count: u64) -> FieldSchema {
+    let string_count = self
+      .types
+      .get(&BsonType::String)
+      .map(|t| t.count)
+      .unwrap_or(0);
+    let string_lengths = self.string_length_min.map(|min| StringLengthStatistics {
+      min,
+      max: self.string_length_max,
+      mean: self.string_length_sum as f64 / string_count as f64,
+      histogram: self
+        .string_length_histogram
+        .iter()
+        .map(|(bucket, count)| StringLengthBucket {
+          min_length: if *bucket == 0 { 0 } else { 1 << (bucket - 1) },
+          max_length: (1 << bucket) - 1,
+          count: *count,
+        })
+        .collect(),
+    });
+    let date_range = self
+      .types
+      .get(&BsonType::DateTime)
+      .and_then(|t| match (&t.min, &t.max) {
+        (Some(Bson::DateTime(min)), Some(Bson::DateTime(max))) => Some(DateRange {
+          min: min.timestamp_millis(),
+          max: max.timestamp_millis(),
+        }),
+        _ => None,
+      });
+    let cardinality = self
+      .types
+      .values()
+      .map(|t| t.distinct_values.len() as u64)
+      .sum();
+    let cardinality_capped = self.types.values().any(|t| t.capped);
+    let mut types = self
+      .types
+      .into_iter()
+      .map(|(bson_type, t)| {
+        let mut top_values = t.distinct_values.into_iter().collect::<Vec<_>>();
+        // Ties are ordered by value, the map iterates in a different order on every run.
+        top_values
+          .sort_by(|(l_key, l), (r_key, r)| r.count.cmp(&l.count).then_with(|| l_key.cmp(r_key)));
+        top_values.truncate(TOP_VALUES);
+        let top_values = top_values.into_iter().map(|(_, v)| v).collect();
+        TypeStatistics {
+          bson_type,
+          count: t.count,
+          min: t.min,
+          max: t.max,
+          top_values,
+        }
+      })
+      .collect::<Vec<_>>();
+    types.sort_by_key(|t| (Reverse(t.count), t.bson_type.alias()));
+    FieldSchema {
+      path: segments.join("."),
+      segments,
+      count: self.count,
+      presence: if document_count > 0 {
+        self.count as f64 * 100.0 / document_count as f64
+      } else {
+        0.0
+      },
+      values: self.values,
+      types,
+      cardinality,
+      cardinality_capped,
+      string_lengths,
+      date_range,
+    }
+  }
+}
+
+/// Builds the schema of documents one at a time.
+#[derive(Default)]
+pub struct SchemaAnalyzer {
+  document_count: u64,
+  fields: BTreeMap<Vec<String>, FieldAccumulator>,
+}
+
+fn child_segments(segments: &[String], key: &str) -> Vec<String> {
+  let mut result = segments.to_vec();
+  result.push(key.to_string());
+  result
+}
+
+impl SchemaAnalyzer {
+  pub fn add(&mut self, document: &Document) {
+    self.document_count += 1;
+    let mut seen = HashSet::new();
+    for (key, value) in document {
+      self.add_value(vec![key.clone()], value, &mut seen);
+    }
+  }
+
+  fn add_value(&mut self, segments: Vec<String>, value: &Bson, seen: &mut HashSet<Vec<String>>) {
+    let field = self.fields.entry(segments.clone()).or_default();
+    if seen.insert(segments.clone()) {
+      field.count += 1;
+    }
+    field.add(value);
+    match value {
+      Bson::Document(document) => {
+        for (key, value) in document {
+          self.add_value(child_segments(&segments, key), value, seen);
+        }
+      }
+      Bson::Array(array) => {
+        for value in array {
+          self.add_value(child_segments(&segments, ARRAY_ELEMENTS), value, seen);
+        }
+      }
+      _ => {}
+    }
+  }
+
+  pub fn into_schema(self, sample_size: i64) -> CollectionSchema {
+    let document_count = self.document_count;
+    CollectionSchema {
+      sample_size,
+      document_count,
+      fields: self
+        .fields
+        .into_iter()
+        .map(|(segments, field)| field.into_schema(segments, document_count))
+        .collect(),
+    }
+  }
+}
+
+fn sample_stages(documents_filter: Document, sample_size: i64) -> Vec<Document> {
+  vec![
+    doc! { "$match": documents_filter },
+    doc! { "$sample": { "size": sample_size } },
+  ]
+}
+
+/// Returns a random sample of the documents matching `documents_filter`.
+pub fn sample_documents(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  documents_filter: Document,
+  sample_size: i64,
+) -> Result<Vec<Document>, PError> {
+  let result = client
+    .database(database_name)
+    .collection::<Document>(collection_name)
+    .aggregate(sample_stages(documents_filter, sample_size), None)
+    .and_then(|cursor| cursor.collect::<Result<Vec<_>, _>>())?;
+  Ok(result)
+}
+
+/// Analyzes a random sample of the documents matching `documents_filter`.
+pub fn analyze_documents(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  documents_filter: Document,
+  sample_size: i64,
+) -> Result<CollectionSchema, PError> {
+  let cursor = client
+    .database(database_name)
+    .collection::<Document>(collection_name)
+    .aggregate(sample_stages(documents_filter, sample_size), None)?;
+  let mut analyzer = SchemaAnalyzer::default();
+  for document in cursor {
+    analyzer.add(&document?);
+  }
+  Ok(analyzer.into_schema(sample_size))
+}
+
+#[cfg(test)]
+mod tests {
+  use mongodb::bson::doc;
+
+  use super::*;
+
+  #[test]
+  fn ties_are_ordered_by_value() {
+    let mut analyzer = SchemaAnalyzer::default();
+    for value in ["d", "b", "a", "c", "b"] {
+      analyzer.add(&doc! { "a": value });
+    }
+    for value in [3, 1, 2] {
+      analyzer.add(&doc! { "a": value });
+    }
+    let schema = analyzer.into_schema(8);
+    let types = &schema.fields[0].types;
+    assert_eq!(types[0].bson_type, BsonType::String);
+    let values = types[0]
+      .top_values
+      .iter()
+      .map(|v| v.value.as_str().unwrap())
+      .collect::<Vec<_>>();
+    assert_eq!(values, vec!["b", "a", "c", "d"]);
+    assert_eq!(types[1].bson_type, BsonType::Int32);
+  }
+}
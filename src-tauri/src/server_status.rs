@@ -0,0 +1,251 @@
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex},
+  thread,
+  time::Duration,
+};
+
+use mongodb::{
+  bson::{doc, Document},
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{model::get_number, store::current_time_millis};
+
+lazy_static! {
+  pub static ref SERVER_STATUS: Arc<Mutex<ServerStatusSeries>> =
+    Arc::new(Mutex::new(ServerStatusSeries::default()));
+}
+
+/// Number of samples kept in the series.
+const SERVER_STATUS_HISTORY_SIZE: usize = 600;
+
+pub const DEFAULT_SERVER_STATUS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The poller sends `serverStatus` to this database, these commands are left out of the command
+/// statistics.
+const SERVER_STATUS_DATABASE: &str = "admin";
+const SERVER_STATUS_COMMAND: &str = "serverStatus";
+
+/// Whether a command intercepted by the command handler was sent by the poller.
+pub fn is_server_status_poll(command_name: &str, database_name: &str) -> bool {
+  command_name == SERVER_STATUS_COMMAND && database_name == SERVER_STATUS_DATABASE
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct OpCounters {
+  pub insert: i64,
+  pub query: i64,
+  pub update: i64,
+  pub delete: i64,
+  pub getmore: i64,
+  pub command: i64,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Connections {
+  pub current: i64,
+  pub available: i64,
+  pub total_created: i64,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Network {
+  pub bytes_in: i64,
+  pub bytes_out: i64,
+  pub num_requests: i64,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct WiredTigerCache {
+  pub bytes_currently_in_cache: i64,
+  pub maximum_bytes_configured: i64,
+  pub tracked_dirty_bytes: i64,
+  pub pages_read_into_cache: i64,
+  pub pages_written_from_cache: i64,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Tickets {
+  pub read_out: i64,
+  pub read_available: i64,
+  pub write_out: i64,
+  pub write_available: i64,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct Asserts {
+  pub regular: i64,
+  pub warning: i64,
+  pub msg: i64,
+  pub user: i64,
+  pub rollovers: i64,
+}
+
+/// One `serverStatus` poll. Counters are the difference since the previous poll, which was
+/// `interval` milliseconds earlier, while gauges such as the current connections are as reported.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServerStatusSample {
+  pub timestamp: u64,
+  pub interval: u64,
+  pub opcounters: OpCounters,
+  pub connections: Connections,
+  pub network: Network,
+  /// Only reported by the WiredTiger storage engine.
+  pub wired_tiger_cache: Option<WiredTigerCache>,
+  pub tickets: Option<Tickets>,
+  pub asserts: Asserts,
+}
+
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
+pub struct ServerStatusSeries {
+  samples: VecDeque<ServerStatusSample>,
+  previous: Option<(u64, Document)>,
+  error: Option<String>,
+  /// Incremented by `clear`, so that a poll of the previous client is not added afterwards.
+  generation: u64,
+}
+
+impl ServerStatusSeries {
+  pub fn clear(&mut self) {
+    *self = ServerStatusSeries {
+      generation: self.generation + 1,
+      ..ServerStatusSeries::default()
+    };
+  }
+
+  pub fn generation(&self) -> u64 {
+    self.generation
+  }
+
+  pub fn add_error(&mut self, error: String) {
+    self.error = Some(error);
+  }
+
+  pub fn add_server_status(&mut self, timestamp: u64, status: Document) {
+    self.error = None;
+    let (previous_timestamp, previous) = match self.previous.replace((timestamp, status.clone())) {
+      Some(previous) => previous,
+      // The first poll only provides the baseline for the counters.
+      None => return,
+    };
+    let delta = |path: &str| {
+      let current = get_number(&status, path).unwrap_or(0.0);
+      let previous = get_number(&previous, path).unwrap_or(0.0);
+      // Counters restart from zero when the server restarts.
+      (current - previous).max(0.0) as i64
+    };
+    let gauge = |path: &str| get_number(&status, path).unwrap_or(0.0) as i64;
+
+    let wired_tiger_cache = if status.get_document("wiredTiger").is_ok() {
+      Some(WiredTigerCache {
+        bytes_currently_in_cache: gauge("wiredTiger.cache.bytes currently in the cache"),
+        maximum_bytes_configured: gauge("wiredTiger.cache.maximum bytes configured"),
+        tracked_dirty_bytes: gauge("wiredTiger.cache.tracked dirty bytes in the cache"),
+        pages_read_into_cache: delta("wiredTiger.cache.pages read into cache"),
+        pages_written_from_cache: delta("wiredTiger.cache.pages written from cache"),
+      })
+    } else {
+      None
+    };
+    let tickets = if status
+      .get_document("wiredTiger")
+      .and_then(|v| v.get_document("concurrentTransactions"))
+      .is_ok()
+    {
+      Some(Tickets {
+        read_out: gauge("wiredTiger.concurrentTransactions.read.out"),
+        read_available: gauge("wiredTiger.concurrentTransactions.read.available"),
+        write_out: gauge("wiredTiger.concurrentTransactions.write.out"),
+        write_available: gauge("wiredTiger.concurrentTransactions.write.available"),
+      })
+    } else {
+      None
+    };
+    let sample = ServerStatusSample {
+      timestamp,
+      // The wall clock may step backwards.
+      interval: timestamp.saturating_sub(previous_timestamp),
+      opcounters: OpCounters {
+        insert: delta("opcounters.insert"),
+        query: delta("opcounters.query"),
+        update: delta("opcounters.update"),
+        delete: delta("opcounters.delete"),
+        getmore: delta("opcounters.getmore"),
+        command: delta("opcounters.command"),
+      },
+      connections: Connections {
+        current: gauge("connections.current"),
+        available: gauge("connections.available"),
+        total_created: delta("connections.totalCreated"),
+      },
+      network: Network {
+        bytes_in: delta("network.bytesIn"),
+        bytes_out: delta("network.bytesOut"),
+        num_requests: delta("network.numRequests"),
+      },
+      wired_tiger_cache,
+      tickets,
+      asserts: Asserts {
+        regular: delta("asserts.regular"),
+        warning: delta("asserts.warning"),
+        msg: delta("asserts.msg"),
+        user: delta("asserts.user"),
+        rollovers: delta("asserts.rollovers"),
+      },
+    };
+    if self.samples.len() == SERVER_STATUS_HISTORY_SIZE {
+      self.samples.pop_front();
+    }
+    self.samples.push_back(sample);
+  }
+
+  /// Returns the latest `count` samples and the error of the last poll if it failed.
+  pub fn get_server_status_series(
+    &self,
+    count: usize,
+  ) -> (Vec<ServerStatusSample>, Option<String>) {
+    let skip = self.samples.len().saturating_sub(count);
+    (
+      self.samples.iter().skip(skip).cloned().collect(),
+      self.error.clone(),
+    )
+  }
+}
+
+/// Polls `serverStatus` of whichever client is currently connected, every `interval` or not at
+/// all while it is `None`.
+pub fn spawn_server_status_thread(
+  client: Arc<Mutex<Option<Client>>>,
+  interval: Arc<Mutex<Option<Duration>>>,
+) {
+  thread::spawn(move || loop {
+    let current_interval = *interval.lock().unwrap();
+    let current_interval = match current_interval {
+      Some(current_interval) => current_interval,
+      None => {
+        // Checks again later whether the poller was turned back on.
+        thread::sleep(DEFAULT_SERVER_STATUS_INTERVAL);
+        continue;
+      }
+    };
+    thread::sleep(current_interval);
+    // The series is cleared while the client is locked, see `mongodb_connect`.
+    let (client, generation) = match &*client.lock().unwrap() {
+      Some(client) => (client.clone(), SERVER_STATUS.lock().unwrap().generation()),
+      None => continue,
+    };
+    let result = client
+      .database(SERVER_STATUS_DATABASE)
+      .run_command(doc! { SERVER_STATUS_COMMAND: 1 }, None);
+    let handle = &mut *SERVER_STATUS.lock().unwrap();
+    if handle.generation() != generation {
+      continue;
+    }
+    match result {
+      Ok(status) => handle.add_server_status(current_time_millis(), status),
+      Err(e) => handle.add_error(format!("{}", e)),
+    }
+  });
+}
@@ -0,0 +1,244 @@
+use std::collections::{BTreeMap, HashMap};
+
+use mongodb::{
+  bson::{doc, Bson, Document},
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::PError,
+  model::{escape_regex, get_number},
+};
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Shard {
+  pub id: String,
+  pub host: String,
+  pub state: Option<i32>,
+  pub draining: bool,
+  /// The zones that the shard belongs to.
+  pub tags: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ShardedCollection {
+  pub namespace: String,
+  pub key: Document,
+  pub unique: bool,
+  /// Number of chunks owned by each shard.
+  pub chunks: BTreeMap<String, u64>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BalancerStatus {
+  /// `full` or `off`.
+  pub mode: String,
+  pub in_balancer_round: bool,
+  pub num_balancer_rounds: i64,
+  /// The documents of `config.migrations`, one for each chunk being moved.
+  pub active_migrations: Vec<Document>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ZoneRange {
+  pub zone: String,
+  pub namespace: String,
+  pub min: Document,
+  pub max: Document,
+}
+
+fn collect_documents(client: &Client, collection_name: &str) -> Result<Vec<Document>, PError> {
+  let result = client
+    .database("config")
+    .collection::<Document>(collection_name)
+    .find(None, None)
+    .and_then(|cursor| cursor.collect::<Result<Vec<_>, _>>())?;
+  Ok(result)
+}
+
+/// Fails unless connected to a `mongos`.
+pub fn list_shards(client: &Client) -> Result<Vec<Shard>, PError> {
+  let result = client
+    .database("admin")
+    .run_command(doc! { "listShards": 1 }, None)?;
+  let shards = result
+    .get_array("shards")
+    .map(|shards| {
+      shards
+        .iter()
+        .filter_map(Bson::as_document)
+        .map(|shard| Shard {
+          id: shard.get_str("_id").unwrap_or_default().to_string(),
+          host: shard.get_str("host").unwrap_or_default().to_string(),
+          state: get_number(shard, "state").map(|v| v as i32),
+          draining: shard.get_bool("draining").unwrap_or(false),
+          tags: shard
+            .get_array("tags")
+            .map(|tags| {
+              tags
+                .iter()
+                .filter_map(|v| v.as_str().map(|v| v.to_string()))
+                .collect()
+            })
+            .unwrap_or_default(),
+        })
+        .collect()
+    })
+    .unwrap_or_default();
+  Ok(shards)
+}
+
+/// Returns the number of chunks per shard of every collection, or of the collections of
+/// `database_name`, keyed by namespace.
+fn get_chunk_distribution(
+  client: &Client,
+  database_name: Option<&str>,
+) -> Result<HashMap<String, BTreeMap<String, u64>>, PError> {
+  let namespace_prefix = database_name.map(|database_name| format!("{}.", database_name));
+  // Since MongoDB 5.0 the chunks refer to their collection by `uuid` instead of `ns`.
+  let collections = collect_documents(client, "collections")?
+    .into_iter()
+    .filter_map(|collection| {
+      let uuid = collection.get("uuid")?.clone();
+      let namespace = collection.get_str("_id").ok()?.to_string();
+      match &namespace_prefix {
+        Some(prefix) if !namespace.starts_with(prefix.as_str()) => None,
+        _ => Some((uuid, namespace)),
+      }
+    })
+    .collect::<Vec<_>>();
+  let mut stages = Vec::new();
+  if let Some(prefix) = &namespace_prefix {
+    let uuids = collections
+      .iter()
+      .map(|(uuid, _)| uuid.clone())
+      .collect::<Vec<_>>();
+    stages.push(doc! {
+      "$match": {
+        "$or": [
+          { "ns": { "$regex": format!("^{}", escape_regex(prefix)) } },
+          { "uuid": { "$in": uuids } },
+        ]
+      }
+    });
+  }
+  stages.push(doc! {
+    "$group": {
+      "_id": { "ns": "$ns", "uuid": "$uuid", "shard": "$shard" },
+      "count": { "$sum": 1 },
+    }
+  });
+  let namespace_by_uuid = collections
+    .into_iter()
+    .map(|(uuid, namespace)| (format!("{}", uuid), namespace))
+    .collect::<HashMap<_, _>>();
+  let groups = client
+    .database("config")
+    .collection::<Document>("chunks")
+    .aggregate(stages, None)
+    .and_then(|cursor| cursor.collect::<Result<Vec<_>, _>>())?;
+  let mut result: HashMap<String, BTreeMap<String, u64>> = HashMap::new();
+  for group in groups {
+    let id = group.get_document("_id").cloned().unwrap_or_default();
+    let namespace = match id.get_str("ns") {
+      Ok(namespace) => Some(namespace.to_string()),
+      Err(_) => id
+        .get("uuid")
+        .and_then(|uuid| namespace_by_uuid.get(&format!("{}", uuid)).cloned()),
+    };
+    if let Some(namespace) = namespace {
+      let shard = id.get_str("shard").unwrap_or_default().to_string();
+      let count = get_number(&group, "count").unwrap_or(0.0) as u64;
+      *result
+        .entry(namespace)
+        .or_default()
+        .entry(shard)
+        .or_default() += count;
+    }
+  }
+  Ok(result)
+}
+
+pub fn list_sharded_collections(client: &Client) -> Result<Vec<ShardedCollection>, PError> {
+  let mut chunk_distribution = get_chunk_distribution(client, None)?;
+  let result = collect_documents(client, "collections")?
+    .into_iter()
+    .filter(|collection| !collection.get_bool("dropped").unwrap_or(false))
+    .map(|collection| {
+      let namespace = collection.get_str("_id").unwrap_or_default().to_string();
+      ShardedCollection {
+        key: collection.get_document("key").cloned().unwrap_or_default(),
+        unique: collection.get_bool("unique").unwrap_or(false),
+        chunks: chunk_distribution.remove(&namespace).unwrap_or_default(),
+        namespace,
+      }
+    })
+    .collect();
+  Ok(result)
+}
+
+pub fn get_balancer_status(client: &Client) -> Result<BalancerStatus, PError> {
+  let result = client
+    .database("admin")
+    .run_command(doc! { "balancerStatus": 1 }, None)?;
+  Ok(BalancerStatus {
+    mode: result.get_str("mode").unwrap_or_default().to_string(),
+    in_balancer_round: result.get_bool("inBalancerRound").unwrap_or(false),
+    num_balancer_rounds: get_number(&result, "numBalancerRounds").unwrap_or(0.0) as i64,
+    active_migrations: collect_documents(client, "migrations")?,
+  })
+}
+
+pub fn list_zones(client: &Client) -> Result<Vec<ZoneRange>, PError> {
+  let result = collect_documents(client, "tags")?
+    .into_iter()
+    .map(|range| ZoneRange {
+      zone: range.get_str("tag").unwrap_or_default().to_string(),
+      namespace: range.get_str("ns").unwrap_or_default().to_string(),
+      min: range.get_document("min").cloned().unwrap_or_default(),
+      max: range.get_document("max").cloned().unwrap_or_default(),
+    })
+    .collect();
+  Ok(result)
+}
+
+/// Describes how each database is spread over the shards, keyed by database name and then by
+/// shard id. Counting the chunks goes through all of `config.chunks`, so they are only counted for
+/// the database `chunks_of`. Returns nothing unless connected to a `mongos`.
+pub fn get_database_shards(client: &Client, chunks_of: Option<&str>) -> HashMap<String, Document> {
+  let shards = match list_shards(client) {
+    Ok(shards) => shards,
+    Err(_) => return HashMap::new(),
+  };
+  let primary_shards = collect_documents(client, "databases")
+    .unwrap_or_default()
+    .into_iter()
+    .filter_map(|database| {
+      let name = database.get_str("_id").ok()?.to_string();
+      let primary = database.get_str("primary").ok()?.to_string();
+      Some((name, primary))
+    })
+    .collect::<HashMap<_, _>>();
+  let chunk_distribution = match chunks_of {
+    Some(database_name) => get_chunk_distribution(client, Some(database_name)).unwrap_or_default(),
+    None => HashMap::new(),
+  };
+  let mut result: HashMap<String, Document> = HashMap::new();
+  for (database_name, primary) in &primary_shards {
+    let mut database_shards = Document::new();
+    for shard in &shards {
+      let mut database_shard = doc! { "primary": primary == &shard.id };
+      if chunks_of == Some(database_name.as_str()) {
+        let chunks: u64 = chunk_distribution
+          .values()
+          .filter_map(|chunks| chunks.get(&shard.id))
+          .sum();
+        database_shard.insert("chunks", chunks as i64);
+      }
+      database_shards.insert(shard.id.clone(), database_shard);
+    }
+    result.insert(database_name.clone(), database_shards);
+  }
+  result
+}
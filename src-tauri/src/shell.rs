@@ -0,0 +1,940 @@
+use std::convert::TryFrom;
+
+use mongodb::bson::{
+  oid::ObjectId, spec::BinarySubtype, Binary, Bson, DateTime, Decimal128, Document, Regex,
+  Timestamp,
+};
+
+use crate::error::PError;
+
+/// Exponent bias and range of the IEEE 754 decimal128 format.
+const DECIMAL128_EXPONENT_BIAS: i32 = 6176;
+const DECIMAL128_EXPONENT_MAX: i32 = 6111;
+const DECIMAL128_EXPONENT_MIN: i32 = -6176;
+const DECIMAL128_MAX_DIGITS: usize = 34;
+
+/// Encodes a decimal string such as `-1.50` or `2.5E+3` as a `Decimal128`.
+fn parse_decimal128(value: &str) -> Option<Decimal128> {
+  let (negative, unsigned) = match value.strip_prefix('-') {
+    Some(rest) => (true, rest),
+    None => (false, value.strip_prefix('+').unwrap_or(value)),
+  };
+  let sign = if negative { 1u128 << 127 } else { 0 };
+  match unsigned {
+    "Infinity" | "Inf" => return Some(Decimal128::from_bytes((sign | 0x78 << 120).to_le_bytes())),
+    "NaN" => return Some(Decimal128::from_bytes((0x7cu128 << 120).to_le_bytes())),
+    _ => {}
+  }
+  let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+    Some(idx) => (&unsigned[..idx], unsigned[idx + 1..].parse::<i32>().ok()?),
+    None => (unsigned, 0),
+  };
+  let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+  if integer.is_empty() && fraction.is_empty() {
+    return None;
+  }
+  let digits = format!("{}{}", integer, fraction);
+  if !digits.chars().all(|c| c.is_ascii_digit()) {
+    return None;
+  }
+  let digits = digits.trim_start_matches('0');
+  if digits.len() > DECIMAL128_MAX_DIGITS {
+    return None;
+  }
+  let exponent = exponent.checked_sub(i32::try_from(fraction.len()).ok()?)?;
+  if !(DECIMAL128_EXPONENT_MIN..=DECIMAL128_EXPONENT_MAX).contains(&exponent) {
+    return None;
+  }
+  let coefficient = if digits.is_empty() {
+    0
+  } else {
+    digits.parse::<u128>().ok()?
+  };
+  let biased_exponent = (exponent + DECIMAL128_EXPONENT_BIAS) as u128;
+  let bits = sign | biased_exponent << 113 | coefficient;
+  Some(Decimal128::from_bytes(bits.to_le_bytes()))
+}
+
+/// Decodes a `Decimal128` into its string representation, e.g. `1.50` or `2.5E+3`.
+fn format_decimal128(value: &Decimal128) -> String {
+  let bits = u128::from_le_bytes(value.bytes());
+  let sign = if bits >> 127 == 1 { "-" } else { "" };
+  let combination = (bits >> 122) & 0x1f;
+  if combination == 0x1f {
+    return "NaN".to_string();
+  }
+  if combination == 0x1e {
+    return format!("{}Infinity", sign);
+  }
+  // The coefficients larger than 113 bits are not canonical and read as 0.
+  let (exponent, coefficient) = if (bits >> 125) & 0b11 == 0b11 {
+    (((bits >> 111) & 0x3fff) as i32, 0)
+  } else {
+    (((bits >> 113) & 0x3fff) as i32, bits & ((1u128 << 113) - 1))
+  };
+  let exponent = exponent - DECIMAL128_EXPONENT_BIAS;
+  let digits = coefficient.to_string();
+  let adjusted_exponent = exponent + digits.len() as i32 - 1;
+  if exponent <= 0 && adjusted_exponent >= -6 {
+    if exponent == 0 {
+      return format!("{}{}", sign, digits);
+    }
+    let scale = (-exponent) as usize;
+    let padded = format!("{:0>width$}", digits, width = scale + 1);
+    let (integer, fraction) = padded.split_at(padded.len() - scale);
+    return format!("{}{}.{}", sign, integer, fraction);
+  }
+  let (first, rest) = digits.split_at(1);
+  let point = if rest.is_empty() { "" } else { "." };
+  format!("{}{}{}{}E{:+}", sign, first, point, rest, adjusted_exponent)
+}
+
+fn decode_hex(value: &str) -> Option<Vec<u8>> {
+  value
+    .as_bytes()
+    .chunks(2)
+    .map(|pair| {
+      let pair = std::str::from_utf8(pair)
+        .ok()
+        .filter(|pair| pair.len() == 2)?;
+      u8::from_str_radix(pair, 16).ok()
+    })
+    .collect()
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Accepts the dates of `ISODate`, with or without time and time zone.
+fn parse_date(value: &str) -> Option<DateTime> {
+  let value = value.trim();
+  let normalized = match value.find('T').or_else(|| value.find(' ')) {
+    None => format!("{}T00:00:00Z", value),
+    Some(idx) => {
+      let time = &value[idx + 1..];
+      let date = &value[..idx];
+      let has_zone = time.ends_with('Z') || time.ends_with('z') || time.contains(['+', '-']);
+      format!("{}T{}{}", date, time, if has_zone { "" } else { "Z" })
+    }
+  };
+  DateTime::parse_rfc3339_str(normalized).ok()
+}
+
+fn is_identifier_start(c: char) -> bool {
+  c.is_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_identifier_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+struct Parser {
+  chars: Vec<char>,
+  pos: usize,
+}
+
+impl Parser {
+  /// Reports `message` at the 1-based line and column of the character at `pos`.
+  fn error_at(&self, pos: usize, message: String) -> PError {
+    let mut line = 1;
+    let mut column = 1;
+    for c in self.chars.iter().take(pos) {
+      if *c == '\n' {
+        line += 1;
+        column = 1;
+      } else {
+        column += 1;
+      }
+    }
+    PError::ShellSyntaxError {
+      message,
+      line,
+      column,
+    }
+  }
+
+  fn error(&self, message: String) -> PError {
+    self.error_at(self.pos, message)
+  }
+
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).copied()
+  }
+
+  fn peek_at(&self, offset: usize) -> Option<char> {
+    self.chars.get(self.pos + offset).copied()
+  }
+
+  fn bump(&mut self) -> Option<char> {
+    let result = self.peek();
+    self.pos += 1;
+    result
+  }
+
+  fn describe_next(&self) -> String {
+    match self.peek() {
+      Some(c) => format!("`{}`", c),
+      None => "the end of the input".to_string(),
+    }
+  }
+
+  fn expect(&mut self, expected: char) -> Result<(), PError> {
+    self.skip_whitespace()?;
+    if self.peek() == Some(expected) {
+      self.pos += 1;
+      Ok(())
+    } else {
+      Err(self.error(format!(
+        "expected `{}` but found {}",
+        expected,
+        self.describe_next()
+      )))
+    }
+  }
+
+  /// Skips the whitespace and the `//` and `/* */` comments.
+  fn skip_whitespace(&mut self) -> Result<(), PError> {
+    loop {
+      match (self.peek(), self.peek_at(1)) {
+        (Some(c), _) if c.is_whitespace() => self.pos += 1,
+        (Some('/'), Some('/')) => {
+          while !matches!(self.peek(), None | Some('\n')) {
+            self.pos += 1;
+          }
+        }
+        (Some('/'), Some('*')) => {
+          let start = self.pos;
+          self.pos += 2;
+          loop {
+            match (self.peek(), self.peek_at(1)) {
+              (Some('*'), Some('/')) => {
+                self.pos += 2;
+                break;
+              }
+              (Some(_), _) => self.pos += 1,
+              (None, _) => return Err(self.error_at(start, "unterminated comment".to_string())),
+            }
+          }
+        }
+        _ => return Ok(()),
+      }
+    }
+  }
+
+  fn parse_value(&mut self) -> Result<Bson, PError> {
+    self.skip_whitespace()?;
+    match self.peek() {
+      Some('{') => self.parse_object().map(Bson::Document),
+      Some('[') => self.parse_array(),
+      Some('"') | Some('\'') => self.parse_string().map(Bson::String),
+      Some('/') => self.parse_regex(),
+      Some(c) if c.is_ascii_digit() || c == '-' || c == '+' || c == '.' => self.parse_number(),
+      Some(c) if is_identifier_start(c) => {
+        let start = self.pos;
+        let identifier = self.parse_identifier();
+        match identifier.as_str() {
+          "true" => Ok(Bson::Boolean(true)),
+          "false" => Ok(Bson::Boolean(false)),
+          "null" | "undefined" => Ok(Bson::Null),
+          "Infinity" => Ok(Bson::Double(f64::INFINITY)),
+          "NaN" => Ok(Bson::Double(f64::NAN)),
+          "new" => {
+            self.skip_whitespace()?;
+            let start = self.pos;
+            let name = self.parse_identifier();
+            self.parse_call(start, &name)
+          }
+          name => self.parse_call(start, name),
+        }
+      }
+      _ => Err(self.error(format!(
+        "expected a value but found {}",
+        self.describe_next()
+      ))),
+    }
+  }
+
+  fn parse_identifier(&mut self) -> String {
+    let mut result = String::new();
+    while let Some(c) = self.peek().filter(|c| is_identifier_char(*c)) {
+      result.push(c);
+      self.pos += 1;
+    }
+    result
+  }
+
+  fn parse_object(&mut self) -> Result<Document, PError> {
+    self.expect('{')?;
+    let mut result = Document::new();
+    loop {
+      self.skip_whitespace()?;
+      if self.peek() == Some('}') {
+        self.pos += 1;
+        return Ok(result);
+      }
+      let key = self.parse_key()?;
+      self.expect(':')?;
+      let value = self.parse_value()?;
+      result.insert(key, value);
+      self.skip_whitespace()?;
+      match self.peek() {
+        Some(',') => self.pos += 1,
+        Some('}') => {}
+        _ => {
+          return Err(self.error(format!(
+            "expected `,` or `}}` but found {}",
+            self.describe_next()
+          )))
+        }
+      }
+    }
+  }
+
+  /// Keys may be quoted, or unquoted identifiers and numbers, and dotted paths such as `a.b`.
+  fn parse_key(&mut self) -> Result<String, PError> {
+    match self.peek() {
+      Some('"') | Some('\'') => self.parse_string(),
+      Some(c) if is_identifier_char(c) => {
+        let mut result = String::new();
+        while let Some(c) = self.peek().filter(|c| is_identifier_char(*c) || *c == '.') {
+          result.push(c);
+          self.pos += 1;
+        }
+        Ok(result)
+      }
+      _ => Err(self.error(format!("expected a key but found {}", self.describe_next()))),
+    }
+  }
+
+  fn parse_array(&mut self) -> Result<Bson, PError> {
+    self.expect('[')?;
+    let mut result = Vec::new();
+    loop {
+      self.skip_whitespace()?;
+      if self.peek() == Some(']') {
+        self.pos += 1;
+        return Ok(Bson::Array(result));
+      }
+      result.push(self.parse_value()?);
+      self.skip_whitespace()?;
+      match self.peek() {
+        Some(',') => self.pos += 1,
+        Some(']') => {}
+        _ => {
+          return Err(self.error(format!(
+            "expected `,` or `]` but found {}",
+            self.describe_next()
+          )))
+        }
+      }
+    }
+  }
+
+  fn parse_hex_escape(&mut self, length: usize) -> Result<u32, PError> {
+    let start = self.pos;
+    let digits = self
+      .chars
+      .get(self.pos..self.pos + length)
+      .map(|chars| chars.iter().collect::<String>())
+      .unwrap_or_default();
+    self.pos += length;
+    u32::from_str_radix(&digits, 16)
+      .ok()
+      .filter(|_| digits.len() == length)
+      .ok_or_else(|| self.error_at(start, "invalid escape sequence".to_string()))
+  }
+
+  fn parse_string(&mut self) -> Result<String, PError> {
+    let start = self.pos;
+    let quote = self.bump();
+    let mut result = String::new();
+    loop {
+      match self.bump() {
+        None | Some('\n') => return Err(self.error_at(start, "unterminated string".to_string())),
+        Some(c) if Some(c) == quote => return Ok(result),
+        Some('\\') => {
+          let escape_start = self.pos - 1;
+          match self.bump() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('b') => result.push('\u{8}'),
+            Some('f') => result.push('\u{c}'),
+            Some('v') => result.push('\u{b}'),
+            Some('0') => result.push('\0'),
+            // A line continuation.
+            Some('\n') => {}
+            Some('x') => {
+              let code = self.parse_hex_escape(2)?;
+              result.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+            }
+            Some('u') => {
+              let mut code = self.parse_hex_escape(4)?;
+              // Characters outside of the BMP are written as a surrogate pair.
+              if (0xd800..0xdc00).contains(&code)
+                && self.peek() == Some('\\')
+                && self.peek_at(1) == Some('u')
+              {
+                self.pos += 2;
+                let low = self.parse_hex_escape(4)?;
+                code = 0x10000 + ((code - 0xd800) << 10) + (low.wrapping_sub(0xdc00) & 0x3ff);
+              }
+              match char::from_u32(code) {
+                Some(c) => result.push(c),
+                None => {
+                  return Err(self.error_at(escape_start, "invalid unicode escape".to_string()))
+                }
+              }
+            }
+            Some(c) => result.push(c),
+            None => return Err(self.error_at(start, "unterminated string".to_string())),
+          }
+        }
+        Some(c) => result.push(c),
+      }
+    }
+  }
+
+  fn parse_regex(&mut self) -> Result<Bson, PError> {
+    let start = self.pos;
+    self.pos += 1;
+    let mut pattern = String::new();
+    let mut in_class = false;
+    loop {
+      match self.bump() {
+        None | Some('\n') => {
+          return Err(self.error_at(start, "unterminated regular expression".to_string()))
+        }
+        Some('\\') => {
+          pattern.push('\\');
+          match self.bump() {
+            Some(c) if c != '\n' => pattern.push(c),
+            _ => return Err(self.error_at(start, "unterminated regular expression".to_string())),
+          }
+        }
+        Some('[') => {
+          in_class = true;
+          pattern.push('[');
+        }
+        Some(']') => {
+          in_class = false;
+          pattern.push(']');
+        }
+        Some('/') if !in_class => break,
+        Some(c) => pattern.push(c),
+      }
+    }
+    let flags_start = self.pos;
+    let flags = self.parse_identifier();
+    let options = regex_options(&flags).ok_or_else(|| {
+      self.error_at(
+        flags_start,
+        format!("invalid regular expression flags `{}`", flags),
+      )
+    })?;
+    Ok(Bson::RegularExpression(Regex { pattern, options }))
+  }
+
+  fn parse_number(&mut self) -> Result<Bson, PError> {
+    let start = self.pos;
+    let mut text = String::new();
+    if let Some(sign) = self.peek().filter(|c| *c == '-' || *c == '+') {
+      text.push(sign);
+      self.pos += 1;
+    }
+    if matches!(self.peek(), Some(c) if is_identifier_start(c)) {
+      return match self.parse_identifier().as_str() {
+        "Infinity" if text == "-" => Ok(Bson::Double(f64::NEG_INFINITY)),
+        "Infinity" => Ok(Bson::Double(f64::INFINITY)),
+        _ => Err(self.error_at(start, "invalid number".to_string())),
+      };
+    }
+    while let Some(c) = self.peek() {
+      let after_exponent = matches!(text.chars().last(), Some('e') | Some('E'));
+      if c.is_ascii_alphanumeric()
+        || c == '.'
+        || c == '_'
+        || (after_exponent && (c == '-' || c == '+'))
+      {
+        text.push(c);
+        self.pos += 1;
+      } else {
+        break;
+      }
+    }
+    let digits = text.replace('_', "");
+    let unsigned = digits.trim_start_matches(['-', '+']);
+    let negative = digits.starts_with('-');
+    let hex = unsigned
+      .strip_prefix("0x")
+      .or_else(|| unsigned.strip_prefix("0X"));
+    let integer = match hex {
+      Some(hex) => i64::from_str_radix(hex, 16).ok(),
+      None => unsigned.parse::<i64>().ok(),
+    }
+    .map(|v| if negative { -v } else { v });
+    if let Some(v) = integer {
+      return Ok(match i32::try_from(v) {
+        Ok(v) => Bson::Int32(v),
+        Err(_) => Bson::Int64(v),
+      });
+    }
+    match digits.parse::<f64>() {
+      Ok(v)
+        if hex.is_none()
+          && !unsigned.contains(|c: char| c.is_alphabetic() && c != 'e' && c != 'E') =>
+      {
+        Ok(Bson::Double(v))
+      }
+      _ => Err(self.error_at(start, format!("invalid number `{}`", text))),
+    }
+  }
+
+  /// Parses the arguments of `name(...)` and builds the value, `start` is the position of the
+  /// name.
+  fn parse_call(&mut self, start: usize, name: &str) -> Result<Bson, PError> {
+    self.skip_whitespace()?;
+    if self.peek() != Some('(') {
+      return Err(self.error_at(start, format!("unknown identifier `{}`", name)));
+    }
+    self.pos += 1;
+    let mut arguments = Vec::new();
+    loop {
+      self.skip_whitespace()?;
+      if self.peek() == Some(')') {
+        self.pos += 1;
+        break;
+      }
+      arguments.push(self.parse_value()?);
+      self.skip_whitespace()?;
+      match self.peek() {
+        Some(',') => self.pos += 1,
+        Some(')') => {}
+        _ => {
+          return Err(self.error(format!(
+            "expected `,` or `)` but found {}",
+            self.describe_next()
+          )))
+        }
+      }
+    }
+    build_value(name, &arguments).map_err(|message| self.error_at(start, message))
+  }
+}
+
+/// Sorts the flags alphabetically, as stored by BSON. `g` is meaningless for a query.
+fn regex_options(flags: &str) -> Option<String> {
+  let mut options = flags.chars().filter(|c| *c != 'g').collect::<Vec<_>>();
+  if options.iter().any(|c| !"ilmsux".contains(*c)) {
+    return None;
+  }
+  options.sort_unstable();
+  options.dedup();
+  Some(options.into_iter().collect())
+}
+
+fn argument_integer(argument: &Bson) -> Option<i64> {
+  match argument {
+    Bson::Int32(v) => Some(i64::from(*v)),
+    Bson::Int64(v) => Some(*v),
+    Bson::Double(v) if v.fract() == 0.0 => Some(*v as i64),
+    Bson::String(s) => s.trim().parse().ok(),
+    _ => None,
+  }
+}
+
+/// Builds the value of a constructor such as `ObjectId("...")` from its arguments.
+fn build_value(name: &str, arguments: &[Bson]) -> Result<Bson, String> {
+  let first = arguments.first();
+  let first_string = first.and_then(Bson::as_str);
+  let invalid = || format!("invalid arguments for {}()", name);
+  match name {
+    "ObjectId" => match first_string {
+      Some(hex) => ObjectId::parse_str(hex)
+        .map(Bson::ObjectId)
+        .map_err(|_| format!("`{}` is not a valid ObjectId", hex)),
+      None if arguments.is_empty() => Ok(Bson::ObjectId(ObjectId::new())),
+      None => Err(invalid()),
+    },
+    "ISODate" | "Date" => match first {
+      None => Ok(Bson::DateTime(DateTime::now())),
+      Some(Bson::String(date)) => parse_date(date)
+        .map(Bson::DateTime)
+        .ok_or_else(|| format!("`{}` is not a valid date", date)),
+      Some(millis) => argument_integer(millis)
+        .map(|millis| Bson::DateTime(DateTime::from_millis(millis)))
+        .ok_or_else(invalid),
+    },
+    "NumberInt" | "Int32" => first
+      .and_then(argument_integer)
+      .and_then(|v| i32::try_from(v).ok())
+      .map(Bson::Int32)
+      .ok_or_else(invalid),
+    "NumberLong" | "Long" => first
+      .and_then(argument_integer)
+      .map(Bson::Int64)
+      .ok_or_else(invalid),
+    "Double" => match first {
+      Some(Bson::String(s)) => s.trim().parse().map(Bson::Double).map_err(|_| invalid()),
+      Some(Bson::Double(v)) => Ok(Bson::Double(*v)),
+      Some(v) => argument_integer(v)
+        .map(|v| Bson::Double(v as f64))
+        .ok_or_else(invalid),
+      None => Err(invalid()),
+    },
+    "NumberDecimal" | "Decimal128" => {
+      let value = match first {
+        Some(Bson::String(s)) => s.trim().to_string(),
+        Some(Bson::Int32(v)) => v.to_string(),
+        Some(Bson::Int64(v)) => v.to_string(),
+        Some(Bson::Double(v)) => format!("{:?}", v),
+        _ => return Err(invalid()),
+      };
+      parse_decimal128(&value)
+        .map(Bson::Decimal128)
+        .ok_or_else(|| format!("`{}` is not a valid decimal", value))
+    }
+    "Timestamp" => {
+      let (time, increment) = match arguments {
+        [Bson::Document(timestamp)] => (timestamp.get("t"), timestamp.get("i")),
+        [time, increment] => (Some(time), Some(increment)),
+        _ => return Err(invalid()),
+      };
+      let part = |value: Option<&Bson>| {
+        value
+          .and_then(argument_integer)
+          .and_then(|v| u32::try_from(v).ok())
+      };
+      match (part(time), part(increment)) {
+        (Some(time), Some(increment)) => Ok(Bson::Timestamp(Timestamp { time, increment })),
+        _ => Err(invalid()),
+      }
+    }
+    "UUID" => {
+      let uuid = first_string.ok_or_else(invalid)?;
+      decode_hex(&uuid.replace('-', ""))
+        .filter(|bytes| bytes.len() == 16)
+        .map(|bytes| {
+          Bson::Binary(Binary {
+            subtype: BinarySubtype::Uuid,
+            bytes,
+          })
+        })
+        .ok_or_else(|| format!("`{}` is not a valid UUID", uuid))
+    }
+    "BinData" => match arguments {
+      [subtype, Bson::String(data)] => {
+        let subtype = argument_integer(subtype)
+          .and_then(|v| u8::try_from(v).ok())
+          .ok_or_else(invalid)?;
+        base64::decode(data)
+          .map(|bytes| {
+            Bson::Binary(Binary {
+              subtype: BinarySubtype::from(subtype),
+              bytes,
+            })
+          })
+          .map_err(|_| "BinData() expects base64 data".to_string())
+      }
+      _ => Err(invalid()),
+    },
+    "RegExp" => match arguments {
+      [Bson::String(pattern)] => Ok(Bson::RegularExpression(Regex {
+        pattern: pattern.clone(),
+        options: String::new(),
+      })),
+      [Bson::String(pattern), Bson::String(flags)] => regex_options(flags)
+        .map(|options| {
+          Bson::RegularExpression(Regex {
+            pattern: pattern.clone(),
+            options,
+          })
+        })
+        .ok_or_else(|| format!("invalid regular expression flags `{}`", flags)),
+      _ => Err(invalid()),
+    },
+    "MinKey" if arguments.is_empty() => Ok(Bson::MinKey),
+    "MaxKey" if arguments.is_empty() => Ok(Bson::MaxKey),
+    _ => Err(format!("unknown function `{}`", name)),
+  }
+}
+
+/// Parses a value written in the mongo shell syntax, e.g. `{ _id: ObjectId("..."), a: /x/i }`.
+pub fn parse_shell_value(input: &str) -> Result<Bson, PError> {
+  let mut parser = Parser {
+    chars: input.chars().collect(),
+    pos: 0,
+  };
+  let result = parser.parse_value()?;
+  parser.skip_whitespace()?;
+  if parser.peek().is_some() {
+    return Err(parser.error(format!(
+      "unexpected {} after the value",
+      parser.describe_next()
+    )));
+  }
+  Ok(result)
+}
+
+/// Parses a filter, projection or sort, an empty input is an empty document.
+pub fn parse_shell_document(input: &str) -> Result<Document, PError> {
+  if input.trim().is_empty() {
+    return Ok(Document::new());
+  }
+  match parse_shell_value(input)? {
+    Bson::Document(document) => Ok(document),
+    _ => Err(PError::ShellSyntaxError {
+      message: "expected a document".to_string(),
+      line: 1,
+      column: 1,
+    }),
+  }
+}
+
+fn is_identifier(key: &str) -> bool {
+  let mut chars = key.chars();
+  matches!(chars.next(), Some(c) if is_identifier_start(c)) && chars.all(is_identifier_char)
+}
+
+fn write_string(out: &mut String, value: &str) {
+  out.push('\'');
+  for c in value.chars() {
+    match c {
+      '\'' => out.push_str("\\'"),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+      c => out.push(c),
+    }
+  }
+  out.push('\'');
+}
+
+/// Escapes the `/` of a pattern that would end the regular expression literal.
+fn write_regex(out: &mut String, regex: &Regex) {
+  out.push('/');
+  let mut escaped = false;
+  let mut in_class = false;
+  for c in regex.pattern.chars() {
+    if !escaped {
+      match c {
+        '/' if !in_class => out.push('\\'),
+        '[' => in_class = true,
+        ']' => in_class = false,
+        _ => {}
+      }
+    }
+    escaped = c == '\\' && !escaped;
+    out.push(c);
+  }
+  out.push('/');
+  out.push_str(&regex.options);
+}
+
+fn write_double(out: &mut String, value: f64) {
+  if value.is_nan() {
+    out.push_str("NaN");
+  } else if value.is_infinite() {
+    out.push_str(if value > 0.0 { "Infinity" } else { "-Infinity" });
+  } else {
+    // The debug format keeps the `.0` of integral values, so that they parse back as doubles.
+    out.push_str(&format!("{:?}", value));
+  }
+}
+
+fn write_entries(
+  out: &mut String,
+  entries: Vec<(Option<&str>, &Bson)>,
+  brackets: (char, char),
+  indent: Option<usize>,
+) {
+  out.push(brackets.0);
+  if entries.is_empty() {
+    out.push(brackets.1);
+    return;
+  }
+  let count = entries.len();
+  for (idx, (key, value)) in entries.into_iter().enumerate() {
+    match indent {
+      Some(indent) => {
+        out.push('\n');
+        out.push_str(&" ".repeat(indent + 2));
+      }
+      None => out.push(' '),
+    }
+    if let Some(key) = key {
+      if is_identifier(key) {
+        out.push_str(key);
+      } else {
+        write_string(out, key);
+      }
+      out.push_str(": ");
+    }
+    write_value(out, value, indent.map(|indent| indent + 2));
+    if idx + 1 < count {
+      out.push(',');
+    }
+  }
+  match indent {
+    Some(indent) => {
+      out.push('\n');
+      out.push_str(&" ".repeat(indent));
+    }
+    None => out.push(' '),
+  }
+  out.push(brackets.1);
+}
+
+fn write_value(out: &mut String, value: &Bson, indent: Option<usize>) {
+  match value {
+    Bson::Document(document) => write_entries(
+      out,
+      document
+        .iter()
+        .map(|(k, v)| (Some(k.as_str()), v))
+        .collect(),
+      ('{', '}'),
+      indent,
+    ),
+    Bson::Array(array) => write_entries(
+      out,
+      array.iter().map(|v| (None, v)).collect(),
+      ('[', ']'),
+      indent,
+    ),
+    Bson::String(s) => write_string(out, s),
+    Bson::Int32(v) => out.push_str(&v.to_string()),
+    Bson::Int64(v) => out.push_str(&format!("NumberLong(\"{}\")", v)),
+    Bson::Double(v) => write_double(out, *v),
+    Bson::Decimal128(v) => out.push_str(&format!("NumberDecimal(\"{}\")", format_decimal128(v))),
+    Bson::Boolean(v) => out.push_str(&v.to_string()),
+    Bson::Null => out.push_str("null"),
+    Bson::Undefined => out.push_str("undefined"),
+    Bson::ObjectId(oid) => out.push_str(&format!("ObjectId(\"{}\")", oid.to_hex())),
+    Bson::DateTime(date) => match date.try_to_rfc3339_string() {
+      Ok(date) => out.push_str(&format!("ISODate(\"{}\")", date)),
+      Err(_) => out.push_str(&format!("new Date({})", date.timestamp_millis())),
+    },
+    Bson::RegularExpression(regex) => write_regex(out, regex),
+    Bson::Timestamp(timestamp) => out.push_str(&format!(
+      "Timestamp({{ t: {}, i: {} }})",
+      timestamp.time, timestamp.increment
+    )),
+    Bson::Binary(binary) if binary.subtype == BinarySubtype::Uuid && binary.bytes.len() == 16 => {
+      let hex = encode_hex(&binary.bytes);
+      out.push_str(&format!(
+        "UUID(\"{}-{}-{}-{}-{}\")",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+      ))
+    }
+    Bson::Binary(binary) => out.push_str(&format!(
+      "BinData({}, \"{}\")",
+      u8::from(binary.subtype),
+      base64::encode(&binary.bytes)
+    )),
+    Bson::MinKey => out.push_str("MinKey()"),
+    Bson::MaxKey => out.push_str("MaxKey()"),
+    // The remaining types cannot be written in the shell, the extended JSON is shown instead.
+    value => out.push_str(&value.clone().into_relaxed_extjson().to_string()),
+  }
+}
+
+/// Writes a value in the mongo shell syntax, over several lines indented by 2 spaces when
+/// `pretty` is set.
+pub fn to_shell_syntax(value: &Bson, pretty: bool) -> String {
+  let mut result = String::new();
+  write_value(&mut result, value, if pretty { Some(0) } else { None });
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use mongodb::bson::doc;
+
+  use super::*;
+
+  fn round_trip(input: &str) -> Document {
+    let document = parse_shell_document(input).unwrap();
+    for pretty in [false, true] {
+      let written = to_shell_syntax(&Bson::Document(document.clone()), pretty);
+      assert_eq!(
+        parse_shell_document(&written).unwrap(),
+        document,
+        "{}",
+        written
+      );
+    }
+    document
+  }
+
+  #[test]
+  fn unquoted_and_single_quoted_keys() {
+    let document = round_trip("{ a: 1, 'b c': 'it\\'s', \"d\": \"x\" }");
+    assert_eq!(document, doc! { "a": 1, "b c": "it's", "d": "x" });
+  }
+
+  #[test]
+  fn regex_flags_are_sorted() {
+    let document = round_trip("{ name: /^a\\/b/xi, other: RegExp('c', 'gm') }");
+    assert_eq!(
+      document,
+      doc! {
+        "name": Regex { pattern: "^a\\/b".to_string(), options: "ix".to_string() },
+        "other": Regex { pattern: "c".to_string(), options: "m".to_string() },
+      }
+    );
+  }
+
+  #[test]
+  fn trailing_commas() {
+    let document = round_trip("{ a: [1, 2,], b: { c: true, }, }");
+    assert_eq!(document, doc! { "a": [1, 2], "b": { "c": true } });
+  }
+
+  #[test]
+  fn number_long_and_decimal() {
+    let document = round_trip(
+      "{ long: NumberLong('9007199254740993'), decimal: NumberDecimal('-1.50'), big: NumberDecimal('2.5E+3') }",
+    );
+    assert_eq!(document.get("long"), Some(&Bson::Int64(9007199254740993)));
+    assert_eq!(
+      document.get("decimal"),
+      Some(&Bson::Decimal128(parse_decimal128("-1.50").unwrap()))
+    );
+    assert_eq!(
+      format_decimal128(&parse_decimal128("-1.50").unwrap()),
+      "-1.50"
+    );
+  }
+
+  #[test]
+  fn decimal_exponent_overflow() {
+    assert!(parse_decimal128("1.5e-2147483648").is_none());
+    assert!(parse_shell_document("{ a: NumberDecimal('1.5e-2147483648') }").is_err());
+  }
+
+  #[test]
+  fn uuid() {
+    let document = round_trip("{ id: UUID('0123456789abcdef0123456789abcdef') }");
+    let binary = document.get("id").unwrap();
+    assert!(matches!(
+      binary,
+      Bson::Binary(Binary { subtype: BinarySubtype::Uuid, bytes }) if bytes.len() == 16
+    ));
+  }
+
+  #[test]
+  fn errors_report_the_line_and_column() {
+    match parse_shell_document("{\n  a: 1,\n  b: ObjectId('nope')\n}") {
+      Err(PError::ShellSyntaxError { line, column, .. }) => assert_eq!((line, column), (3, 6)),
+      result => panic!("unexpected {:?}", result),
+    }
+  }
+}
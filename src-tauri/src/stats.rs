@@ -0,0 +1,200 @@
+use mongodb::{
+  bson::{doc, Document},
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::PError,
+  model::{as_number, get_number},
+};
+
+/// Returns the value of `key` in a WiredTiger creation string such as
+/// `block_compressor=snappy,prefix_compression=true`.
+fn creation_string_value(creation_string: &str, key: &str) -> Option<String> {
+  creation_string
+    .split(',')
+    .filter_map(|option| option.split_once('='))
+    .find(|(k, _)| *k == key)
+    .map(|(_, v)| v.to_string())
+    .filter(|v| !v.is_empty())
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct IndexStatistics {
+  pub name: String,
+  pub size: u64,
+  pub prefix_compression: Option<bool>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct CollectionStatistics {
+  pub namespace: String,
+  pub count: u64,
+  pub avg_obj_size: f64,
+  /// Uncompressed size of the documents in bytes.
+  pub size: u64,
+  pub storage_size: u64,
+  /// Space that could be reclaimed by `compact`, unknown before MongoDB 4.4.
+  pub free_storage_size: Option<u64>,
+  pub total_index_size: u64,
+  pub indexes: Vec<IndexStatistics>,
+  /// e.g. `snappy`, `zlib`, `zstd` or `none`.
+  pub block_compressor: Option<String>,
+  pub capped: bool,
+  pub max: Option<i64>,
+  pub max_size: Option<i64>,
+  /// The `timeseries` statistics, only for the time series collections.
+  pub timeseries: Option<Document>,
+  /// The shards holding the collection, empty unless connected to a `mongos`.
+  pub shards: Vec<String>,
+}
+
+impl CollectionStatistics {
+  fn new(namespace: String) -> Self {
+    CollectionStatistics {
+      namespace,
+      count: 0,
+      avg_obj_size: 0.0,
+      size: 0,
+      storage_size: 0,
+      free_storage_size: None,
+      total_index_size: 0,
+      indexes: Vec::new(),
+      block_compressor: None,
+      capped: false,
+      max: None,
+      max_size: None,
+      timeseries: None,
+      shards: Vec::new(),
+    }
+  }
+
+  /// Adds the `storageStats` of one shard, or of the whole collection on a `mongod`.
+  fn add_storage_stats(&mut self, storage_stats: &Document) {
+    let get_size = |key: &str| get_number(storage_stats, key).unwrap_or(0.0) as u64;
+    self.count += get_size("count");
+    self.size += get_size("size");
+    self.storage_size += get_size("storageSize");
+    self.total_index_size += get_size("totalIndexSize");
+    if let Some(free_storage_size) = get_number(storage_stats, "freeStorageSize") {
+      *self.free_storage_size.get_or_insert(0) += free_storage_size as u64;
+    }
+    let index_details = storage_stats
+      .get_document("indexDetails")
+      .cloned()
+      .unwrap_or_default();
+    for (name, size) in storage_stats
+      .get_document("indexSizes")
+      .cloned()
+      .unwrap_or_default()
+    {
+      // Index names may contain dots, so they cannot be read with `get_number`.
+      let size = as_number(&size).unwrap_or(0.0) as u64;
+      match self.indexes.iter_mut().find(|index| index.name == name) {
+        Some(index) => index.size += size,
+        None => self.indexes.push(IndexStatistics {
+          prefix_compression: index_details
+            .get_document(&name)
+            .and_then(|details| details.get_str("creationString"))
+            .ok()
+            .and_then(|v| creation_string_value(v, "prefix_compression"))
+            .map(|v| v == "true"),
+          name,
+          size,
+        }),
+      }
+    }
+    if let (None, Ok(wired_tiger)) = (
+      &self.block_compressor,
+      storage_stats.get_document("wiredTiger"),
+    ) {
+      // An empty `block_compressor` means that the collection is not compressed.
+      let block_compressor = wired_tiger
+        .get_str("creationString")
+        .ok()
+        .and_then(|v| creation_string_value(v, "block_compressor"));
+      self.block_compressor = Some(block_compressor.unwrap_or_else(|| "none".to_string()));
+    }
+    self.capped |= storage_stats.get_bool("capped").unwrap_or(false);
+    self.max = self
+      .max
+      .or_else(|| get_number(storage_stats, "max").map(|v| v as i64));
+    self.max_size = self
+      .max_size
+      .or_else(|| get_number(storage_stats, "maxSize").map(|v| v as i64));
+    if self.timeseries.is_none() {
+      self.timeseries = storage_stats.get_document("timeseries").ok().cloned();
+    }
+  }
+}
+
+/// Runs `$collStats`, which unlike `collStats` reports every shard when connected to a `mongos`.
+pub fn get_collection_statistics(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+) -> Result<CollectionStatistics, PError> {
+  let stages = vec![doc! { "$collStats": { "storageStats": {} } }];
+  let shards = client
+    .database(database_name)
+    .collection::<Document>(collection_name)
+    .aggregate(stages, None)
+    .and_then(|cursor| cursor.collect::<Result<Vec<_>, _>>())?;
+  let mut result = CollectionStatistics::new(format!("{}.{}", database_name, collection_name));
+  for shard in shards {
+    if let Ok(storage_stats) = shard.get_document("storageStats") {
+      result.add_storage_stats(storage_stats);
+    }
+    if let Ok(shard) = shard.get_str("shard") {
+      result.shards.push(shard.to_string());
+    }
+  }
+  if result.count > 0 {
+    result.avg_obj_size = result.size as f64 / result.count as f64;
+  }
+  Ok(result)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DatabaseStatistics {
+  pub database_name: String,
+  pub collections: u64,
+  pub views: u64,
+  pub objects: u64,
+  pub avg_obj_size: f64,
+  pub data_size: u64,
+  pub storage_size: u64,
+  pub free_storage_size: Option<u64>,
+  pub indexes: u64,
+  pub index_size: u64,
+  pub total_size: Option<u64>,
+  /// Disk usage of the file system holding the data, unknown on a `mongos`.
+  pub fs_used_size: Option<u64>,
+  pub fs_total_size: Option<u64>,
+}
+
+pub fn get_database_statistics(
+  client: &Client,
+  database_name: &str,
+) -> Result<DatabaseStatistics, PError> {
+  let result = client
+    .database(database_name)
+    .run_command(doc! { "dbStats": 1 }, None)?;
+  let get_size = |key: &str| get_number(&result, key).map(|v| v as u64);
+  Ok(DatabaseStatistics {
+    database_name: database_name.to_string(),
+    collections: get_size("collections").unwrap_or(0),
+    views: get_size("views").unwrap_or(0),
+    objects: get_size("objects").unwrap_or(0),
+    avg_obj_size: get_number(&result, "avgObjSize").unwrap_or(0.0),
+    data_size: get_size("dataSize").unwrap_or(0),
+    storage_size: get_size("storageSize").unwrap_or(0),
+    free_storage_size: get_size("freeStorageSize"),
+    indexes: get_size("indexes").unwrap_or(0),
+    index_size: get_size("indexSize").unwrap_or(0),
+    total_size: get_size("totalSize"),
+    fs_used_size: get_size("fsUsedSize"),
+    fs_total_size: get_size("fsTotalSize"),
+  })
+}
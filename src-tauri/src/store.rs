@@ -0,0 +1,341 @@
+use std::{
+  path::PathBuf,
+  sync::{Arc, Mutex},
+  thread,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::PError,
+  mongodb_events::{DATABASE_HEARTBEAT, DATABASE_TOPOLOGY, SERVER_METRIC},
+};
+
+/// How often the in-memory metrics are rolled up into the store.
+pub const ROLLUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Rollups older than this are removed unless another retention is configured.
+pub const DEFAULT_RETENTION_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Longest retention that can be configured, ten years.
+pub const MAX_RETENTION_SECS: u64 = 10 * 365 * 24 * 60 * 60;
+
+const RETENTION_SETTING: &str = "retention_secs";
+
+pub fn current_time_millis() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .unwrap()
+    .as_millis() as u64
+}
+
+/// Connections are identified by the address they were made with.
+pub fn connection_profile(url: &str, port: u16) -> String {
+  format!("{}:{}", url, port)
+}
+
+/// A summary of the heartbeat, topology and command metrics over one `ROLLUP_INTERVAL`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MetricsRollup {
+  pub profile: String,
+  pub timestamp: u64,
+  pub heartbeat_succeeded: u64,
+  pub heartbeat_failed: u64,
+  pub heartbeat_average_duration: u64,
+  pub commands_started: u64,
+  pub commands_succeeded: u64,
+  pub commands_failed: u64,
+  pub command_average_duration: u64,
+  pub server_count: u64,
+}
+
+impl MetricsRollup {
+  /// Collects the metrics of the interval ending at `timestamp`.
+  pub fn collect(profile: String, timestamp: u64) -> MetricsRollup {
+    let heartbeat = DATABASE_HEARTBEAT.lock().unwrap().take_rollup();
+    let commands = SERVER_METRIC.lock().unwrap().get_commands_rollup(
+      (timestamp - ROLLUP_INTERVAL.as_millis() as u64) as usize,
+      timestamp as usize,
+    );
+    let server_count = DATABASE_TOPOLOGY.lock().unwrap().get_server_count() as u64;
+    let heartbeat_count = heartbeat.succeeded + heartbeat.failed;
+    let commands_finished = commands.succeeded + commands.failed;
+    MetricsRollup {
+      profile,
+      timestamp,
+      heartbeat_succeeded: heartbeat.succeeded,
+      heartbeat_failed: heartbeat.failed,
+      heartbeat_average_duration: heartbeat
+        .total_duration
+        .checked_div(heartbeat_count)
+        .unwrap_or(0),
+      commands_started: commands.started,
+      commands_succeeded: commands.succeeded,
+      commands_failed: commands.failed,
+      command_average_duration: commands
+        .total_time_taken
+        .checked_div(commands_finished)
+        .unwrap_or(0),
+      server_count,
+    }
+  }
+}
+
+pub struct MetricsStore {
+  connection: Connection,
+  profile: Option<String>,
+  retention_secs: u64,
+}
+
+impl MetricsStore {
+  pub fn default_path() -> Option<PathBuf> {
+    tauri::api::path::data_dir().map(|p| p.join("pinky-pie").join("metrics.sqlite3"))
+  }
+
+  pub fn open(path: PathBuf) -> Result<MetricsStore, PError> {
+    if let Some(parent) = path.parent() {
+      std::fs::create_dir_all(parent).map_err(|e| PError::StoreError(format!("{:#?}", e)))?;
+    }
+    Self::from_connection(Connection::open(path)?)
+  }
+
+  fn from_connection(connection: Connection) -> Result<MetricsStore, PError> {
+    connection.execute_batch(
+      "CREATE TABLE IF NOT EXISTS metrics_rollup (
+        profile TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        heartbeat_succeeded INTEGER NOT NULL,
+        heartbeat_failed INTEGER NOT NULL,
+        heartbeat_average_duration INTEGER NOT NULL,
+        commands_started INTEGER NOT NULL,
+        commands_succeeded INTEGER NOT NULL,
+        commands_failed INTEGER NOT NULL,
+        command_average_duration INTEGER NOT NULL,
+        server_count INTEGER NOT NULL,
+        PRIMARY KEY (profile, timestamp)
+      );
+      CREATE TABLE IF NOT EXISTS settings (
+        key TEXT PRIMARY KEY,
+        value INTEGER NOT NULL
+      );",
+    )?;
+    let retention_secs = connection
+      .query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        params![RETENTION_SETTING],
+        |row| row.get::<_, i64>(0),
+      )
+      .optional()?
+      .map(|v| v as u64)
+      .unwrap_or(DEFAULT_RETENTION_SECS);
+    Ok(MetricsStore {
+      connection,
+      profile: None,
+      retention_secs,
+    })
+  }
+
+  pub fn set_profile(&mut self, profile: String) {
+    self.profile = Some(profile);
+  }
+
+  pub fn get_retention_secs(&self) -> u64 {
+    self.retention_secs
+  }
+
+  pub fn set_retention_secs(&mut self, retention_secs: u64) -> Result<(), PError> {
+    self.connection.execute(
+      "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+      params![RETENTION_SETTING, retention_secs as i64],
+    )?;
+    self.retention_secs = retention_secs;
+    self.apply_retention(current_time_millis())
+  }
+
+  pub fn insert_rollup(&self, rollup: &MetricsRollup) -> Result<(), PError> {
+    self.connection.execute(
+      "INSERT OR REPLACE INTO metrics_rollup (
+        profile, timestamp, heartbeat_succeeded, heartbeat_failed, heartbeat_average_duration,
+        commands_started, commands_succeeded, commands_failed, command_average_duration,
+        server_count
+      ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+      params![
+        rollup.profile,
+        rollup.timestamp as i64,
+        rollup.heartbeat_succeeded as i64,
+        rollup.heartbeat_failed as i64,
+        rollup.heartbeat_average_duration as i64,
+        rollup.commands_started as i64,
+        rollup.commands_succeeded as i64,
+        rollup.commands_failed as i64,
+        rollup.command_average_duration as i64,
+        rollup.server_count as i64,
+      ],
+    )?;
+    Ok(())
+  }
+
+  /// Removes every rollup that is older than the retention relative to `now`.
+  pub fn apply_retention(&self, now: u64) -> Result<(), PError> {
+    let oldest = now.saturating_sub(self.retention_secs.saturating_mul(1000));
+    self.connection.execute(
+      "DELETE FROM metrics_rollup WHERE timestamp < ?1",
+      params![oldest as i64],
+    )?;
+    Ok(())
+  }
+
+  pub fn get_profiles(&self) -> Result<Vec<String>, PError> {
+    let mut statement = self
+      .connection
+      .prepare("SELECT DISTINCT profile FROM metrics_rollup ORDER BY profile")?;
+    let result = statement
+      .query_map([], |row| row.get(0))?
+      .collect::<Result<Vec<String>, _>>()?;
+    Ok(result)
+  }
+
+  /// Returns the rollups of `profile` within `[from, until]`, both in milliseconds since epoch.
+  pub fn get_rollups(
+    &self,
+    profile: &str,
+    from: u64,
+    until: u64,
+  ) -> Result<Vec<MetricsRollup>, PError> {
+    let mut statement = self.connection.prepare(
+      "SELECT
+        profile, timestamp, heartbeat_succeeded, heartbeat_failed, heartbeat_average_duration,
+        commands_started, commands_succeeded, commands_failed, command_average_duration,
+        server_count
+      FROM metrics_rollup
+      WHERE profile = ?1 AND timestamp >= ?2 AND timestamp <= ?3
+      ORDER BY timestamp",
+    )?;
+    let result = statement
+      .query_map(params![profile, from as i64, until as i64], |row| {
+        Ok(MetricsRollup {
+          profile: row.get(0)?,
+          timestamp: row.get::<_, i64>(1)? as u64,
+          heartbeat_succeeded: row.get::<_, i64>(2)? as u64,
+          heartbeat_failed: row.get::<_, i64>(3)? as u64,
+          heartbeat_average_duration: row.get::<_, i64>(4)? as u64,
+          commands_started: row.get::<_, i64>(5)? as u64,
+          commands_succeeded: row.get::<_, i64>(6)? as u64,
+          commands_failed: row.get::<_, i64>(7)? as u64,
+          command_average_duration: row.get::<_, i64>(8)? as u64,
+          server_count: row.get::<_, i64>(9)? as u64,
+        })
+      })?
+      .collect::<Result<Vec<_>, _>>()?;
+    Ok(result)
+  }
+}
+
+/// Periodically persists the in-memory metrics of the current connection into the store.
+pub fn spawn_rollup_thread(store: Arc<Mutex<Option<MetricsStore>>>) {
+  thread::spawn(move || loop {
+    thread::sleep(ROLLUP_INTERVAL);
+    let now = current_time_millis();
+    let handle = &*store.lock().unwrap();
+    let store = match handle {
+      Some(store) => store,
+      None => continue,
+    };
+    let profile = match &store.profile {
+      Some(profile) => profile.clone(),
+      None => continue,
+    };
+    let rollup = MetricsRollup::collect(profile, now);
+    if let Err(e) = store
+      .insert_rollup(&rollup)
+      .and_then(|_| store.apply_retention(now))
+    {
+      eprintln!(
+        "Failed to persist the metrics rollup:{:?} error:{}",
+        rollup, e
+      );
+    }
+  });
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn open_store() -> MetricsStore {
+    MetricsStore::from_connection(Connection::open_in_memory().unwrap()).unwrap()
+  }
+
+  fn rollup(profile: &str, timestamp: u64) -> MetricsRollup {
+    MetricsRollup {
+      profile: profile.to_string(),
+      timestamp,
+      heartbeat_succeeded: 1,
+      heartbeat_failed: 0,
+      heartbeat_average_duration: 2,
+      commands_started: 3,
+      commands_succeeded: 2,
+      commands_failed: 1,
+      command_average_duration: 4,
+      server_count: 3,
+    }
+  }
+
+  fn timestamps(store: &MetricsStore, profile: &str, from: u64, until: u64) -> Vec<u64> {
+    store
+      .get_rollups(profile, from, until)
+      .unwrap()
+      .iter()
+      .map(|rollup| rollup.timestamp)
+      .collect()
+  }
+
+  #[test]
+  fn inserted_rollups_are_read_back() {
+    let store = open_store();
+    store.insert_rollup(&rollup("a:1", 2000)).unwrap();
+    store.insert_rollup(&rollup("a:1", 1000)).unwrap();
+    store.insert_rollup(&rollup("b:1", 1000)).unwrap();
+    // The same interval is only stored once.
+    store.insert_rollup(&rollup("b:1", 1000)).unwrap();
+
+    let rollups = store.get_rollups("a:1", 0, 5000).unwrap();
+    assert_eq!(
+      rollups.iter().map(|r| r.timestamp).collect::<Vec<_>>(),
+      vec![1000, 2000]
+    );
+    assert_eq!(rollups[0].commands_failed, 1);
+    assert_eq!(rollups[0].server_count, 3);
+    assert_eq!(store.get_profiles().unwrap(), vec!["a:1", "b:1"]);
+  }
+
+  #[test]
+  fn rollups_are_queried_within_the_range() {
+    let store = open_store();
+    for timestamp in [1000, 2000, 3000, 4000] {
+      store.insert_rollup(&rollup("a:1", timestamp)).unwrap();
+    }
+    assert_eq!(timestamps(&store, "a:1", 2000, 3000), vec![2000, 3000]);
+    assert_eq!(timestamps(&store, "a:1", 4001, 5000), Vec::<u64>::new());
+    assert_eq!(timestamps(&store, "b:1", 0, 5000), Vec::<u64>::new());
+  }
+
+  #[test]
+  fn retention_removes_the_older_rollups() {
+    let mut store = open_store();
+    assert_eq!(store.get_retention_secs(), DEFAULT_RETENTION_SECS);
+    store.retention_secs = 2;
+    for timestamp in [1000, 2000, 3000, 4000] {
+      store.insert_rollup(&rollup("a:1", timestamp)).unwrap();
+    }
+    store.apply_retention(4000).unwrap();
+    assert_eq!(timestamps(&store, "a:1", 0, 5000), vec![2000, 3000, 4000]);
+
+    // A retention too large to be expressed in milliseconds keeps everything.
+    store.retention_secs = u64::MAX;
+    store.apply_retention(4000).unwrap();
+    assert_eq!(timestamps(&store, "a:1", 0, 5000), vec![2000, 3000, 4000]);
+  }
+}
@@ -0,0 +1,366 @@
+use std::collections::HashMap;
+
+use mongodb::{
+  bson::{doc, Bson, Document},
+  options::FindOptions,
+  sync::Client,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  error::PError,
+  model::BsonType,
+  schema::{sample_documents, CollectionSchema, FieldSchema, SchemaAnalyzer, ARRAY_ELEMENTS},
+};
+
+/// Fields present in at least this percentage of their parent objects are required by default.
+pub const DEFAULT_REQUIRED_THRESHOLD: f64 = 100.0;
+
+fn type_count(field: &FieldSchema, bson_type: BsonType) -> u64 {
+  field
+    .types
+    .iter()
+    .find(|t| t.bson_type == bson_type)
+    .map(|t| t.count)
+    .unwrap_or(0)
+}
+
+struct JsonSchemaGenerator<'a> {
+  fields: HashMap<&'a [String], &'a FieldSchema>,
+  children: HashMap<Option<&'a [String]>, Vec<&'a FieldSchema>>,
+  required_threshold: f64,
+}
+
+impl<'a> JsonSchemaGenerator<'a> {
+  fn new(schema: &'a CollectionSchema, required_threshold: f64) -> Self {
+    let mut children: HashMap<Option<&[String]>, Vec<&FieldSchema>> = HashMap::new();
+    for field in &schema.fields {
+      children
+        .entry(field.parent_segments())
+        .or_default()
+        .push(field);
+    }
+    JsonSchemaGenerator {
+      fields: schema
+        .fields
+        .iter()
+        .map(|f| (f.segments.as_slice(), f))
+        .collect(),
+      children,
+      required_threshold,
+    }
+  }
+
+  /// `properties` and `required` of the objects at `segments`, the root documents for `None`.
+  fn object_schema(&self, segments: Option<&[String]>, object_count: u64) -> Document {
+    let mut properties = Document::new();
+    let mut required = Vec::new();
+    for field in self.children.get(&segments).into_iter().flatten() {
+      let name = field.key();
+      if name == ARRAY_ELEMENTS {
+        continue;
+      }
+      properties.insert(name, self.field_schema(field));
+      if field.values as f64 * 100.0 / object_count.max(1) as f64 >= self.required_threshold {
+        required.push(name);
+      }
+    }
+    let mut result = doc! { "bsonType": "object" };
+    if !required.is_empty() {
+      result.insert("required", required);
+    }
+    result.insert("properties", properties);
+    result
+  }
+
+  fn field_schema(&self, field: &FieldSchema) -> Document {
+    let mut aliases = field
+      .types
+      .iter()
+      .map(|t| t.bson_type.alias())
+      .collect::<Vec<_>>();
+    let mut result = if aliases.len() == 1 {
+      doc! { "bsonType": aliases.remove(0) }
+    } else {
+      doc! { "bsonType": aliases }
+    };
+    let object_count = type_count(field, BsonType::Document);
+    if object_count > 0 {
+      let object_schema = self.object_schema(Some(&field.segments), object_count);
+      for key in ["required", "properties"] {
+        if let Some(value) = object_schema.get(key) {
+          result.insert(key, value.clone());
+        }
+      }
+    }
+    if let Some(items) = self.fields.get(field.items_segments().as_slice()) {
+      result.insert("items", self.field_schema(items));
+    }
+    result
+  }
+}
+
+/// Turns the observed schema into a validator, the types of a field become a `bsonType` union.
+pub fn generate_validator(schema: &CollectionSchema, required_threshold: f64) -> Document {
+  let generator = JsonSchemaGenerator::new(schema, required_threshold);
+  doc! { "$jsonSchema": generator.object_schema(None, schema.document_count) }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValidatorPreview {
+  pub validator: Document,
+  /// Number of sampled documents, the validator is generated from them and tried against them.
+  pub document_count: u64,
+  pub failing_count: u64,
+}
+
+/// Counts how many of the documents with the `ids` do not pass `validator`.
+pub fn count_failing_documents(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  ids: Vec<Bson>,
+  validator: &Document,
+) -> Result<u64, PError> {
+  let filter = doc! { "_id": { "$in": ids }, "$nor": [validator] };
+  let result = client
+    .database(database_name)
+    .collection::<Document>(collection_name)
+    .count_documents(filter, None)?;
+  Ok(result)
+}
+
+pub fn preview_validator(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  documents_filter: Document,
+  sample_size: i64,
+  required_threshold: f64,
+) -> Result<ValidatorPreview, PError> {
+  let documents = sample_documents(
+    client,
+    database_name,
+    collection_name,
+    documents_filter,
+    sample_size,
+  )?;
+  let mut analyzer = SchemaAnalyzer::default();
+  for document in &documents {
+    analyzer.add(document);
+  }
+  let schema = analyzer.into_schema(sample_size);
+  let validator = generate_validator(&schema, required_threshold);
+  let ids = documents
+    .iter()
+    .filter_map(|document| document.get("_id").cloned())
+    .collect();
+  let failing_count =
+    count_failing_documents(client, database_name, collection_name, ids, &validator)?;
+  Ok(ValidatorPreview {
+    validator,
+    document_count: documents.len() as u64,
+    failing_count,
+  })
+}
+
+/// Replaces the validator of the collection with `collMod`.
+pub fn apply_validator(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  validator: Document,
+  validation_level: &str,
+  validation_action: &str,
+) -> Result<Document, PError> {
+  let command = doc! {
+    "collMod": collection_name,
+    "validator": validator,
+    "validationLevel": validation_level,
+    "validationAction": validation_action,
+  };
+  let result = client.database(database_name).run_command(command, None)?;
+  Ok(result)
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValidationRules {
+  pub validator: Option<Document>,
+  /// `off`, `strict` or `moderate`.
+  pub validation_level: Option<String>,
+  /// `error` or `warn`.
+  pub validation_action: Option<String>,
+}
+
+pub fn get_validation_rules(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+) -> Result<ValidationRules, PError> {
+  let command = doc! { "listCollections": 1, "filter": { "name": collection_name } };
+  let result = client.database(database_name).run_command(command, None)?;
+  let options = result
+    .get_document("cursor")
+    .and_then(|cursor| cursor.get_array("firstBatch"))
+    .ok()
+    .and_then(|batch| batch.first())
+    .and_then(Bson::as_document)
+    .and_then(|collection| collection.get_document("options").ok())
+    .cloned()
+    .unwrap_or_default();
+  let get_string = |key: &str| options.get_str(key).ok().map(|v| v.to_string());
+  Ok(ValidationRules {
+    validator: options.get_document("validator").ok().cloned(),
+    validation_level: get_string("validationLevel"),
+    validation_action: get_string("validationAction"),
+  })
+}
+
+/// Splits a validator into one check per field, so that the failing fields can be told apart.
+fn field_checks(validator: &Document) -> Vec<(String, Document)> {
+  match validator.get_document("$jsonSchema") {
+    Ok(json_schema) => {
+      let mut result = Vec::new();
+      json_schema_checks(json_schema, "", &mut |path, check| {
+        result.push((path, doc! { "$jsonSchema": check }))
+      });
+      result
+    }
+    // A query validator, e.g. `{ "age": { "$gte": 0 }, "$or": [...] }`.
+    Err(_) => validator
+      .iter()
+      .map(|(key, value)| (key.clone(), doc! { key: value.clone() }))
+      .collect(),
+  }
+}
+
+/// Calls `add` with a schema checking only one of the properties, for every nested property.
+fn json_schema_checks(json_schema: &Document, prefix: &str, add: &mut dyn FnMut(String, Document)) {
+  let properties = json_schema
+    .get_document("properties")
+    .cloned()
+    .unwrap_or_default();
+  let required = json_schema
+    .get_array("required")
+    .map(|required| required.iter().filter_map(Bson::as_str).collect::<Vec<_>>())
+    .unwrap_or_default();
+  for (name, property) in &properties {
+    let property = match property.as_document() {
+      Some(property) => property,
+      None => continue,
+    };
+    let path = format!("{}{}", prefix, name);
+    // Checks the property alone, without the schemas of its nested properties.
+    let mut property_check = property.clone();
+    property_check.remove("properties");
+    property_check.remove("required");
+    let mut check = doc! { "properties": { name: property_check } };
+    if required.contains(&name.as_str()) {
+      check.insert("required", vec![name.as_str()]);
+    }
+    add(path.clone(), check);
+    // The nested checks are wrapped into the schema of their parent.
+    json_schema_checks(
+      property,
+      &format!("{}.", path),
+      &mut |nested_path, nested_check| {
+        add(nested_path, doc! { "properties": { name: nested_check } })
+      },
+    );
+  }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ViolatingDocument {
+  pub document: Document,
+  /// Dotted paths of the fields that do not pass the validator.
+  pub failing_paths: Vec<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValidationViolations {
+  pub count: u64,
+  pub examples: Vec<ViolatingDocument>,
+}
+
+/// Finds the documents that do not pass `validator` by querying for `{ "$nor": [validator] }`, and
+/// returns `limit` of them as examples.
+pub fn find_validation_violations(
+  client: &Client,
+  database_name: &str,
+  collection_name: &str,
+  validator: &Document,
+  limit: i64,
+) -> Result<ValidationViolations, PError> {
+  // The server reads a limit of 0, or a negative one, as no limit at all.
+  if limit <= 0 {
+    return Err(PError::InvalidArgument(
+      "the number of examples must be positive".to_string(),
+    ));
+  }
+  let collection = client
+    .database(database_name)
+    .collection::<Document>(collection_name);
+  let filter = doc! { "$nor": [validator] };
+  let count = collection.count_documents(filter.clone(), None)?;
+  let find_options = FindOptions::builder().limit(limit).build();
+  let documents = collection
+    .find(filter, find_options)
+    .and_then(|cursor| cursor.collect::<Result<Vec<_>, _>>())?;
+  let ids = documents
+    .iter()
+    .filter_map(|document| document.get("_id").cloned())
+    .collect::<Vec<_>>();
+  let mut failing_paths: HashMap<String, Vec<String>> = HashMap::new();
+  for (path, check) in field_checks(validator) {
+    let filter = doc! { "_id": { "$in": ids.clone() }, "$nor": [check] };
+    let find_options = FindOptions::builder().projection(doc! { "_id": 1 }).build();
+    for document in collection.find(filter, find_options)? {
+      if let Some(id) = document?.get("_id") {
+        failing_paths
+          .entry(id.to_string())
+          .or_default()
+          .push(path.clone());
+      }
+    }
+  }
+  let examples = documents
+    .into_iter()
+    .map(|document| ViolatingDocument {
+      failing_paths: document
+        .get("_id")
+        .and_then(|id| failing_paths.remove(&id.to_string()))
+        .unwrap_or_default(),
+      document,
+    })
+    .collect();
+  Ok(ValidationViolations { count, examples })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn keys_containing_a_dot_are_not_nested() {
+    let mut analyzer = SchemaAnalyzer::default();
+    analyzer.add(&doc! { "a.b": 1, "a": { "b": "x" } });
+    let validator = generate_validator(&analyzer.into_schema(1), DEFAULT_REQUIRED_THRESHOLD);
+    let properties = validator
+      .get_document("$jsonSchema")
+      .and_then(|schema| schema.get_document("properties"))
+      .unwrap();
+    assert_eq!(
+      properties.get_document("a.b").unwrap(),
+      &doc! { "bsonType": "int" }
+    );
+    assert_eq!(
+      properties.get_document("a").unwrap(),
+      &doc! {
+        "bsonType": "object",
+        "required": ["b"],
+        "properties": { "b": { "bsonType": "string" } },
+      }
+    );
+  }
+}
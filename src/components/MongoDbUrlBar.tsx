@@ -4,7 +4,7 @@ import { isEmpty } from "lodash";
 
 import { VALUE_STATES, DatabaseSpecification, DISPLAY_TYPES } from "../types";
 import { AppState } from "../App";
-import { mongodb_connect } from "../util";
+import { mongodb_connect, mongodb_list_collections } from "../util";
 import { DOCUMENTS_TAB_INITIATE_STATE } from "./DocumentsTab";
 import {
   AGGREGATE_TAB_STAGE_INPUT_INITIAL_STATE,
@@ -21,6 +21,8 @@ export type MongodbUrlBarProps = {
   collectionName: string | undefined;
 };
 
+const COLLECTIONS_PER_PAGE = 200;
+
 export const MONGODB_URL_BAR_INITIAL_STATE: MongodbUrlBarProps = {
   url: "localhost",
   port: 27017,
@@ -86,6 +88,62 @@ export const MongoDbUrlBar = ({
     f();
   }, [url, port, status, setState]);
 
+  const collections = databaseName
+    ? databases[databaseName]?.collections
+    : undefined;
+  const collectionsError = databaseName
+    ? databases[databaseName]?.error
+    : undefined;
+
+  const loadCollections = async (databaseName: string, page: number) => {
+    try {
+      const result = await mongodb_list_collections({
+        databaseName,
+        nameOnly: true,
+        withSizes: false,
+        page,
+        perPage: COLLECTIONS_PER_PAGE,
+      });
+      setState((state) => {
+        const database = state.databases[databaseName];
+        const loaded = page > 0 ? database.collections?.collections ?? [] : [];
+        return {
+          ...state,
+          databases: {
+            ...state.databases,
+            [databaseName]: {
+              ...database,
+              collections: {
+                ...result,
+                collections: [...loaded, ...result.collections],
+              },
+              error: undefined,
+            },
+          },
+        };
+      });
+    } catch (error) {
+      console.error(error);
+      setState((state) => ({
+        ...state,
+        databases: {
+          ...state.databases,
+          [databaseName]: {
+            ...state.databases[databaseName],
+            error: String(error),
+          },
+        },
+      }));
+    }
+  };
+
+  useEffect(() => {
+    if (databaseName && !collections && !collectionsError) {
+      loadCollections(databaseName, 0);
+    }
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, [databaseName, collections, collectionsError]);
+
   return (
     <div
       style={{
@@ -211,7 +269,15 @@ export const MongoDbUrlBar = ({
           {/* COLLECTIONS SELECT */}
           <>
             {!databaseName && <div></div>}
-            {databaseName && (
+            {databaseName && collectionsError && (
+              <div title={collectionsError}>Cannot list collections</div>
+            )}
+            {databaseName && !collectionsError && !collections && (
+              <Spinner animation="border" role="status">
+                <span className="visually-hidden">Loading...</span>
+              </Spinner>
+            )}
+            {databaseName && collections && (
               <select
                 name={collectionName}
                 onChange={(value) => {
@@ -230,13 +296,24 @@ export const MongoDbUrlBar = ({
                 }}
               >
                 {!collectionName && <option key={0} value={undefined}></option>}
-                {databases[databaseName].collections.map(({ name }) => (
+                {collections.collections.map(({ name }) => (
                   <option key={name} value={name}>
                     {name}
                   </option>
                 ))}
               </select>
             )}
+            {databaseName &&
+              collections &&
+              collections.collections.length < collections.total && (
+                <button
+                  onClick={() =>
+                    loadCollections(databaseName, collections.page + 1)
+                  }
+                >
+                  More
+                </button>
+              )}
           </>
         </div>
       </div>
@@ -1,5 +1,3 @@
-import { invoke } from "@tauri-apps/api";
-import { chain } from "lodash";
 import { useState, useEffect } from "react";
 import {
   Card,
@@ -12,17 +10,20 @@ import {
 
 import { VALUE_STATES } from "../types";
 import { AppState } from "../App";
+import { CollectionSchema, mongodb_analyze_documents } from "../util";
 
 export type SchemaTabProps = {
   status: VALUE_STATES;
   documentsFilter: Record<string, unknown>;
-  documents: Array<[string, [string, number][]]>;
+  sampleSize: number;
+  schema: CollectionSchema | undefined;
 };
 
 export const SCHEMA_TAB_INITIAL_STATE: SchemaTabProps = {
   status: VALUE_STATES.UNLOADED,
   documentsFilter: {},
-  documents: [],
+  sampleSize: 1000,
+  schema: undefined,
 };
 
 export const useSchemaTabState = () => {
@@ -41,7 +42,7 @@ export const SchemaTab = ({
       state: { databaseName, collectionName },
     },
     schemaTabState: {
-      state: { status, documents, documentsFilter },
+      state: { status, schema, documentsFilter, sampleSize },
       setState,
     },
   },
@@ -57,20 +58,18 @@ export const SchemaTab = ({
           setState((state) => ({
             ...state,
             status: VALUE_STATES.LOADING,
-            documents: [],
+            schema: undefined,
           }));
-          const result = await invoke<Array<[string, [string, number][]]>>(
-            "mongodb_analyze_documents",
-            {
-              databaseName,
-              collectionName,
-              documentsFilter,
-            }
-          );
+          const result = await mongodb_analyze_documents({
+            databaseName,
+            collectionName,
+            documentsFilter,
+            sampleSize,
+          });
           setState((state) => ({
             ...state,
             status: VALUE_STATES.LOADED,
-            documents: chain(result).sort().value(),
+            schema: result,
           }));
         } catch (e) {
           console.error(e);
@@ -79,7 +78,14 @@ export const SchemaTab = ({
       }
     };
     f();
-  }, [databaseName, collectionName, status, documentsFilter, setState]);
+  }, [
+    databaseName,
+    collectionName,
+    status,
+    documentsFilter,
+    sampleSize,
+    setState,
+  ]);
 
   return (
     <Stack
@@ -127,6 +133,32 @@ export const SchemaTab = ({
             }}
           />
         </InputGroup>
+        <InputGroup style={{ width: "250px" }}>
+          <InputGroup.Text
+            style={{
+              height: "30px",
+            }}
+          >
+            Sample
+          </InputGroup.Text>
+          <FormControl
+            style={{
+              height: "30px",
+            }}
+            type="number"
+            defaultValue={sampleSize}
+            disabled={inputDisabled}
+            onChange={(e) => {
+              const sampleSize = parseInt(e.target.value, 10);
+              if (sampleSize > 0) {
+                setState((state) => ({
+                  ...state,
+                  sampleSize,
+                }));
+              }
+            }}
+          />
+        </InputGroup>
         <button
           disabled={inputDisabled}
           onClick={() =>
@@ -151,7 +183,7 @@ export const SchemaTab = ({
             <span className="visually-hidden">Loading...</span>
           </Spinner>
         )}
-        {status === VALUE_STATES.LOADED && (
+        {status === VALUE_STATES.LOADED && schema && (
           <div
             style={{
               display: "flex",
@@ -159,22 +191,24 @@ export const SchemaTab = ({
               rowGap: "5px",
             }}
           >
-            {documents.map(([key, document]) => (
-              <div key={key}>
+            {schema.fields.map((field) => (
+              <div key={field.path}>
                 <Card
                   style={{
                     display: "flex",
                     flexDirection: "column",
                   }}
                 >
-                  <Card.Header>{key}</Card.Header>
+                  <Card.Header>
+                    {field.path} ({field.presence.toFixed(1)}% present,{" "}
+                    {field.cardinality}
+                    {field.cardinality_capped && "+"} distinct)
+                  </Card.Header>
                   <Card.Body>
                     <ProgressBar>
-                      {document.map(([key, v], idx) => (
+                      {field.types.map(({ bson_type: key, count }, idx) => (
                         <ProgressBar
-                          now={
-                            (v / chain(document).map("1").sum().value()) * 100
-                          }
+                          now={(count / field.values) * 100}
                           variant={
                             PROGRESS_BAR_VARIANT[
                               idx % PROGRESS_BAR_VARIANT.length
@@ -18,10 +18,18 @@ export type ServerInfoProps = {
     last_update_time: string | undefined;
     max_wire_version: number | undefined;
     min_wire_version: number | undefined;
-    replicat_set_name: string | undefined;
-    replicate_set_version: number | undefined;
+    replica_set_name: string | undefined;
+    replica_set_version: number | undefined;
     server_type: string;
     tags: Record<string, string> | undefined;
+    error: string | undefined;
+    election_id: string | undefined;
+    primary: string | undefined;
+    hosts: string[];
+    passives: string[];
+    arbiters: string[];
+    logical_session_timeout_minutes: number | undefined;
+    stale: boolean;
   }[];
   heartbeat: [number, number][];
 };
@@ -66,7 +74,7 @@ export const ServerInfo = ({
             const result = await mongodb_get_database_topology();
             setState((state) => ({
               ...state,
-              servers: result,
+              servers: result.servers,
             }));
           }
         } catch (e) {
@@ -3,15 +3,26 @@ import React from "react";
 export type ReactSetState<T> = React.Dispatch<React.SetStateAction<T>>;
 export type ReactState<T> = [T, ReactSetState<T>];
 
+export type CollectionPage = Readonly<{
+  collections: {
+    name: string;
+    type?: string;
+    count?: number;
+    size?: number;
+    storageSize?: number;
+  }[];
+  page: number;
+  perPage: number;
+  total: number;
+}>;
+
 export type DatabaseSpecification = Readonly<{
   name: string;
   sizeOnDisk: number;
   empty: boolean;
   shards?: Record<string, unknown>;
-  collections: {
-    name: string;
-    type: string;
-  }[];
+  collections?: CollectionPage;
+  error?: string;
 }>;
 
 export type BsonDocument = Readonly<Record<string, unknown>>;
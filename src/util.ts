@@ -1,7 +1,7 @@
 import { useState, useEffect } from "react";
 import { invoke } from "@tauri-apps/api";
 
-import { BsonDocument, DatabaseSpecification } from "./types";
+import { BsonDocument, CollectionPage, DatabaseSpecification } from "./types";
 import { ServerInfoProps } from "./components/ServerInfo";
 
 async function apiCall<O>(
@@ -64,9 +64,314 @@ export const mongodb_aggregate_documents = async ({
     ],
   });
 
-export const mongodb_connect = async (args: { url: string; port: number }) =>
+export type StagePreview = {
+  index: number;
+  operator: string;
+  documents: BsonDocument[];
+  count: number | null;
+  count_capped: boolean;
+  duration: number;
+  count_duration: number | null;
+  skipped: boolean;
+  error: unknown;
+};
+
+export const mongodb_preview_pipeline = async ({
+  databaseName,
+  collectionName,
+  sampleCount,
+  previewSize,
+  withCount,
+  countLimit,
+  stages,
+}: {
+  databaseName: string;
+  collectionName: string;
+  sampleCount: number;
+  previewSize?: number;
+  withCount: boolean;
+  countLimit?: number;
+  stages: { stageBody: string; stageOperation: string }[];
+}) =>
+  apiCall<StagePreview[]>("mongodb_preview_pipeline", {
+    databaseName,
+    collectionName,
+    options: {
+      sampleSize: sampleCount,
+      previewSize,
+      withCount,
+      countLimit,
+    },
+    stages: stages.map(({ stageBody, stageOperation }) => ({
+      [stageOperation]: JSON.parse(stageBody),
+    })),
+  });
+
+export type PipelineIssue = {
+  stage: number;
+  pointer: string;
+  severity: "error" | "warning";
+  message: string;
+};
+
+export const mongodb_lint_pipeline = async ({
+  databaseName,
+  stages,
+}: {
+  databaseName: string;
+  stages: { stageBody: string; stageOperation: string }[];
+}) =>
+  apiCall<PipelineIssue[]>("mongodb_lint_pipeline", {
+    databaseName,
+    stages: stages.map(({ stageBody, stageOperation }) => ({
+      [stageOperation]: JSON.parse(stageBody),
+    })),
+  });
+
+export const mongodb_connect = async (args: {
+  url: string;
+  port: number;
+  maxPoolSize?: number;
+  minPoolSize?: number;
+}) =>
   apiCall<Record<string, DatabaseSpecification>>("mongodb_connect", args);
 
+export const mongodb_list_collections = async (args: {
+  databaseName: string;
+  nameOnly: boolean;
+  withSizes: boolean;
+  page: number;
+  perPage: number;
+}) => apiCall<CollectionPage>("mongodb_list_collections", args);
+
+export const mongodb_refresh_database = async (args: {
+  databaseName: string;
+  nameOnly: boolean;
+  withSizes: boolean;
+  perPage: number;
+}) =>
+  apiCall<DatabaseSpecification | null>("mongodb_refresh_database", args);
+
+export interface IndexStatistics {
+  name: string;
+  size: number;
+  prefix_compression?: boolean;
+}
+
+export interface CollectionStatistics {
+  namespace: string;
+  count: number;
+  avg_obj_size: number;
+  size: number;
+  storage_size: number;
+  free_storage_size?: number;
+  total_index_size: number;
+  indexes: IndexStatistics[];
+  block_compressor?: string;
+  capped: boolean;
+  max?: number;
+  max_size?: number;
+  timeseries?: BsonDocument;
+  shards: string[];
+}
+
+export interface DatabaseStatistics {
+  database_name: string;
+  collections: number;
+  views: number;
+  objects: number;
+  avg_obj_size: number;
+  data_size: number;
+  storage_size: number;
+  free_storage_size?: number;
+  indexes: number;
+  index_size: number;
+  total_size?: number;
+  fs_used_size?: number;
+  fs_total_size?: number;
+}
+
+export interface ValueCount {
+  value: unknown;
+  count: number;
+}
+
+export interface TypeStatistics {
+  bson_type: string;
+  count: number;
+  min?: unknown;
+  max?: unknown;
+  top_values: ValueCount[];
+}
+
+export interface FieldSchema {
+  path: string;
+  segments: string[];
+  count: number;
+  presence: number;
+  values: number;
+  types: TypeStatistics[];
+  cardinality: number;
+  cardinality_capped: boolean;
+  string_lengths?: {
+    min: number;
+    max: number;
+    mean: number;
+    histogram: { min_length: number; max_length: number; count: number }[];
+  };
+  date_range?: { min: number; max: number };
+}
+
+export interface CollectionSchema {
+  sample_size: number;
+  document_count: number;
+  fields: FieldSchema[];
+}
+
+export const mongodb_analyze_documents = async (args: {
+  databaseName: string;
+  collectionName: string;
+  documentsFilter: Record<string, unknown>;
+  sampleSize?: number;
+}) => apiCall<CollectionSchema>("mongodb_analyze_documents", args);
+
+export interface ValidatorPreview {
+  validator: BsonDocument;
+  document_count: number;
+  failing_count: number;
+}
+
+export const mongodb_preview_validator = async (args: {
+  databaseName: string;
+  collectionName: string;
+  documentsFilter: Record<string, unknown>;
+  sampleSize?: number;
+  requiredThreshold?: number;
+}) => apiCall<ValidatorPreview>("mongodb_preview_validator", args);
+
+export const mongodb_apply_validator = async (args: {
+  databaseName: string;
+  collectionName: string;
+  validator: BsonDocument;
+  validationLevel: "off" | "strict" | "moderate";
+  validationAction: "error" | "warn";
+  confirmed: boolean;
+}) => apiCall<BsonDocument>("mongodb_apply_validator", args);
+
+export interface ValidationRules {
+  validator?: BsonDocument;
+  validation_level?: "off" | "strict" | "moderate";
+  validation_action?: "error" | "warn";
+}
+
+export interface ValidationViolations {
+  count: number;
+  examples: { document: BsonDocument; failing_paths: string[] }[];
+}
+
+export const mongodb_get_validation_rules = async (args: {
+  databaseName: string;
+  collectionName: string;
+}) => apiCall<ValidationRules>("mongodb_get_validation_rules", args);
+
+export const mongodb_find_validation_violations = async (args: {
+  databaseName: string;
+  collectionName: string;
+  validator: BsonDocument;
+  limit: number;
+}) =>
+  apiCall<ValidationViolations>("mongodb_find_validation_violations", args);
+
+export interface SchemaSnapshotSummary {
+  id: string;
+  name: string;
+  database_name: string;
+  collection_name: string;
+  created_at: number;
+  document_count: number;
+}
+
+export interface SchemaDrift {
+  added_fields: string[];
+  removed_fields: string[];
+  type_changes: {
+    path: string;
+    added_types: string[];
+    removed_types: string[];
+  }[];
+  presence_changes: { path: string; before: number; after: number }[];
+}
+
+export const mongodb_save_schema_snapshot = async (args: {
+  name: string;
+  databaseName: string;
+  collectionName: string;
+  documentsFilter: Record<string, unknown>;
+  sampleSize?: number;
+}) => apiCall<SchemaSnapshotSummary>("mongodb_save_schema_snapshot", args);
+
+export const mongodb_list_schema_snapshots = async () =>
+  apiCall<SchemaSnapshotSummary[]>("mongodb_list_schema_snapshots", {});
+
+export const mongodb_delete_schema_snapshot = async (args: { id: string }) =>
+  apiCall<void>("mongodb_delete_schema_snapshot", args);
+
+export const mongodb_compare_schema_snapshots = async (args: {
+  beforeId: string;
+  afterId: string;
+  presenceThreshold?: number;
+}) => apiCall<SchemaDrift>("mongodb_compare_schema_snapshots", args);
+
+export const mongodb_generate_types = async (args: {
+  databaseName: string;
+  collectionName: string;
+  documentsFilter: Record<string, unknown>;
+  sampleSize?: number;
+  language: "rust" | "typescript" | "go";
+}) =>
+  apiCall<{ file_name: string; content: string }>(
+    "mongodb_generate_types",
+    args
+  );
+
+export type DistributionKind = "top_values" | "numeric" | "date";
+export type DateUnit = "day" | "week" | "month";
+
+export type ValueDistribution =
+  | { kind: "top_values"; values: ValueCount[] }
+  | {
+      kind: "numeric";
+      buckets: { min: unknown; max: unknown; count: number }[];
+    }
+  | {
+      kind: "date";
+      unit: DateUnit;
+      buckets: { label: string; count: number }[];
+    };
+
+export const mongodb_get_value_distribution = async (args: {
+  databaseName: string;
+  collectionName: string;
+  documentsFilter: Record<string, unknown>;
+  options: {
+    path: string;
+    kind: DistributionKind;
+    top_n?: number;
+    buckets?: number;
+    date_unit?: DateUnit;
+  };
+}) => apiCall<ValueDistribution>("mongodb_get_value_distribution", args);
+
+export const mongodb_get_collection_statistics = async (args: {
+  databaseName: string;
+  collectionName: string;
+}) =>
+  apiCall<CollectionStatistics>("mongodb_get_collection_statistics", args);
+
+export const mongodb_get_database_statistics = async (args: {
+  databaseName: string;
+}) => apiCall<DatabaseStatistics>("mongodb_get_database_statistics", args);
+
 export const mongodb_find_documents = async (args: {
   databaseName: string;
   collectionName: string;
@@ -84,7 +389,14 @@ export const mongodb_count_documents = async (args: {
 }) => apiCall<number>("mongodb_count_documents", args);
 
 export const mongodb_get_database_topology = async () =>
-  apiCall<ServerInfoProps["servers"]>("mongodb_get_database_topology", {});
+  apiCall<{
+    topology_type: string;
+    set_name: string | undefined;
+    max_set_version: number | undefined;
+    max_election_id: string | undefined;
+    compatibility_error: string | undefined;
+    servers: ServerInfoProps["servers"];
+  }>("mongodb_get_database_topology", {});
 
 export const mongodb_get_connection_heartbeat = async () =>
   apiCall<ServerInfoProps["heartbeat"]>("mongodb_get_connection_heartbeat", {});
@@ -99,3 +411,410 @@ export const mongodb_get_commands_statistics_per_sec = async (args: {
     "mongodb_get_commands_statistics_per_sec",
     args
   );
+
+export type MetricsRollup = Readonly<{
+  profile: string;
+  timestamp: number;
+  heartbeat_succeeded: number;
+  heartbeat_failed: number;
+  heartbeat_average_duration: number;
+  commands_started: number;
+  commands_succeeded: number;
+  commands_failed: number;
+  command_average_duration: number;
+  server_count: number;
+}>;
+
+export const mongodb_get_metrics_profiles = async () =>
+  apiCall<string[]>("mongodb_get_metrics_profiles", {});
+
+export const mongodb_get_metrics_history = async (args: {
+  profile: string;
+  from: number;
+  until: number;
+}) => apiCall<MetricsRollup[]>("mongodb_get_metrics_history", args);
+
+export const mongodb_get_metrics_retention = async () =>
+  apiCall<number>("mongodb_get_metrics_retention", {});
+
+export const mongodb_set_metrics_retention = async (args: {
+  retentionSecs: number;
+}) => apiCall<void>("mongodb_set_metrics_retention", args);
+
+export const mongodb_start_prometheus_exporter = async (args: {
+  port: number;
+}) => apiCall<void>("mongodb_start_prometheus_exporter", args);
+
+export const mongodb_stop_prometheus_exporter = async () =>
+  apiCall<void>("mongodb_stop_prometheus_exporter", {});
+
+export const mongodb_get_prometheus_exporter_port = async () =>
+  apiCall<number | null>("mongodb_get_prometheus_exporter_port", {});
+
+export type HeartbeatRecord = Readonly<{
+  timestamp: number;
+  duration: number;
+  error: string | null;
+  awaited: boolean;
+}>;
+
+export type HeartbeatStatistics = Readonly<{
+  succeeded: number;
+  failed: number;
+  min_duration: number;
+  average_duration: number;
+  max_duration: number;
+  jitter: number;
+  current_failure_streak: number;
+  longest_failure_streak: number;
+  last_error: string | null;
+}>;
+
+export const mongodb_get_server_heartbeats = async () =>
+  apiCall<
+    Record<
+      string,
+      {
+        heartbeats: HeartbeatRecord[];
+        statistics: HeartbeatStatistics;
+        in_progress_since: number | null;
+      }
+    >
+  >("mongodb_get_server_heartbeats", {});
+
+export type TopologyEvent = Readonly<{
+  timestamp: number;
+  kind: Record<string, Record<string, unknown>>;
+}>;
+
+export const MONGODB_TOPOLOGY_FAILOVER_EVENT = "mongodb_topology_failover";
+
+export const mongodb_get_topology_timeline = async (args: { since: number }) =>
+  apiCall<TopologyEvent[]>("mongodb_get_topology_timeline", args);
+
+export type ConnectionPoolStatistics = Readonly<{
+  max_pool_size: number;
+  min_pool_size: number;
+  ready: boolean;
+  closed: boolean;
+  total_connections: number;
+  checked_out: number;
+  utilization: number;
+  connections_created: number;
+  connections_closed: number;
+  checkouts_started: number;
+  checkouts_succeeded: number;
+  checkouts_failed: number;
+  pool_cleared: number;
+  checkout_wait_time: { p50: number; p90: number; p99: number; max: number };
+  checkout_failures: Record<string, number>;
+  pool_cleared_incidents: {
+    timestamp: number;
+    service_id: string | null;
+    checked_out: number;
+  }[];
+}>;
+
+export const mongodb_get_connection_pools = async () =>
+  apiCall<Record<string, ConnectionPoolStatistics>>(
+    "mongodb_get_connection_pools",
+    {}
+  );
+
+export type ServerStatusSample = Readonly<{
+  timestamp: number;
+  interval: number;
+  opcounters: Record<
+    "insert" | "query" | "update" | "delete" | "getmore" | "command",
+    number
+  >;
+  connections: { current: number; available: number; total_created: number };
+  network: { bytes_in: number; bytes_out: number; num_requests: number };
+  wired_tiger_cache: {
+    bytes_currently_in_cache: number;
+    maximum_bytes_configured: number;
+    tracked_dirty_bytes: number;
+    pages_read_into_cache: number;
+    pages_written_from_cache: number;
+  } | null;
+  tickets: {
+    read_out: number;
+    read_available: number;
+    write_out: number;
+    write_available: number;
+  } | null;
+  asserts: Record<"regular" | "warning" | "msg" | "user" | "rollovers", number>;
+}>;
+
+export const mongodb_get_server_status_series = async (args: {
+  count: number;
+}) =>
+  apiCall<[ServerStatusSample[], string | null]>(
+    "mongodb_get_server_status_series",
+    args
+  );
+
+export const mongodb_get_server_status_interval = async () =>
+  apiCall<number | null>("mongodb_get_server_status_interval", {});
+
+export const mongodb_set_server_status_interval = async (args: {
+  intervalMillis: number | null;
+}) => apiCall<void>("mongodb_set_server_status_interval", args);
+
+export type CurrentOpFilter = {
+  active_only: boolean;
+  min_secs_running?: number;
+  namespace?: string;
+  op_type?: string;
+  client?: string;
+};
+
+export const mongodb_get_current_operations = async (args: {
+  filter: CurrentOpFilter;
+}) => apiCall<BsonDocument[]>("mongodb_get_current_operations", args);
+
+export const mongodb_kill_operation = async (args: {
+  opid: unknown;
+  confirmed: boolean;
+}) => apiCall<BsonDocument>("mongodb_kill_operation", args);
+
+export type QueryShapeStatistics = Readonly<{
+  command_name: string;
+  namespace: string;
+  shape: BsonDocument;
+  count: number;
+  total_millis: number;
+  mean_millis: number;
+  max_millis: number;
+  docs_examined: number | null;
+  n_returned: number | null;
+  docs_examined_per_returned: number | null;
+  plan_summary: string | null;
+}>;
+
+export type ProfilingStatus = Readonly<{
+  level: number;
+  slowms: number;
+  sample_rate: number;
+}>;
+
+export type ProfileFilter = {
+  namespace?: string;
+  op?: string;
+  min_millis?: number;
+  since?: number;
+  until?: number;
+};
+
+export const mongodb_get_commands_by_shape = async () =>
+  apiCall<QueryShapeStatistics[]>("mongodb_get_commands_by_shape", {});
+
+export const mongodb_get_profiling_status = async (args: {
+  databaseName: string;
+}) => apiCall<ProfilingStatus>("mongodb_get_profiling_status", args);
+
+export const mongodb_set_profiling_status = async (args: {
+  databaseName: string;
+  level: number;
+  slowms?: number;
+}) => apiCall<ProfilingStatus>("mongodb_set_profiling_status", args);
+
+export const mongodb_find_profile_entries = async (args: {
+  databaseName: string;
+  filter: ProfileFilter;
+  limit: number;
+}) => apiCall<BsonDocument[]>("mongodb_find_profile_entries", args);
+
+export const mongodb_analyze_profile_entries = async (args: {
+  databaseName: string;
+  filter: ProfileFilter;
+}) =>
+  apiCall<QueryShapeStatistics[]>("mongodb_analyze_profile_entries", args);
+
+export const mongodb_get_replica_set_status = async () =>
+  apiCall<BsonDocument>("mongodb_get_replica_set_status", {});
+
+export const mongodb_get_replica_set_config = async () =>
+  apiCall<BsonDocument>("mongodb_get_replica_set_config", {});
+
+export const mongodb_set_replica_set_write_mode = async (args: {
+  enabled: boolean;
+}) => apiCall<void>("mongodb_set_replica_set_write_mode", args);
+
+export const mongodb_step_down_primary = async (args: {
+  stepDownSecs: number;
+  secondaryCatchUpPeriodSecs?: number;
+}) => apiCall<BsonDocument>("mongodb_step_down_primary", args);
+
+export const mongodb_freeze_member = async (args: {
+  host: string;
+  freezeSecs: number;
+}) => apiCall<BsonDocument>("mongodb_freeze_member", args);
+
+export interface Shard {
+  id: string;
+  host: string;
+  state?: number;
+  draining: boolean;
+  tags: string[];
+}
+
+export interface ShardedCollection {
+  namespace: string;
+  key: BsonDocument;
+  unique: boolean;
+  chunks: Record<string, number>;
+}
+
+export interface BalancerStatus {
+  mode: string;
+  in_balancer_round: boolean;
+  num_balancer_rounds: number;
+  active_migrations: BsonDocument[];
+}
+
+export interface ZoneRange {
+  zone: string;
+  namespace: string;
+  min: BsonDocument;
+  max: BsonDocument;
+}
+
+export const mongodb_list_shards = async () =>
+  apiCall<Shard[]>("mongodb_list_shards", {});
+
+export const mongodb_list_sharded_collections = async () =>
+  apiCall<ShardedCollection[]>("mongodb_list_sharded_collections", {});
+
+export const mongodb_get_balancer_status = async () =>
+  apiCall<BalancerStatus>("mongodb_get_balancer_status", {});
+
+export const mongodb_list_zones = async () =>
+  apiCall<ZoneRange[]>("mongodb_list_zones", {});
+
+export type SavedQueryBody =
+  | {
+      kind: "find";
+      filter: Record<string, unknown>;
+      projection?: Record<string, unknown>;
+      sort?: Record<string, unknown>;
+      collation?: Record<string, unknown>;
+    }
+  | {
+      kind: "aggregate";
+      stages: Record<string, unknown>[];
+      collation?: Record<string, unknown>;
+    };
+
+export type QueryParameter = {
+  name: string;
+  default?: unknown;
+  description?: string;
+};
+
+export type SavedQuery = {
+  name: string;
+  database_name: string;
+  collection_name: string;
+  tags: string[];
+  parameters: QueryParameter[];
+  query: SavedQueryBody;
+  created_at?: number;
+  updated_at?: number;
+};
+
+export const mongodb_list_saved_queries = async (args: { tag?: string }) =>
+  apiCall<SavedQuery[]>("mongodb_list_saved_queries", args);
+
+export const mongodb_get_saved_query = async (args: { name: string }) =>
+  apiCall<SavedQuery>("mongodb_get_saved_query", args);
+
+export const mongodb_save_query = async (args: { savedQuery: SavedQuery }) =>
+  apiCall<SavedQuery>("mongodb_save_query", args);
+
+export const mongodb_delete_saved_query = async (args: { name: string }) =>
+  apiCall<void>("mongodb_delete_saved_query", args);
+
+export const mongodb_export_saved_queries = async (args: { names: string[] }) =>
+  apiCall<string>("mongodb_export_saved_queries", args);
+
+export const mongodb_import_saved_queries = async (args: {
+  content: string;
+  overwrite: boolean;
+}) => apiCall<string[]>("mongodb_import_saved_queries", args);
+
+export const mongodb_run_saved_query = async (args: {
+  name: string;
+  arguments: Record<string, unknown>;
+  page: number;
+  perPage: number;
+}) => apiCall<BsonDocument[]>("mongodb_run_saved_query", args);
+
+export type HistoryQuery =
+  | {
+      kind: "find";
+      query: {
+        filter: Record<string, unknown>;
+        projection?: Record<string, unknown>;
+        sort?: Record<string, unknown>;
+        collation?: Record<string, unknown>;
+      };
+      page: number;
+      per_page: number;
+    }
+  | { kind: "count"; filter: Record<string, unknown> }
+  | {
+      kind: "aggregate";
+      query: {
+        stages: Record<string, unknown>[];
+        collation?: Record<string, unknown>;
+      };
+    };
+
+export type HistoryEntry = Readonly<{
+  id: number;
+  database_name: string;
+  collection_name: string;
+  query: HistoryQuery;
+  shape: Record<string, unknown>;
+  duration: number;
+  result_count: number | null;
+  error: string | null;
+  run_count: number;
+  pinned: boolean;
+  first_run_at: number;
+  last_run_at: number;
+}>;
+
+export type QueryOutput =
+  | { kind: "documents"; result: BsonDocument[] }
+  | { kind: "count"; result: number };
+
+export const mongodb_search_query_history = async (args: {
+  text?: string;
+  pinnedOnly: boolean;
+  limit?: number;
+}) => apiCall<HistoryEntry[]>("mongodb_search_query_history", args);
+
+export const mongodb_pin_query_history_entry = async (args: {
+  id: number;
+  pinned: boolean;
+}) => apiCall<void>("mongodb_pin_query_history_entry", args);
+
+export const mongodb_delete_query_history_entry = async (args: { id: number }) =>
+  apiCall<void>("mongodb_delete_query_history_entry", args);
+
+export const mongodb_rerun_query_history_entry = async (args: { id: number }) =>
+  apiCall<QueryOutput>("mongodb_rerun_query_history_entry", args);
+
+export type ShellSyntaxError = {
+  ShellSyntaxError: { message: string; line: number; column: number };
+};
+
+export const mongodb_parse_shell_syntax = async (args: { text: string }) =>
+  apiCall<Record<string, unknown>>("mongodb_parse_shell_syntax", args);
+
+export const mongodb_format_shell_syntax = async (args: {
+  document: Record<string, unknown>;
+  pretty: boolean;
+}) => apiCall<string>("mongodb_format_shell_syntax", args);